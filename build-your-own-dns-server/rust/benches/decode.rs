@@ -0,0 +1,54 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use dns_starter_rust::models::{
+    Class, DnsAnswer, DnsHeader, DnsPacket, DnsQuestion, Label, RData, RecordType,
+};
+use dns_starter_rust::traits::{Decodable, Encodable};
+use std::net::Ipv4Addr;
+
+// A response to `www.example.com A` with `answer_count` A records, encoded
+// with name compression like most responses seen in the wild.
+fn response_packet(answer_count: u8) -> Vec<u8> {
+    let mut query = vec![0x12, 0x34, 0x01, 0x00, 0x00, 0x01, 0, 0, 0, 0, 0, 0];
+    query.extend(Label::encode_name(
+        &Label::from_domain_name("www.example.com").unwrap(),
+    ));
+    query.extend([0x00, 0x01, 0x00, 0x01]);
+    let dns_request = DnsPacket::decode(&query).unwrap();
+    let dns_question: DnsQuestion = dns_request.dns_questions[0].clone();
+    let mut dns_header = DnsHeader::from_request_header(dns_request.dns_header);
+    dns_header.answer_record_count = answer_count as u16;
+    DnsPacket {
+        dns_header,
+        dns_questions: vec![dns_question.clone()],
+        dns_answers: (0..answer_count)
+            .map(|index| {
+                DnsAnswer::new(
+                    dns_question.labels.clone(),
+                    RecordType::A,
+                    Class::IN,
+                    300,
+                    RData::A(Ipv4Addr::new(192, 0, 2, index)),
+                )
+            })
+            .collect(),
+        dns_authorities: vec![],
+        dns_additionals: vec![],
+        edns: None,
+    }
+    .encode()
+}
+
+fn bench_decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode");
+    for answer_count in [1, 8, 32] {
+        let packet = response_packet(answer_count);
+        group.throughput(Throughput::Bytes(packet.len() as u64));
+        group.bench_function(format!("{}_answers", answer_count), |b| {
+            b.iter(|| DnsPacket::decode(black_box(&packet)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_decode);
+criterion_main!(benches);
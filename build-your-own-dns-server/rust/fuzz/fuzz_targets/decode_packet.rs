@@ -0,0 +1,19 @@
+// Decodes arbitrary bytes as a message, and whatever decodes has to encode
+// into something that decodes to the same message.
+#![no_main]
+
+use dns_starter_rust::models::DnsPacket;
+use dns_starter_rust::traits::Decodable;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(dns_packet) = DnsPacket::decode(data) {
+        let encoded = dns_packet.encode_with_compression(false);
+        let decoded = DnsPacket::decode(&encoded).expect("re-encoded message must decode");
+        assert_eq!(
+            decoded.encode_with_compression(false),
+            encoded,
+            "re-encoded message must be stable"
+        );
+    }
+});
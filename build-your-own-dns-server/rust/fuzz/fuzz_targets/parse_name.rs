@@ -0,0 +1,12 @@
+// Parses a name at every offset of arbitrary bytes, so compression pointers
+// loop, point past the end or into the middle of other names.
+#![no_main]
+
+use dns_starter_rust::models::Label;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    for offset in 0..data.len() {
+        let _ = Label::parse_name(data, &data[offset..]);
+    }
+});
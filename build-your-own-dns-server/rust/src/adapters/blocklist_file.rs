@@ -0,0 +1,46 @@
+use crate::models::blocklist::DomainSet;
+use anyhow::Context;
+use std::net::IpAddr;
+
+// Names found in the header of most hosts-format lists, which map the
+// machine's own names and must never be blocked.
+const HOSTS_FILE_DEFAULTS: [&str; 8] = [
+    "localhost",
+    "localhost.localdomain",
+    "local",
+    "broadcasthost",
+    "ip6-localhost",
+    "ip6-loopback",
+    "ip6-allnodes",
+    "ip6-allrouters",
+];
+
+pub fn read_domain_list(file_path: &str) -> anyhow::Result<DomainSet> {
+    let contents = std::fs::read_to_string(file_path)
+        .with_context(|| format!("Failed to read domain list '{}'", file_path))?;
+    Ok(parse_domain_list(&contents))
+}
+
+// Reads both hosts-format lines ("0.0.0.0 ads.example tracker.example") and
+// plain domain lists with one name or "*." wildcard per line, which may be
+// mixed in one file. "#" starts a comment.
+pub fn parse_domain_list(contents: &str) -> DomainSet {
+    let mut domains = DomainSet::new();
+    for line in contents.lines() {
+        let line = line.split('#').next().unwrap_or_default();
+        let mut tokens = line.split_whitespace().peekable();
+        let is_hosts_format = tokens
+            .peek()
+            .is_some_and(|token| token.parse::<IpAddr>().is_ok());
+        if is_hosts_format {
+            tokens.next();
+        }
+        for name in tokens {
+            if HOSTS_FILE_DEFAULTS.contains(&name.to_ascii_lowercase().as_str()) {
+                continue;
+            }
+            domains.insert(name);
+        }
+    }
+    domains
+}
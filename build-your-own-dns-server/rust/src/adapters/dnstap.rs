@@ -0,0 +1,164 @@
+use anyhow::Context;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::net::{IpAddr, SocketAddr};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Frame Streams content type announced in the START frame, readers such as
+// the dnstap command line tool only accept this one.
+// Source: https://github.com/farsightsec/fstrm/blob/master/fstrm/control.h
+const CONTENT_TYPE: &[u8] = b"protobuf:dnstap.Dnstap";
+const CONTROL_START: u32 = 0x02;
+const CONTROL_FIELD_CONTENT_TYPE: u32 = 0x01;
+
+// Enum values and field numbers from the dnstap schema.
+// Source: https://github.com/dnstap/dnstap.pb/blob/master/dnstap.proto
+const DNSTAP_TYPE_MESSAGE: u64 = 1;
+const MESSAGE_TYPE_CLIENT_QUERY: u64 = 5;
+const MESSAGE_TYPE_CLIENT_RESPONSE: u64 = 6;
+const SOCKET_FAMILY_INET: u64 = 1;
+const SOCKET_FAMILY_INET6: u64 = 2;
+const SOCKET_PROTOCOL_UDP: u64 = 1;
+const SOCKET_PROTOCOL_TCP: u64 = 2;
+
+// Protobuf wire types.
+// Source: https://protobuf.dev/programming-guides/encoding/#structure
+const WIRE_VARINT: u32 = 0;
+const WIRE_LENGTH_DELIMITED: u32 = 2;
+const WIRE_FIXED32: u32 = 5;
+
+// Writes client queries and responses as dnstap messages into a
+// unidirectional Frame Streams file.
+pub struct DnstapWriter {
+    writer: BufWriter<File>,
+    identity: Vec<u8>,
+}
+
+impl DnstapWriter {
+    pub fn create(file_path: &str, identity: &str) -> anyhow::Result<DnstapWriter> {
+        let file = File::create(file_path)
+            .with_context(|| format!("Failed to create dnstap file '{}'", file_path))?;
+        let mut dnstap_writer = DnstapWriter {
+            writer: BufWriter::new(file),
+            identity: identity.as_bytes().to_vec(),
+        };
+        dnstap_writer.write_start_frame()?;
+        return Ok(dnstap_writer);
+    }
+
+    // A control frame starts with an escape of four zero bytes where a data
+    // frame would have its length.
+    // Source: https://github.com/farsightsec/fstrm/blob/master/fstrm/control.h
+    fn write_start_frame(&mut self) -> anyhow::Result<()> {
+        let mut control = vec![];
+        control.extend_from_slice(&CONTROL_START.to_be_bytes());
+        control.extend_from_slice(&CONTROL_FIELD_CONTENT_TYPE.to_be_bytes());
+        control.extend_from_slice(&(CONTENT_TYPE.len() as u32).to_be_bytes());
+        control.extend_from_slice(CONTENT_TYPE);
+        self.writer.write_all(&0u32.to_be_bytes())?;
+        self.writer
+            .write_all(&(control.len() as u32).to_be_bytes())?;
+        self.writer.write_all(&control)?;
+        self.writer.flush()?;
+        return Ok(());
+    }
+
+    pub fn write_query(
+        &mut self,
+        client: SocketAddr,
+        is_tcp: bool,
+        query_time: SystemTime,
+        query: &[u8],
+    ) -> anyhow::Result<()> {
+        let mut message = socket_fields(client, is_tcp);
+        encode_varint_field(&mut message, 1, MESSAGE_TYPE_CLIENT_QUERY);
+        encode_time_fields(&mut message, 8, 9, query_time);
+        encode_bytes_field(&mut message, 10, query);
+        return self.write_frame(&message);
+    }
+
+    pub fn write_response(
+        &mut self,
+        client: SocketAddr,
+        is_tcp: bool,
+        query_time: SystemTime,
+        response_time: SystemTime,
+        response: &[u8],
+    ) -> anyhow::Result<()> {
+        let mut message = socket_fields(client, is_tcp);
+        encode_varint_field(&mut message, 1, MESSAGE_TYPE_CLIENT_RESPONSE);
+        encode_time_fields(&mut message, 8, 9, query_time);
+        encode_time_fields(&mut message, 12, 13, response_time);
+        encode_bytes_field(&mut message, 14, response);
+        return self.write_frame(&message);
+    }
+
+    // Wraps a Message in a Dnstap envelope and writes it as one data frame.
+    fn write_frame(&mut self, message: &[u8]) -> anyhow::Result<()> {
+        let mut dnstap = vec![];
+        encode_bytes_field(&mut dnstap, 1, &self.identity);
+        encode_bytes_field(&mut dnstap, 2, env!("CARGO_PKG_VERSION").as_bytes());
+        encode_bytes_field(&mut dnstap, 14, message);
+        encode_varint_field(&mut dnstap, 15, DNSTAP_TYPE_MESSAGE);
+        self.writer
+            .write_all(&(dnstap.len() as u32).to_be_bytes())?;
+        self.writer.write_all(&dnstap)?;
+        self.writer.flush()?;
+        return Ok(());
+    }
+}
+
+// The fields of a Message describing the client side of the exchange.
+fn socket_fields(client: SocketAddr, is_tcp: bool) -> Vec<u8> {
+    let mut message = vec![];
+    let (socket_family, address) = match client.ip() {
+        IpAddr::V4(address) => (SOCKET_FAMILY_INET, address.octets().to_vec()),
+        IpAddr::V6(address) => (SOCKET_FAMILY_INET6, address.octets().to_vec()),
+    };
+    let socket_protocol = match is_tcp {
+        true => SOCKET_PROTOCOL_TCP,
+        false => SOCKET_PROTOCOL_UDP,
+    };
+    encode_varint_field(&mut message, 2, socket_family);
+    encode_varint_field(&mut message, 3, socket_protocol);
+    encode_bytes_field(&mut message, 4, &address);
+    encode_varint_field(&mut message, 6, client.port() as u64);
+    return message;
+}
+
+fn encode_time_fields(
+    buffer: &mut Vec<u8>,
+    seconds_field: u32,
+    nanos_field: u32,
+    time: SystemTime,
+) {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    encode_varint_field(buffer, seconds_field, since_epoch.as_secs());
+    encode_key(buffer, nanos_field, WIRE_FIXED32);
+    buffer.extend_from_slice(&since_epoch.subsec_nanos().to_le_bytes());
+}
+
+fn encode_key(buffer: &mut Vec<u8>, field: u32, wire_type: u32) {
+    encode_varint(buffer, ((field << 3) | wire_type) as u64);
+}
+
+fn encode_varint_field(buffer: &mut Vec<u8>, field: u32, value: u64) {
+    encode_key(buffer, field, WIRE_VARINT);
+    encode_varint(buffer, value);
+}
+
+fn encode_bytes_field(buffer: &mut Vec<u8>, field: u32, bytes: &[u8]) {
+    encode_key(buffer, field, WIRE_LENGTH_DELIMITED);
+    encode_varint(buffer, bytes.len() as u64);
+    buffer.extend_from_slice(bytes);
+}
+
+// Seven bits at a time, least significant group first, with the high bit
+// set on every byte but the last.
+fn encode_varint(buffer: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buffer.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    buffer.push(value as u8);
+}
@@ -0,0 +1,350 @@
+use crate::adapters::upstream_pool::UpstreamPool;
+use crate::models::metrics::Metrics;
+use crate::models::{Class, DnsHeader, DnsPacket, DnsQuestion, Label, RecordType, ResponseCode};
+use crate::traits::{Decodable, Encodable};
+use anyhow::{anyhow, bail, Context};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpStream, UdpSocket};
+use tokio::runtime::Runtime;
+use tokio::time::timeout;
+
+// How often every upstream is asked for the NS records of the names it is
+// used for, the root for the default ones and the suffix of the rule for the
+// others, which brings ejected upstreams back as soon as they answer again.
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+// Sends requests to the upstream resolvers and waits for the replies on a
+// runtime of its own, so the threads serving clients only block on the
+// result. Every attempt goes out from a fresh socket with a random source
+// port and transaction ID, and only a reply matching both is accepted.
+// Source: https://www.rfc-editor.org/rfc/rfc5452#section-9
+pub struct Forwarder {
+    runtime: Runtime,
+    // Used for every name not matched by one of the rules, if any.
+    default_pool: Option<Arc<UpstreamPool>>,
+    // Upstreams for names under a suffix, longest suffix first.
+    rules: Vec<(Vec<Label>, Arc<UpstreamPool>)>,
+    // How long to wait for a reply to one attempt, and how many more
+    // attempts to make after the first one times out or fails.
+    timeout: Duration,
+    retries: u32,
+    udp_payload_size: u16,
+    metrics: Arc<Metrics>,
+}
+
+impl Forwarder {
+    pub fn new(
+        default_pool: Option<UpstreamPool>,
+        rules: Vec<(Vec<Label>, UpstreamPool)>,
+        timeout: Duration,
+        retries: u32,
+        udp_payload_size: u16,
+        metrics: Arc<Metrics>,
+    ) -> anyhow::Result<Forwarder> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .context("Failed to start forwarder runtime")?;
+        let default_pool = default_pool.map(Arc::new);
+        let mut rules: Vec<(Vec<Label>, Arc<UpstreamPool>)> = rules
+            .into_iter()
+            .map(|(suffix, upstream_pool)| (suffix, Arc::new(upstream_pool)))
+            .collect();
+        rules.sort_by_key(|(suffix, _)| std::cmp::Reverse(suffix.len()));
+        for (probe_name, upstream_pool) in default_pool
+            .iter()
+            .map(|pool| (vec![], pool))
+            .chain(rules.iter().map(|(suffix, pool)| (suffix.clone(), pool)))
+        {
+            runtime.spawn(check_health(
+                Arc::clone(upstream_pool),
+                probe_name,
+                Arc::clone(&metrics),
+                timeout,
+                udp_payload_size,
+            ));
+        }
+        return Ok(Forwarder {
+            runtime,
+            default_pool,
+            rules,
+            timeout,
+            retries,
+            udp_payload_size,
+            metrics,
+        });
+    }
+
+    // Whether there is an upstream to forward questions for the name to.
+    pub fn routes(&self, labels: &[Label]) -> bool {
+        return self.upstream_pool(labels).is_some();
+    }
+
+    // Whether both names are forwarded to the same upstreams, and so whether
+    // records for one can be trusted from the upstreams asked about the other.
+    pub fn shares_upstreams(&self, a: &[Label], b: &[Label]) -> bool {
+        return match (self.upstream_pool(a), self.upstream_pool(b)) {
+            (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        };
+    }
+
+    // The upstreams of the rule with the longest suffix matching the name,
+    // or the default ones if no rule does.
+    fn upstream_pool(&self, labels: &[Label]) -> Option<&Arc<UpstreamPool>> {
+        return self
+            .rules
+            .iter()
+            .find(|(suffix, _)| Label::is_subdomain(labels, suffix))
+            .map(|(_, upstream_pool)| upstream_pool)
+            .or(self.default_pool.as_ref());
+    }
+
+    // Forwards all requests at once and returns the replies in the same
+    // order, each carrying the transaction ID of its request again and
+    // paired with the upstream that sent it. The upstreams are chosen by the
+    // first question of each request.
+    pub fn forward(
+        &self,
+        dns_requests: Vec<DnsPacket>,
+    ) -> Vec<anyhow::Result<(DnsPacket, SocketAddr)>> {
+        return self.runtime.block_on(async {
+            let tasks: Vec<_> = dns_requests
+                .into_iter()
+                .map(|dns_request| {
+                    let labels = dns_request
+                        .dns_questions
+                        .first()
+                        .map_or(&[][..], |dns_question| &dns_question.labels[..]);
+                    let upstream_pool = self.upstream_pool(labels).map(Arc::clone);
+                    let upstream_pool = upstream_pool?;
+                    Some(self.runtime.spawn(forward_with_retries(
+                        upstream_pool,
+                        Arc::clone(&self.metrics),
+                        self.timeout,
+                        self.retries,
+                        self.udp_payload_size,
+                        dns_request,
+                    )))
+                })
+                .collect();
+            let mut replies = Vec::with_capacity(tasks.len());
+            for task in tasks {
+                replies.push(match task {
+                    Some(task) => match task.await {
+                        Ok(reply) => reply,
+                        Err(e) => Err(anyhow!("forwarding task failed: {}", e)),
+                    },
+                    None => Err(anyhow!("no upstream resolver for the name")),
+                });
+            }
+            replies
+        });
+    }
+}
+
+// Each retry moves on to the next upstream in the order the pool gives.
+async fn forward_with_retries(
+    upstream_pool: Arc<UpstreamPool>,
+    metrics: Arc<Metrics>,
+    query_timeout: Duration,
+    retries: u32,
+    udp_payload_size: u16,
+    mut dns_request: DnsPacket,
+) -> anyhow::Result<(DnsPacket, SocketAddr)> {
+    let client_identifier = dns_request.dns_header.packet_identifier;
+    let candidates = upstream_pool.candidates(Instant::now());
+    if candidates.is_empty() {
+        bail!("no upstream resolvers configured");
+    }
+    let mut last_error = anyhow!("no attempt made");
+    for attempt in 0..=retries {
+        let upstream = candidates[attempt as usize % candidates.len()];
+        dns_request.dns_header.packet_identifier = rand::random();
+        match timed_exchange(
+            &upstream_pool,
+            &metrics,
+            upstream,
+            query_timeout,
+            udp_payload_size,
+            &dns_request,
+        )
+        .await
+        {
+            Ok(mut reply) => {
+                reply.dns_header.packet_identifier = client_identifier;
+                return Ok((reply, upstream));
+            }
+            Err(e) => {
+                last_error = e.context(format!("attempt {} to {}", attempt + 1, upstream));
+            }
+        }
+    }
+    return Err(last_error);
+}
+
+// One attempt, whose outcome counts towards the health of the upstream.
+async fn timed_exchange(
+    upstream_pool: &UpstreamPool,
+    metrics: &Metrics,
+    upstream: SocketAddr,
+    query_timeout: Duration,
+    udp_payload_size: u16,
+    dns_request: &DnsPacket,
+) -> anyhow::Result<DnsPacket> {
+    let start = Instant::now();
+    let result = match timeout(
+        query_timeout,
+        exchange(upstream, udp_payload_size, dns_request),
+    )
+    .await
+    {
+        Ok(result) => result,
+        Err(_) => Err(anyhow!("timed out after {:?}", query_timeout)),
+    };
+    // An upstream that cannot or will not answer is as good as one that does
+    // not reply, so the next one gets asked instead.
+    let result = result.and_then(|reply| {
+        let response_code = reply.dns_header.response_code;
+        if response_code == ResponseCode::ServerFailure as u8
+            || response_code == ResponseCode::Refused as u8
+        {
+            bail!("answered with RCODE {}", response_code);
+        }
+        Ok(reply)
+    });
+    match &result {
+        Ok(_) => {
+            let rtt = start.elapsed();
+            upstream_pool.record_success(upstream, rtt);
+            metrics.record_upstream_latency(upstream, rtt);
+        }
+        Err(_) => upstream_pool.record_failure(upstream, Instant::now()),
+    }
+    return result;
+}
+
+async fn check_health(
+    upstream_pool: Arc<UpstreamPool>,
+    probe_name: Vec<Label>,
+    metrics: Arc<Metrics>,
+    query_timeout: Duration,
+    udp_payload_size: u16,
+) {
+    let mut interval = tokio::time::interval(HEALTH_CHECK_INTERVAL);
+    loop {
+        interval.tick().await;
+        for upstream in upstream_pool.addresses() {
+            let dns_request = DnsPacket {
+                dns_header: DnsHeader::new_query(rand::random(), true),
+                dns_questions: vec![DnsQuestion {
+                    labels: probe_name.clone(),
+                    record_type: RecordType::NS,
+                    class: Class::IN,
+                }],
+                dns_answers: vec![],
+                dns_authorities: vec![],
+                dns_additionals: vec![],
+                edns: None,
+            };
+            let health_check = timed_exchange(
+                &upstream_pool,
+                &metrics,
+                upstream,
+                query_timeout,
+                udp_payload_size,
+                &dns_request,
+            );
+            if let Err(e) = health_check.await {
+                eprintln!("Health check of upstream {} failed: {:#}", upstream, e);
+            }
+        }
+    }
+}
+
+async fn exchange(
+    upstream: SocketAddr,
+    udp_payload_size: u16,
+    dns_request: &DnsPacket,
+) -> anyhow::Result<DnsPacket> {
+    let encoded_request = dns_request.encode();
+    let bind_address = match upstream {
+        SocketAddr::V4(_) => "0.0.0.0:0",
+        SocketAddr::V6(_) => "[::]:0",
+    };
+    let udp_socket = UdpSocket::bind(bind_address)
+        .await
+        .context("Failed to bind upstream socket")?;
+    // A connected socket drops datagrams from any other address.
+    udp_socket.connect(upstream).await?;
+    udp_socket
+        .send(&encoded_request)
+        .await
+        .context("Failed to send request upstream")?;
+    let mut buf = vec![0; udp_payload_size.max(512) as usize];
+    let reply = loop {
+        let size = udp_socket
+            .recv(&mut buf)
+            .await
+            .context("Failed to receive response from upstream")?;
+        match DnsPacket::decode(&buf[..size]) {
+            Ok(reply) if is_reply_to(&reply, dns_request) => break reply,
+            _ => continue,
+        }
+    };
+    if !reply.dns_header.truncation {
+        return Ok(reply);
+    }
+    return exchange_tcp(upstream, &encoded_request, dns_request).await;
+}
+
+// A reply with TC set was cut short to fit in a datagram, so the same
+// request is repeated over TCP to get all of it.
+// Source: https://www.rfc-editor.org/rfc/rfc7766#section-5
+async fn exchange_tcp(
+    upstream: SocketAddr,
+    encoded_request: &[u8],
+    dns_request: &DnsPacket,
+) -> anyhow::Result<DnsPacket> {
+    let mut tcp_stream = TcpStream::connect(upstream)
+        .await
+        .with_context(|| format!("Failed to connect to upstream {}", upstream))?;
+    let mut message = Vec::with_capacity(encoded_request.len() + 2);
+    message.extend_from_slice(&(encoded_request.len() as u16).to_be_bytes());
+    message.extend_from_slice(encoded_request);
+    tcp_stream
+        .write_all(&message)
+        .await
+        .context("Failed to send request upstream over TCP")?;
+    let length = tcp_stream
+        .read_u16()
+        .await
+        .context("Failed to receive response from upstream over TCP")?;
+    let mut reply = vec![0; length as usize];
+    tcp_stream
+        .read_exact(&mut reply)
+        .await
+        .context("Failed to receive response from upstream over TCP")?;
+    let reply = DnsPacket::decode(&reply)?;
+    if !is_reply_to(&reply, dns_request) {
+        bail!("reply over TCP does not match the request");
+    }
+    return Ok(reply);
+}
+
+fn is_reply_to(reply: &DnsPacket, dns_request: &DnsPacket) -> bool {
+    return reply.dns_header.packet_identifier == dns_request.dns_header.packet_identifier
+        && reply.dns_questions.len() == dns_request.dns_questions.len()
+        && reply
+            .dns_questions
+            .iter()
+            .zip(dns_request.dns_questions.iter())
+            .all(|(reply_question, request_question)| {
+                reply_question.record_type == request_question.record_type
+                    && Label::to_key(&reply_question.labels)
+                        == Label::to_key(&request_question.labels)
+            });
+}
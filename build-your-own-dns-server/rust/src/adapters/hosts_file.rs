@@ -0,0 +1,40 @@
+use crate::models::presentation::parse_name;
+use crate::models::{Hosts, Label};
+use anyhow::{anyhow, bail, Context};
+use std::net::IpAddr;
+
+pub fn read_hosts_file(file_path: &str) -> anyhow::Result<Hosts> {
+    let contents = std::fs::read_to_string(file_path)
+        .with_context(|| format!("Failed to read hosts file '{}'", file_path))?;
+    parse_hosts(&contents).with_context(|| format!("Failed to parse hosts file '{}'", file_path))
+}
+
+// Lines in /etc/hosts format, an IPv4 or IPv6 address followed by one or
+// more names for it. "#" starts a comment.
+// Source: https://man7.org/linux/man-pages/man5/hosts.5.html
+pub fn parse_hosts(contents: &str) -> anyhow::Result<Hosts> {
+    let mut hosts = Hosts::new();
+    for (line_number, line) in contents.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default();
+        let mut tokens = line.split_whitespace();
+        let address = match tokens.next() {
+            Some(address) => address,
+            None => continue,
+        };
+        let address: IpAddr = match address.parse() {
+            Ok(address) => address,
+            Err(_) => bail!("Invalid address '{}' on line {}", address, line_number + 1),
+        };
+        // Names are taken as absolute whether or not they end in a dot, and
+        // are held to the same length limits as those in zone files.
+        let names = tokens
+            .map(|name| parse_name(name, Some(&vec![])))
+            .collect::<Result<Vec<Vec<Label>>, String>>()
+            .map_err(|e| anyhow!("Invalid name on line {}: {}", line_number + 1, e))?;
+        if names.is_empty() {
+            bail!("No names for {} on line {}", address, line_number + 1);
+        }
+        hosts.insert(address, &names);
+    }
+    Ok(hosts)
+}
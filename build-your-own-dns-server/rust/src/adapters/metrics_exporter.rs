@@ -0,0 +1,46 @@
+use crate::models::metrics::Metrics;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::time::Duration;
+
+// How long a scraper may take to send its request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Answers HTTP GET requests for /metrics with the current metrics, one
+// connection at a time as scrapes are rare and quick.
+pub fn serve_metrics(tcp_listener: TcpListener, metrics: Arc<Metrics>) {
+    for tcp_stream in tcp_listener.incoming() {
+        let result = tcp_stream.and_then(|tcp_stream| serve_scrape(tcp_stream, &metrics));
+        if let Err(e) = result {
+            eprintln!("Failed to serve metrics: {}", e);
+        }
+    }
+}
+
+fn serve_scrape(tcp_stream: TcpStream, metrics: &Metrics) -> std::io::Result<()> {
+    tcp_stream.set_read_timeout(Some(REQUEST_TIMEOUT))?;
+    let mut reader = BufReader::new(&tcp_stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // The headers are of no interest, but are read so that closing the
+    // connection does not reset it before the client has our response.
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+    let path = request_line.split_whitespace().nth(1).unwrap_or_default();
+    let (status, body) = match path {
+        "/metrics" => ("200 OK", metrics.render()),
+        _ => ("404 Not Found", "Not found\n".to_string()),
+    };
+    let mut tcp_stream = &tcp_stream;
+    write!(
+        tcp_stream,
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )?;
+    tcp_stream.flush()
+}
@@ -0,0 +1,11 @@
+pub mod blocklist_file;
+pub mod dnstap;
+pub mod forwarder;
+pub mod hosts_file;
+pub mod metrics_exporter;
+pub mod query_log;
+pub mod recursive_resolver;
+pub mod tcp;
+pub mod upstream_pool;
+pub mod zone_file;
+pub mod zone_transfer;
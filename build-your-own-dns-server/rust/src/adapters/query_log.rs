@@ -0,0 +1,155 @@
+use crate::adapters::dnstap::DnstapWriter;
+use crate::models::{DnsQuestion, Label, RateLimitAction};
+use anyhow::Context;
+use serde::Serialize;
+use std::fs::OpenOptions;
+use std::io::{LineWriter, Write};
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// What happened to one query, gathered while it is answered.
+#[derive(Debug)]
+pub struct QueryRecord {
+    pub time: SystemTime,
+    pub client: SocketAddr,
+    pub is_tcp: bool,
+    pub dns_questions: Vec<DnsQuestion>,
+    pub response_code: u16,
+    pub answer_count: usize,
+    // The upstreams that answered the forwarded questions, if any were.
+    pub upstreams: Vec<SocketAddr>,
+    // Whether the cache answered, None if it was not asked at all.
+    pub cache_hit: Option<bool>,
+    // Whether rate limiting slipped or dropped the response.
+    pub rate_limit: RateLimitAction,
+    pub latency: Duration,
+}
+
+#[derive(Serialize)]
+struct JsonQuestion {
+    name: String,
+    #[serde(rename = "type")]
+    record_type: String,
+    class: String,
+}
+
+#[derive(Serialize)]
+struct JsonRecord {
+    // Seconds since the Unix epoch when the query came in.
+    time: f64,
+    client: String,
+    protocol: &'static str,
+    questions: Vec<JsonQuestion>,
+    rcode: u16,
+    answers: usize,
+    upstreams: Vec<String>,
+    cache_hit: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rate_limit: Option<String>,
+    latency_us: u128,
+}
+
+// Writes a JSON object per query to a file or stdout, and the raw messages
+// to a dnstap file for tools that replay or inspect them.
+pub struct QueryLog {
+    json: Option<Mutex<Box<dyn Write + Send>>>,
+    dnstap: Option<Mutex<DnstapWriter>>,
+}
+
+impl QueryLog {
+    // "-" as the JSON log path writes to stdout.
+    pub fn new(json_path: Option<&str>, dnstap_path: Option<&str>) -> anyhow::Result<QueryLog> {
+        let json: Option<Box<dyn Write + Send>> = match json_path {
+            None => None,
+            Some("-") => Some(Box::new(std::io::stdout())),
+            Some(json_path) => {
+                let file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(json_path)
+                    .with_context(|| format!("Failed to open query log '{}'", json_path))?;
+                Some(Box::new(LineWriter::new(file)))
+            }
+        };
+        let dnstap = match dnstap_path {
+            None => None,
+            Some(dnstap_path) => Some(DnstapWriter::create(dnstap_path, env!("CARGO_PKG_NAME"))?),
+        };
+        return Ok(QueryLog {
+            json: json.map(Mutex::new),
+            dnstap: dnstap.map(Mutex::new),
+        });
+    }
+
+    // The response is None when it was dropped rather than sent.
+    pub fn log(&self, query_record: &QueryRecord, query: &[u8], response: Option<&[u8]>) {
+        if let Some(json) = self.json.as_ref() {
+            let line = serde_json::to_string(&json_record(query_record))
+                .expect("Query records always serialize");
+            if let Err(e) = writeln!(json.lock().unwrap(), "{}", line) {
+                eprintln!("Failed to write query log: {}", e);
+            }
+        }
+        if let Some(dnstap) = self.dnstap.as_ref() {
+            let mut dnstap = dnstap.lock().unwrap();
+            let result = dnstap
+                .write_query(
+                    query_record.client,
+                    query_record.is_tcp,
+                    query_record.time,
+                    query,
+                )
+                .and_then(|_| match response {
+                    Some(response) => dnstap.write_response(
+                        query_record.client,
+                        query_record.is_tcp,
+                        query_record.time,
+                        query_record.time + query_record.latency,
+                        response,
+                    ),
+                    None => Ok(()),
+                });
+            if let Err(e) = result {
+                eprintln!("Failed to write dnstap frame: {:#}", e);
+            }
+        }
+    }
+}
+
+fn json_record(query_record: &QueryRecord) -> JsonRecord {
+    return JsonRecord {
+        time: query_record
+            .time
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64(),
+        client: query_record.client.to_string(),
+        protocol: match query_record.is_tcp {
+            true => "tcp",
+            false => "udp",
+        },
+        questions: query_record
+            .dns_questions
+            .iter()
+            .map(|dns_question| JsonQuestion {
+                name: format!("{}.", Label::to_key(&dns_question.labels)),
+                record_type: dns_question.record_type.to_string(),
+                class: format!("{:?}", dns_question.class),
+            })
+            .collect(),
+        rcode: query_record.response_code,
+        answers: query_record.answer_count,
+        upstreams: query_record
+            .upstreams
+            .iter()
+            .map(|upstream| upstream.to_string())
+            .collect(),
+        cache_hit: query_record.cache_hit,
+        rate_limit: match query_record.rate_limit {
+            RateLimitAction::Send => None,
+            action => Some(action.to_string()),
+        },
+        latency_us: query_record.latency.as_micros(),
+    };
+}
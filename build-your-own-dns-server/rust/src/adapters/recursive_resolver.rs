@@ -0,0 +1,352 @@
+use crate::adapters::tcp;
+use crate::models::{
+    Class, DnsAnswer, DnsHeader, DnsPacket, DnsQuestion, Edns, Label, RData, RecordType,
+    ResponseCode,
+};
+use crate::traits::{Decodable, Encodable};
+use anyhow::{anyhow, bail, Context};
+use std::io::ErrorKind;
+use std::net::{IpAddr, SocketAddr, TcpStream, UdpSocket};
+use std::time::{Duration, Instant};
+
+// Resolves names on its own by walking down from the root servers, following
+// referrals until a server answers authoritatively.
+// Source: https://www.rfc-editor.org/rfc/rfc1034#section-5.3.3
+
+const QUERY_TIMEOUT: Duration = Duration::from_secs(2);
+const UDP_PAYLOAD_SIZE: u16 = 1232;
+// Upper bounds on the work done for one question, so that referral loops and
+// CNAME or glueless delegation chains can not keep us busy forever.
+const MAX_REFERRALS: usize = 16;
+const MAX_DEPTH: usize = 8;
+
+// The outcome of resolving one question, ready to be put into a response.
+#[derive(Debug)]
+pub struct Resolution {
+    pub response_code: ResponseCode,
+    pub answers: Vec<DnsAnswer>,
+    pub authorities: Vec<DnsAnswer>,
+}
+
+pub struct RecursiveResolver {
+    root_servers: Vec<SocketAddr>,
+    // Name servers are always queried on this port, it is only ever other
+    // than 53 when testing against a local stand-in for the hierarchy.
+    port: u16,
+}
+
+impl RecursiveResolver {
+    pub fn new(root_hints: &[DnsAnswer], port: u16) -> anyhow::Result<RecursiveResolver> {
+        let root_servers = addresses(root_hints.iter(), port);
+        if root_servers.is_empty() {
+            bail!("root hints contain no root server addresses");
+        }
+        return Ok(RecursiveResolver { root_servers, port });
+    }
+
+    pub fn resolve(&self, dns_question: &DnsQuestion) -> anyhow::Result<Resolution> {
+        return self.resolve_at_depth(dns_question, 0);
+    }
+
+    fn resolve_at_depth(
+        &self,
+        dns_question: &DnsQuestion,
+        depth: usize,
+    ) -> anyhow::Result<Resolution> {
+        if depth > MAX_DEPTH {
+            bail!(
+                "too many CNAMEs or glueless delegations resolving '{}'",
+                Label::to_key(&dns_question.labels)
+            );
+        }
+        let mut servers = self.root_servers.clone();
+        // The zone the servers we are currently asking are authoritative for.
+        let mut zone_cut: Vec<Label> = vec![];
+        for _ in 0..MAX_REFERRALS {
+            let reply = query_servers(&servers, dns_question)?;
+            if reply.dns_header.response_code == ResponseCode::NameError as u8 {
+                return Ok(Resolution {
+                    response_code: ResponseCode::NameError,
+                    answers: vec![],
+                    authorities: reply.dns_authorities,
+                });
+            }
+
+            let (mut answers, target, complete) = follow_answers(
+                &reply.dns_answers,
+                &dns_question.labels,
+                dns_question.record_type,
+                &zone_cut,
+            );
+            if complete {
+                return Ok(Resolution {
+                    response_code: ResponseCode::NoError,
+                    answers,
+                    authorities: vec![],
+                });
+            }
+            // A CNAME out of the zone of the server starts over from the
+            // root, the server that gave it need not know anything about the
+            // target and is not to be trusted with it.
+            if !answers.is_empty() {
+                let target_question = DnsQuestion {
+                    labels: target,
+                    record_type: dns_question.record_type,
+                    class: dns_question.class,
+                };
+                let resolution = self.resolve_at_depth(&target_question, depth + 1)?;
+                answers.extend(resolution.answers);
+                return Ok(Resolution {
+                    response_code: resolution.response_code,
+                    answers,
+                    authorities: resolution.authorities,
+                });
+            }
+
+            // Anything but a referral to a zone closer to the name is a
+            // negative answer, which is passed on with its SOA.
+            let name_servers: Vec<&DnsAnswer> = reply
+                .dns_authorities
+                .iter()
+                .filter(|record| matches!(record.rdata, RData::NS(_)))
+                .filter(|record| Label::is_subdomain(&dns_question.labels, &record.name))
+                .collect();
+            let delegation = match name_servers.first() {
+                Some(record)
+                    if record.name.len() > zone_cut.len()
+                        && Label::is_subdomain(&record.name, &zone_cut) =>
+                {
+                    record.name.clone()
+                }
+                _ => {
+                    return Ok(Resolution {
+                        response_code: ResponseCode::NoError,
+                        answers: vec![],
+                        authorities: reply.dns_authorities,
+                    });
+                }
+            };
+            servers = self.name_server_addresses(
+                &name_servers,
+                &reply.dns_additionals,
+                &zone_cut,
+                depth,
+            )?;
+            zone_cut = delegation;
+        }
+        bail!(
+            "too many referrals resolving '{}'",
+            Label::to_key(&dns_question.labels)
+        );
+    }
+
+    // Addresses for the name servers of a referral. Glue is only taken from
+    // inside the zone of the server that sent it, when there is none the
+    // name server names are resolved separately.
+    fn name_server_addresses(
+        &self,
+        name_servers: &[&DnsAnswer],
+        additionals: &[DnsAnswer],
+        bailiwick: &[Label],
+        depth: usize,
+    ) -> anyhow::Result<Vec<SocketAddr>> {
+        let name_server_names: Vec<&Vec<Label>> = name_servers
+            .iter()
+            .filter_map(|record| match &record.rdata {
+                RData::NS(name) => Some(name),
+                _ => None,
+            })
+            .collect();
+        let glue = additionals.iter().filter(|record| {
+            Label::is_subdomain(&record.name, bailiwick)
+                && name_server_names
+                    .iter()
+                    .any(|name| Label::to_key(name) == Label::to_key(&record.name))
+        });
+        let servers = addresses(glue, self.port);
+        if !servers.is_empty() {
+            return Ok(servers);
+        }
+        for name in name_server_names.iter() {
+            let dns_question = DnsQuestion {
+                labels: name.to_vec(),
+                record_type: RecordType::A,
+                class: Class::IN,
+            };
+            match self.resolve_at_depth(&dns_question, depth + 1) {
+                Ok(resolution) => {
+                    let servers = addresses(resolution.answers.iter(), self.port);
+                    if !servers.is_empty() {
+                        return Ok(servers);
+                    }
+                }
+                Err(e) => {
+                    eprintln!(
+                        "Failed to resolve name server '{}': {:#}",
+                        Label::to_key(name),
+                        e
+                    );
+                }
+            }
+        }
+        return Err(anyhow!("no addresses found for any name server"));
+    }
+}
+
+// Follows the answer section from `name` along CNAMEs. Returns the records on
+// the way, the name reached and whether records of the asked type were found.
+// Only records inside the zone of the server that sent them are taken, so
+// that it cannot slip in answers for names it has no say over.
+// Source: https://www.rfc-editor.org/rfc/rfc2181#section-5.4.1
+fn follow_answers(
+    records: &[DnsAnswer],
+    name: &[Label],
+    record_type: RecordType,
+    bailiwick: &[Label],
+) -> (Vec<DnsAnswer>, Vec<Label>, bool) {
+    let mut chain: Vec<DnsAnswer> = vec![];
+    let mut current = name.to_vec();
+    // Each step uses up at least one record, which bounds CNAME loops.
+    for _ in 0..=records.len() {
+        let owned: Vec<&DnsAnswer> = records
+            .iter()
+            .filter(|record| Label::to_key(&record.name) == Label::to_key(&current))
+            .filter(|record| Label::is_subdomain(&record.name, bailiwick))
+            .collect();
+        if owned.iter().any(|record| record.record_type == record_type) {
+            chain.extend(
+                owned
+                    .into_iter()
+                    .filter(|record| record.record_type == record_type)
+                    .cloned(),
+            );
+            return (chain, current, true);
+        }
+        match owned
+            .into_iter()
+            .find(|record| record.record_type == RecordType::CNAME)
+        {
+            Some(record) => {
+                if let RData::CNAME(target) = &record.rdata {
+                    current = target.clone();
+                }
+                chain.push(record.clone());
+            }
+            None => break,
+        }
+    }
+    return (chain, current, false);
+}
+
+fn addresses<'a>(records: impl Iterator<Item = &'a DnsAnswer>, port: u16) -> Vec<SocketAddr> {
+    let mut addresses: Vec<SocketAddr> = records
+        .filter_map(|record| match record.rdata {
+            RData::A(address) => Some(SocketAddr::new(IpAddr::V4(address), port)),
+            RData::AAAA(address) => Some(SocketAddr::new(IpAddr::V6(address), port)),
+            _ => None,
+        })
+        .collect();
+    // IPv6 is often unreachable, so those are only tried after IPv4.
+    addresses.sort_by_key(|address| address.is_ipv6());
+    addresses.dedup();
+    return addresses;
+}
+
+// Asks the servers in turn until one of them gives a usable reply, which is
+// NOERROR or NXDOMAIN. A lame server answering SERVFAIL or REFUSED for a zone
+// it was delegated is passed over like one that does not answer at all.
+// Source: https://www.rfc-editor.org/rfc/rfc1034#section-5.3.3
+fn query_servers(servers: &[SocketAddr], dns_question: &DnsQuestion) -> anyhow::Result<DnsPacket> {
+    let mut last_error = anyhow!("no name servers to ask");
+    for server in servers {
+        match query(*server, dns_question) {
+            Ok(reply)
+                if reply.dns_header.response_code == ResponseCode::NoError as u8
+                    || reply.dns_header.response_code == ResponseCode::NameError as u8 =>
+            {
+                return Ok(reply)
+            }
+            Ok(reply) => {
+                last_error = anyhow!(
+                    "{} answered {}",
+                    server,
+                    ResponseCode::mnemonic(reply.response_code())
+                );
+            }
+            Err(e) => {
+                last_error = e.context(format!("Failed to query {}", server));
+            }
+        }
+    }
+    return Err(last_error);
+}
+
+// Asks one server without recursion, over TCP again if the UDP reply was
+// truncated.
+pub(crate) fn query(server: SocketAddr, dns_question: &DnsQuestion) -> anyhow::Result<DnsPacket> {
+    let packet_identifier: u16 = rand::random();
+    let dns_request = DnsPacket {
+        dns_header: DnsHeader::new_query(packet_identifier, false),
+        dns_questions: vec![dns_question.clone()],
+        dns_answers: vec![],
+        dns_authorities: vec![],
+        dns_additionals: vec![],
+        edns: Some(Edns::new(UDP_PAYLOAD_SIZE)),
+    };
+    let encoded_request = dns_request.encode();
+    let bind_address = match server {
+        SocketAddr::V4(_) => "0.0.0.0:0",
+        SocketAddr::V6(_) => "[::]:0",
+    };
+    let udp_socket = UdpSocket::bind(bind_address).context("Failed to bind socket")?;
+    udp_socket.connect(server)?;
+    udp_socket.send(&encoded_request)?;
+    let mut buf = vec![0; UDP_PAYLOAD_SIZE as usize];
+    // Stray or spoofed datagrams are skipped, but only until the timeout for
+    // the whole exchange runs out, however many of them keep coming.
+    let deadline = Instant::now() + QUERY_TIMEOUT;
+    let reply = loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let received = match remaining.is_zero() {
+            true => None,
+            false => {
+                udp_socket.set_read_timeout(Some(remaining))?;
+                match udp_socket.recv(&mut buf) {
+                    Ok(size) => Some(size),
+                    Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                        None
+                    }
+                    Err(e) => return Err(e.into()),
+                }
+            }
+        };
+        let size = match received {
+            Some(size) => size,
+            None => bail!("timed out after {}s", QUERY_TIMEOUT.as_secs()),
+        };
+        match DnsPacket::decode(&buf[..size]) {
+            Ok(reply) if is_reply_to(&reply, packet_identifier, dns_question) => break reply,
+            _ => continue,
+        }
+    };
+    if !reply.dns_header.truncation {
+        return Ok(reply);
+    }
+    let mut tcp_stream = TcpStream::connect_timeout(&server, QUERY_TIMEOUT)?;
+    tcp_stream.set_read_timeout(Some(QUERY_TIMEOUT))?;
+    tcp::write_message(&mut tcp_stream, &encoded_request)?;
+    let message =
+        tcp::read_message(&mut tcp_stream)?.context("connection closed without a response")?;
+    let reply = DnsPacket::decode(&message)?;
+    if !is_reply_to(&reply, packet_identifier, dns_question) {
+        bail!("reply over TCP does not match the query");
+    }
+    return Ok(reply);
+}
+
+fn is_reply_to(reply: &DnsPacket, packet_identifier: u16, dns_question: &DnsQuestion) -> bool {
+    return reply.dns_header.packet_identifier == packet_identifier
+        && reply.dns_questions.len() == 1
+        && reply.dns_questions[0].record_type == dns_question.record_type
+        && Label::to_key(&reply.dns_questions[0].labels) == Label::to_key(&dns_question.labels);
+}
@@ -0,0 +1,30 @@
+use std::io::{ErrorKind, Read, Write};
+
+// Messages over TCP are prefixed with their length as a two byte integer.
+// Source: https://www.rfc-editor.org/rfc/rfc1035#section-4.2.2
+
+// Reads one message, or None once the peer has closed the connection
+// between messages.
+pub fn read_message(stream: &mut impl Read) -> std::io::Result<Option<Vec<u8>>> {
+    let mut length = [0; 2];
+    match stream.read_exact(&mut length) {
+        Ok(()) => {}
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    let mut message = vec![0; u16::from_be_bytes(length) as usize];
+    stream.read_exact(&mut message)?;
+    return Ok(Some(message));
+}
+
+// Writes one message, prefix and all, in a single write so that it is not
+// split over two segments for no reason.
+pub fn write_message(stream: &mut impl Write, message: &[u8]) -> std::io::Result<()> {
+    let length = u16::try_from(message.len())
+        .map_err(|_| std::io::Error::new(ErrorKind::InvalidInput, "message over 65535 bytes"))?;
+    let mut buffer = Vec::with_capacity(message.len() + 2);
+    buffer.extend_from_slice(&length.to_be_bytes());
+    buffer.extend_from_slice(message);
+    stream.write_all(&buffer)?;
+    return stream.flush();
+}
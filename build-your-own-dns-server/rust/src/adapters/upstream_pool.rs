@@ -0,0 +1,137 @@
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// Consecutive failures after which an upstream is taken out of rotation, and
+// how long it stays out unless a health check finds it working again.
+const MAX_CONSECUTIVE_FAILURES: u32 = 3;
+const EJECTION_PERIOD: Duration = Duration::from_secs(30);
+
+// How the next upstream to ask is chosen among the healthy ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strategy {
+    // Always the first healthy upstream in the order they were given.
+    Failover,
+    // Each request starts at the next healthy upstream in turn.
+    RoundRobin,
+    // The healthy upstream with the lowest smoothed round trip time.
+    Fastest,
+}
+
+impl FromStr for Strategy {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Strategy, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "failover" => Ok(Strategy::Failover),
+            "round-robin" => Ok(Strategy::RoundRobin),
+            "fastest" => Ok(Strategy::Fastest),
+            _ => Err(format!("Unknown upstream strategy '{}'", name)),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Health {
+    // Smoothed round trip time, None until the first reply.
+    rtt: Option<Duration>,
+    consecutive_failures: u32,
+    ejected_until: Option<Instant>,
+}
+
+#[derive(Debug)]
+struct Upstream {
+    address: SocketAddr,
+    health: Mutex<Health>,
+}
+
+#[derive(Debug)]
+pub struct UpstreamPool {
+    upstreams: Vec<Upstream>,
+    strategy: Strategy,
+    next: AtomicUsize,
+}
+
+impl UpstreamPool {
+    pub fn new(addresses: Vec<SocketAddr>, strategy: Strategy) -> UpstreamPool {
+        UpstreamPool {
+            upstreams: addresses
+                .into_iter()
+                .map(|address| Upstream {
+                    address,
+                    health: Mutex::new(Health::default()),
+                })
+                .collect(),
+            strategy,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn addresses(&self) -> Vec<SocketAddr> {
+        self.upstreams
+            .iter()
+            .map(|upstream| upstream.address)
+            .collect()
+    }
+
+    // All upstreams in the order they should be tried for one request.
+    // Ejected upstreams come last, so with every upstream ejected we still
+    // try them rather than fail outright.
+    pub fn candidates(&self, now: Instant) -> Vec<SocketAddr> {
+        let mut healthy: Vec<(SocketAddr, Option<Duration>)> = vec![];
+        let mut ejected: Vec<SocketAddr> = vec![];
+        for upstream in self.upstreams.iter() {
+            let health = upstream.health.lock().unwrap();
+            match health.ejected_until {
+                Some(until) if until > now => ejected.push(upstream.address),
+                _ => healthy.push((upstream.address, health.rtt)),
+            }
+        }
+        match self.strategy {
+            Strategy::Failover => {}
+            Strategy::RoundRobin if !healthy.is_empty() => {
+                let start = self.next.fetch_add(1, Ordering::Relaxed) % healthy.len();
+                healthy.rotate_left(start);
+            }
+            Strategy::RoundRobin => {}
+            // Upstreams without a measurement yet sort first so they get one.
+            Strategy::Fastest => healthy.sort_by_key(|(_, rtt)| rtt.unwrap_or_default()),
+        }
+        healthy
+            .into_iter()
+            .map(|(address, _)| address)
+            .chain(ejected)
+            .collect()
+    }
+
+    // A reply came back, which also brings an ejected upstream back early.
+    // Source: https://www.rfc-editor.org/rfc/rfc6298#section-2 (smoothing)
+    pub fn record_success(&self, address: SocketAddr, rtt: Duration) {
+        if let Some(mut health) = self.health(address) {
+            health.rtt = Some(match health.rtt {
+                Some(smoothed) => (smoothed * 7 + rtt) / 8,
+                None => rtt,
+            });
+            health.consecutive_failures = 0;
+            health.ejected_until = None;
+        }
+    }
+
+    pub fn record_failure(&self, address: SocketAddr, now: Instant) {
+        if let Some(mut health) = self.health(address) {
+            health.consecutive_failures += 1;
+            if health.consecutive_failures >= MAX_CONSECUTIVE_FAILURES {
+                health.ejected_until = Some(now + EJECTION_PERIOD);
+            }
+        }
+    }
+
+    fn health(&self, address: SocketAddr) -> Option<std::sync::MutexGuard<'_, Health>> {
+        self.upstreams
+            .iter()
+            .find(|upstream| upstream.address == address)
+            .map(|upstream| upstream.health.lock().unwrap())
+    }
+}
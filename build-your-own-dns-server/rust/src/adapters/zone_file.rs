@@ -0,0 +1,177 @@
+use crate::models::journal::Difference;
+use crate::models::presentation::{
+    parse_name, parse_rdata, parse_ttl, parse_ttl_and_class, tokenize,
+};
+use crate::models::{Class, DnsAnswer, Label, RecordType, Zone};
+use anyhow::{anyhow, bail, Context};
+use std::io::Write;
+use std::str::FromStr;
+
+// Master file format specification: https://www.rfc-editor.org/rfc/rfc1035#section-5
+// $TTL directive: https://www.rfc-editor.org/rfc/rfc2308#section-4
+
+pub fn read_zone_file(file_path: &str) -> anyhow::Result<Zone> {
+    let contents = std::fs::read_to_string(file_path)
+        .with_context(|| format!("Failed to read zone file '{}'", file_path))?;
+    parse_zone(&contents).with_context(|| format!("Failed to parse zone file '{}'", file_path))
+}
+
+// Reads a zone along with its journal, kept next to it with ".jnl" appended
+// to the name, if there is one.
+pub fn load_zone(file_path: &str) -> anyhow::Result<Zone> {
+    let mut zone = read_zone_file(file_path)?;
+    let journal_path = journal_path(file_path);
+    if std::path::Path::new(&journal_path).exists() {
+        let journal = read_journal(&journal_path)?;
+        zone.replay(journal)
+            .map_err(|e| anyhow!(e))
+            .with_context(|| format!("Failed to replay journal '{}'", journal_path))?;
+    }
+    Ok(zone)
+}
+
+pub fn journal_path(zone_path: &str) -> String {
+    format!("{}.jnl", zone_path)
+}
+
+// A journal is a master file of the records of each difference in the order
+// an IXFR reply carries them, starting with the old SOA of the oldest.
+pub fn read_journal(file_path: &str) -> anyhow::Result<Vec<Difference>> {
+    let contents = std::fs::read_to_string(file_path)
+        .with_context(|| format!("Failed to read journal '{}'", file_path))?;
+    parse_records(&contents)
+        .and_then(|records| Difference::from_records(&records).map_err(|e| anyhow!(e)))
+        .with_context(|| format!("Failed to parse journal '{}'", file_path))
+}
+
+// Adds a difference to the end of a journal, creating it if need be, so
+// that the change is replayed when the zone is next loaded.
+pub fn append_journal(file_path: &str, difference: &Difference) -> anyhow::Result<()> {
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(file_path)
+        .with_context(|| format!("Failed to open journal '{}'", file_path))?;
+    let contents: String = difference
+        .to_records()
+        .iter()
+        .map(|record| format!("{}\n", record))
+        .collect();
+    file.write_all(contents.as_bytes())
+        .and_then(|_| file.sync_data())
+        .with_context(|| format!("Failed to write journal '{}'", file_path))
+}
+
+pub fn parse_zone(contents: &str) -> anyhow::Result<Zone> {
+    Zone::from_records(parse_records(contents)?).map_err(|e| anyhow!(e))
+}
+
+// Root hints are a master file without SOA, listing the root name servers
+// and their addresses, such as https://www.internic.net/domain/named.root
+pub fn read_root_hints(file_path: &str) -> anyhow::Result<Vec<DnsAnswer>> {
+    let contents = std::fs::read_to_string(file_path)
+        .with_context(|| format!("Failed to read root hints '{}'", file_path))?;
+    parse_records(&contents).with_context(|| format!("Failed to parse root hints '{}'", file_path))
+}
+
+// Trust anchors are DS or DNSKEY records in master file format, such as
+// the root zone's at https://data.iana.org/root-anchors/
+pub fn read_trust_anchors(file_path: &str) -> anyhow::Result<Vec<DnsAnswer>> {
+    let contents = std::fs::read_to_string(file_path)
+        .with_context(|| format!("Failed to read trust anchors '{}'", file_path))?;
+    let records = parse_records(&contents)
+        .with_context(|| format!("Failed to parse trust anchors '{}'", file_path))?;
+    if let Some(record) = records
+        .iter()
+        .find(|record| !matches!(record.record_type, RecordType::DS | RecordType::DNSKEY))
+    {
+        bail!(
+            "Trust anchors '{}' contain a {} record, only DS and DNSKEY are allowed",
+            file_path,
+            record.record_type
+        );
+    }
+    Ok(records)
+}
+
+fn parse_records(contents: &str) -> anyhow::Result<Vec<DnsAnswer>> {
+    let mut origin: Option<Vec<Label>> = None;
+    let mut default_ttl: Option<u32> = None;
+    let mut last_ttl: Option<u32> = None;
+    let mut last_owner: Option<Vec<Label>> = None;
+    let mut last_class = Class::IN;
+    let mut records: Vec<DnsAnswer> = vec![];
+
+    for entry in tokenize(contents).map_err(|e| anyhow!(e))? {
+        let line_number = entry.line_number;
+        let tokens = entry.tokens;
+        let directive = match entry.owner_omitted || tokens[0].quoted {
+            true => "",
+            false => tokens[0].text.as_str(),
+        };
+        match directive {
+            "$ORIGIN" => {
+                let name = tokens
+                    .get(1)
+                    .ok_or_else(|| anyhow!("line {}: $ORIGIN without a name", line_number))?;
+                origin = Some(
+                    parse_name(&name.text, origin.as_ref())
+                        .map_err(|e| anyhow!("line {}: {}", line_number, e))?,
+                );
+                continue;
+            }
+            "$TTL" => {
+                let ttl = tokens
+                    .get(1)
+                    .and_then(|ttl| parse_ttl(&ttl.text))
+                    .ok_or_else(|| anyhow!("line {}: $TTL without a valid TTL", line_number))?;
+                default_ttl = Some(ttl);
+                continue;
+            }
+            "$INCLUDE" => {
+                bail!("line {}: $INCLUDE is not supported", line_number)
+            }
+            _ => {}
+        }
+
+        let mut index = 0;
+        let owner = if entry.owner_omitted {
+            last_owner
+                .clone()
+                .ok_or_else(|| anyhow!("line {}: record without an owner name", line_number))?
+        } else {
+            index += 1;
+            parse_name(&tokens[0].text, origin.as_ref())
+                .map_err(|e| anyhow!("line {}: {}", line_number, e))?
+        };
+        let (ttl, class, index) = parse_ttl_and_class(&tokens, index);
+        let record_type = tokens
+            .get(index)
+            .ok_or_else(|| anyhow!("line {}: record without a type", line_number))
+            .and_then(|token| {
+                RecordType::from_str(&token.text)
+                    .map_err(|e| anyhow!("line {}: {}", line_number, e))
+            })?;
+        let time_to_live = match ttl.or(default_ttl).or(last_ttl) {
+            Some(time_to_live) => time_to_live,
+            None => bail!("line {}: no TTL given and no $TTL set", line_number),
+        };
+        let class = class.unwrap_or(last_class);
+        let rdata = parse_rdata(record_type, &tokens[index + 1..], origin.as_ref())
+            .map_err(|e| anyhow!("line {}: invalid {} data: {}", line_number, record_type, e))?;
+
+        if ttl.is_some() {
+            last_ttl = ttl;
+        }
+        last_class = class;
+        last_owner = Some(owner.clone());
+        records.push(DnsAnswer::new(
+            owner,
+            record_type,
+            class,
+            time_to_live,
+            rdata,
+        ));
+    }
+    Ok(records)
+}
@@ -0,0 +1,161 @@
+use crate::adapters::{recursive_resolver, tcp};
+use crate::models::journal::{serial, serial_newer, Difference};
+use crate::models::{
+    Class, DnsAnswer, DnsHeader, DnsPacket, DnsQuestion, Label, RecordType, ResponseCode, Zone,
+};
+use crate::traits::{Decodable, Encodable};
+use anyhow::{anyhow, bail, Context};
+use std::net::{SocketAddr, TcpStream};
+use std::time::Duration;
+
+// How long to wait for the primary to connect and for each message of a
+// transfer.
+const TRANSFER_TIMEOUT: Duration = Duration::from_secs(10);
+
+// What a transfer reply turned out to hold once it was complete.
+enum Transfer {
+    UpToDate,
+    Full(Vec<DnsAnswer>),
+    Incremental(Vec<Difference>),
+}
+
+// The SOA the primary has for the zone, answered with authority.
+pub fn query_soa(primary: SocketAddr, origin: &[Label]) -> anyhow::Result<DnsAnswer> {
+    let dns_question = DnsQuestion {
+        labels: origin.to_vec(),
+        record_type: RecordType::SOA,
+        class: Class::IN,
+    };
+    let reply = recursive_resolver::query(primary, &dns_question)?;
+    if reply.response_code() != ResponseCode::NoError as u16 {
+        bail!(
+            "primary answered {}",
+            ResponseCode::mnemonic(reply.response_code())
+        );
+    }
+    if !reply.dns_header.authoritative_answer {
+        bail!("primary is not authoritative for the zone");
+    }
+    reply
+        .dns_answers
+        .into_iter()
+        .find(|record| {
+            record.record_type == RecordType::SOA
+                && Label::to_key(&record.name) == Label::to_key(origin)
+        })
+        .context("primary has no SOA for the zone")
+}
+
+// Brings our copy of the zone up to date from the primary over TCP: with an
+// IXFR from the SOA we have if there is a copy, which the primary may still
+// answer with the whole zone, and with an AXFR otherwise.
+// Source: https://www.rfc-editor.org/rfc/rfc1995#section-4
+// Source: https://www.rfc-editor.org/rfc/rfc5936#section-2.2
+pub fn transfer(
+    primary: SocketAddr,
+    origin: &[Label],
+    current: Option<&Zone>,
+) -> anyhow::Result<Zone> {
+    let current_soa = current.and_then(Zone::soa);
+    let record_type = match current_soa {
+        Some(_) => RecordType::IXFR,
+        None => RecordType::AXFR,
+    };
+    let packet_identifier: u16 = rand::random();
+    let dns_request = DnsPacket {
+        dns_header: DnsHeader::new_query(packet_identifier, false),
+        dns_questions: vec![DnsQuestion {
+            labels: origin.to_vec(),
+            record_type,
+            class: Class::IN,
+        }],
+        dns_answers: vec![],
+        dns_authorities: current_soa.into_iter().cloned().collect(),
+        dns_additionals: vec![],
+        edns: None,
+    };
+    let mut tcp_stream = TcpStream::connect_timeout(&primary, TRANSFER_TIMEOUT)?;
+    tcp_stream.set_read_timeout(Some(TRANSFER_TIMEOUT))?;
+    tcp::write_message(&mut tcp_stream, &dns_request.encode())?;
+    let current_serial = current.and_then(Zone::serial);
+    let mut records: Vec<DnsAnswer> = vec![];
+    let transfer = loop {
+        let message = tcp::read_message(&mut tcp_stream)?
+            .context("connection closed before the end of the transfer")?;
+        let reply = DnsPacket::decode(&message)?;
+        if reply.dns_header.packet_identifier != packet_identifier {
+            bail!("reply does not match the transfer request");
+        }
+        if reply.response_code() != ResponseCode::NoError as u16 {
+            bail!(
+                "primary answered {}",
+                ResponseCode::mnemonic(reply.response_code())
+            );
+        }
+        if reply.dns_answers.is_empty() {
+            bail!("primary sent a message without records");
+        }
+        records.extend(reply.dns_answers);
+        if let Some(transfer) = complete_transfer(&records, record_type, current_serial)? {
+            break transfer;
+        }
+    };
+    match transfer {
+        Transfer::UpToDate => current.cloned().context("no copy of the zone to keep"),
+        Transfer::Full(records) => {
+            let zone = Zone::from_records(records).map_err(|e| anyhow!(e))?;
+            if Label::to_key(&zone.origin) != Label::to_key(origin) {
+                bail!("primary sent zone '{}'", Label::to_key(&zone.origin));
+            }
+            Ok(zone)
+        }
+        Transfer::Incremental(differences) => {
+            let mut zone = current.cloned().context("no copy of the zone to change")?;
+            for difference in differences {
+                zone.apply(difference).map_err(|e| anyhow!(e))?;
+            }
+            Ok(zone)
+        }
+    }
+}
+
+// Works out whether the records received so far make up a whole transfer.
+// Both kinds end with the SOA they start with. An incremental one has the
+// SOA of the first difference right after it and ends once the differences
+// reach the new serial, a full one ends at the second copy of its SOA.
+fn complete_transfer(
+    records: &[DnsAnswer],
+    record_type: RecordType,
+    current_serial: Option<u32>,
+) -> anyhow::Result<Option<Transfer>> {
+    let new_serial = serial(&records[0]).context("transfer does not start with an SOA")?;
+    if records.len() == 1 {
+        // A lone SOA answering an IXFR tells us we are up to date.
+        let up_to_date = record_type == RecordType::IXFR
+            && current_serial.is_some_and(|current| !serial_newer(new_serial, current));
+        return Ok(up_to_date.then_some(Transfer::UpToDate));
+    }
+    let last = &records[records.len() - 1];
+    if serial(last) != Some(new_serial) {
+        return Ok(None);
+    }
+    let body = &records[1..records.len() - 1];
+    let is_incremental = record_type == RecordType::IXFR
+        && body
+            .first()
+            .is_some_and(|record| record.record_type == RecordType::SOA);
+    if !is_incremental {
+        return Ok(Some(Transfer::Full(records[..records.len() - 1].to_vec())));
+    }
+    match Difference::from_records(body) {
+        Ok(differences)
+            if differences
+                .last()
+                .is_some_and(|difference| difference.new_serial() == new_serial) =>
+        {
+            Ok(Some(Transfer::Incremental(differences)))
+        }
+        // The SOA seen last started a difference still coming in.
+        _ => Ok(None),
+    }
+}
@@ -0,0 +1,214 @@
+// A dig-like client for sending hand-built queries to any server and
+// printing the reply section by section.
+#![allow(clippy::needless_return)]
+
+use anyhow::{anyhow, bail, Context};
+use clap::Parser;
+use dns_starter_rust::adapters::tcp;
+use dns_starter_rust::models::presentation;
+use dns_starter_rust::models::{
+    Class, DnsHeader, DnsPacket, DnsQuestion, Edns, EdnsOption, RecordType,
+};
+use dns_starter_rust::traits::{Decodable, Encodable};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs, UdpSocket};
+use std::time::{Duration, Instant};
+
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    // [@server] [name] [type] [class], in any order as dig takes them
+    query: Vec<String>,
+    // Port to send the query to
+    #[clap(short, long, default_value_t = 53)]
+    port: u16,
+    // Send the query over TCP instead of UDP
+    #[clap(long)]
+    tcp: bool,
+    // Do not retry over TCP when a UDP reply comes back truncated
+    #[clap(long)]
+    ignore_tc: bool,
+    // Clear the RD flag so the server does not recurse for us
+    #[clap(long)]
+    norecurse: bool,
+    // Send the query without an OPT record
+    #[clap(long, conflicts_with_all = ["bufsize", "dnssec", "ednsopt"])]
+    noedns: bool,
+    // UDP payload size to advertise with EDNS
+    #[clap(long, default_value_t = 1232)]
+    bufsize: u16,
+    // Set the DO bit to ask for DNSSEC records
+    #[clap(long)]
+    dnssec: bool,
+    // EDNS options as CODE or CODE:HEXDATA, may be given multiple times
+    #[clap(long, value_parser = parse_edns_option)]
+    ednsopt: Vec<EdnsOption>,
+    // Seconds to wait for a reply
+    #[clap(long, default_value_t = 5)]
+    timeout: u64,
+}
+
+fn parse_edns_option(option: &str) -> Result<EdnsOption, String> {
+    let (code, data) = option.split_once(':').unwrap_or((option, ""));
+    let code = code
+        .parse::<u16>()
+        .map_err(|_| format!("Invalid option code '{}'", code))?;
+    if !data.len().is_multiple_of(2) {
+        return Err(format!("Odd number of hex digits in '{}'", data));
+    }
+    let data = (0..data.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&data[i..i + 2], 16))
+        .collect::<Result<Vec<u8>, _>>()
+        .map_err(|_| format!("Invalid hex data '{}'", data))?;
+    return Ok(EdnsOption { code, data });
+}
+
+// What the positional arguments ask for, with dig's defaults of the root
+// name, an A record and the IN class.
+struct Query {
+    server: String,
+    dns_question: DnsQuestion,
+}
+
+fn parse_query(arguments: &[String]) -> anyhow::Result<Query> {
+    let mut server = None;
+    let mut name = None;
+    let mut record_type = None;
+    let mut class = None;
+    for argument in arguments {
+        if let Some(address) = argument.strip_prefix('@') {
+            server = Some(address.to_string());
+        } else if record_type.is_none() && argument.parse::<RecordType>().is_ok() {
+            record_type = argument.parse::<RecordType>().ok();
+        } else if class.is_none() && argument.parse::<Class>().is_ok() {
+            class = argument.parse::<Class>().ok();
+        } else if name.is_none() {
+            name = Some(argument.clone());
+        } else {
+            bail!("Unexpected argument '{}'", argument);
+        }
+    }
+    return Ok(Query {
+        server: server.unwrap_or_else(|| "127.0.0.1".to_string()),
+        dns_question: DnsQuestion {
+            // Names are always taken as absolute, as dig does by default.
+            labels: presentation::parse_name(name.as_deref().unwrap_or("."), Some(&vec![]))
+                .map_err(|e| anyhow!(e))?,
+            record_type: record_type.unwrap_or(RecordType::A),
+            class: class.unwrap_or(Class::IN),
+        },
+    });
+}
+
+fn resolve_server(server: &str, port: u16) -> anyhow::Result<SocketAddr> {
+    // A bare IPv6 address has to be bracketed before a port can follow.
+    let with_port = match server.contains(':') && !server.starts_with('[') {
+        true => format!("[{}]:{}", server, port),
+        false => format!("{}:{}", server, port),
+    };
+    return with_port
+        .to_socket_addrs()
+        .ok()
+        .and_then(|mut addresses| addresses.next())
+        .with_context(|| format!("Failed to resolve server '{}'", server));
+}
+
+fn exchange_udp(
+    server: SocketAddr,
+    request: &[u8],
+    dns_request: &DnsPacket,
+    timeout: Duration,
+) -> anyhow::Result<(Vec<u8>, DnsPacket)> {
+    let bind_address = match server {
+        SocketAddr::V4(_) => "0.0.0.0:0",
+        SocketAddr::V6(_) => "[::]:0",
+    };
+    let udp_socket = UdpSocket::bind(bind_address).context("Failed to bind socket")?;
+    udp_socket.connect(server)?;
+    udp_socket.set_read_timeout(Some(timeout))?;
+    udp_socket.send(request).context("Failed to send query")?;
+    let mut buf = vec![0; 65535];
+    loop {
+        let size = udp_socket.recv(&mut buf).context("No reply from server")?;
+        // Anything not answering our ID is a late reply to someone else.
+        if let Ok(reply) = DnsPacket::decode(&buf[..size]) {
+            if reply.dns_header.packet_identifier == dns_request.dns_header.packet_identifier {
+                return Ok((buf[..size].to_vec(), reply));
+            }
+        }
+    }
+}
+
+fn exchange_tcp(
+    server: SocketAddr,
+    request: &[u8],
+    timeout: Duration,
+) -> anyhow::Result<(Vec<u8>, DnsPacket)> {
+    let mut tcp_stream = TcpStream::connect_timeout(&server, timeout)
+        .with_context(|| format!("Failed to connect to {}", server))?;
+    tcp_stream.set_read_timeout(Some(timeout))?;
+    tcp::write_message(&mut tcp_stream, request).context("Failed to send query")?;
+    let reply = tcp::read_message(&mut tcp_stream)
+        .context("No reply from server")?
+        .context("Server closed the connection without replying")?;
+    let dns_reply = DnsPacket::decode(&reply).context("Failed to decode reply")?;
+    return Ok((reply, dns_reply));
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+    let query = parse_query(&args.query)?;
+    let server = resolve_server(&query.server, args.port)?;
+    let timeout = Duration::from_secs(args.timeout);
+    let edns = match args.noedns {
+        true => None,
+        false => {
+            let mut edns = Edns::new(args.bufsize);
+            edns.dnssec_ok = args.dnssec;
+            edns.options = args.ednsopt.clone();
+            Some(edns)
+        }
+    };
+    let dns_request = DnsPacket {
+        dns_header: DnsHeader::new_query(rand::random(), !args.norecurse),
+        dns_questions: vec![query.dns_question.clone()],
+        dns_answers: vec![],
+        dns_authorities: vec![],
+        dns_additionals: vec![],
+        edns,
+    };
+    let request = dns_request.encode();
+    println!(
+        "; <<>> dns-query {} <<>> @{} {}",
+        env!("CARGO_PKG_VERSION"),
+        query.server,
+        query.dns_question
+    );
+    let start = Instant::now();
+    let (mut reply, mut dns_reply) = match args.tcp {
+        true => exchange_tcp(server, &request, timeout)?,
+        false => exchange_udp(server, &request, &dns_request, timeout)?,
+    };
+    let mut over_tcp = args.tcp;
+    if dns_reply.dns_header.truncation && !over_tcp && !args.ignore_tc {
+        println!(";; Truncated, retrying in TCP mode.");
+        (reply, dns_reply) = exchange_tcp(server, &request, timeout)?;
+        over_tcp = true;
+    }
+    let query_time = start.elapsed();
+    println!(";; Got answer:");
+    println!("{}", dns_reply);
+    println!(";; Query time: {} msec", query_time.as_millis());
+    println!(
+        ";; SERVER: {}#{}({}) ({})",
+        server.ip(),
+        server.port(),
+        query.server,
+        match over_tcp {
+            true => "TCP",
+            false => "UDP",
+        }
+    );
+    println!(";; MSG SIZE  rcvd: {}", reply.len());
+    return Ok(());
+}
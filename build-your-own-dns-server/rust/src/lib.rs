@@ -0,0 +1,5 @@
+#![allow(clippy::needless_return)]
+
+pub mod adapters;
+pub mod models;
+pub mod traits;
@@ -1,75 +1,1496 @@
-mod models;
-mod traits;
+#![allow(clippy::needless_return)]
 
-use crate::traits::Encodable;
+use anyhow::Context;
 use clap::Parser;
-use models::{DnsAnswer, DnsHeader, DnsPacket};
-use std::net::UdpSocket;
+use dns_starter_rust::adapters::forwarder::Forwarder;
+use dns_starter_rust::adapters::query_log::{QueryLog, QueryRecord};
+use dns_starter_rust::adapters::recursive_resolver::RecursiveResolver;
+use dns_starter_rust::adapters::upstream_pool::{Strategy, UpstreamPool};
+use dns_starter_rust::adapters::{
+    blocklist_file, hosts_file, metrics_exporter, tcp, zone_file, zone_transfer,
+};
+use dns_starter_rust::models::blocklist::DomainSet;
+use dns_starter_rust::models::edns::MIN_UDP_PAYLOAD_SIZE;
+use dns_starter_rust::models::journal;
+use dns_starter_rust::models::metrics::Metrics;
+use dns_starter_rust::models::tsig::{self, Signer, Verification};
+use dns_starter_rust::models::update;
+use dns_starter_rust::models::{
+    Acl, Authority, BlockResponse, Blocklist, Cache, Class, DnsAnswer, DnsHeader, DnsPacket,
+    DnsQuestion, Edns, Hosts, Label, Network, OperationCode, RData, RateLimitAction,
+    RateLimitConfig, RateLimiter, RecordType, ResponseCode, Secondary, Validation, Validator, Zone,
+    ZoneLookup,
+};
+use dns_starter_rust::traits::{Decodable, Encodable};
+use std::collections::HashMap;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, TcpStream, ToSocketAddrs, UdpSocket};
+use std::sync::mpsc::{self, Receiver, TrySendError};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// How long a TCP client may keep a connection open without sending a query.
+// Source: https://www.rfc-editor.org/rfc/rfc7766#section-6.2.3
+const TCP_IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// Datagrams that may wait for a UDP worker, per worker. Past that new queries
+// are dropped instead of queueing behind ones that would be answered late.
+const UDP_QUEUE_PER_WORKER: usize = 64;
+
+// Zone transfers are split into messages of at most this size, BIND's
+// default, well below the 64 KiB a TCP message could take.
+const TRANSFER_MESSAGE_SIZE: usize = 20480;
+
+// How often secondary zones are looked at to see whether one is due for a
+// check of its SOA, which bounds how long a NOTIFY waits to be acted on.
+const SECONDARY_POLL_INTERVAL: Duration = Duration::from_secs(1);
 
 #[derive(Parser, Debug, Clone)]
 #[clap(author, version, about, long_about = None)]
 struct Args {
-    #[clap(short, long, default_value = "")]
-    resolver: String,
+    // Upstream resolvers to forward to, may be given multiple times or as a
+    // comma separated list
+    #[clap(short, long, value_delimiter = ',', conflicts_with = "root_hints")]
+    resolver: Vec<String>,
+    // How to pick among the upstreams: failover, round-robin or fastest
+    #[clap(long, default_value = "failover")]
+    upstream_strategy: Strategy,
+    // Names under a suffix go to other upstreams than the default ones, e.g.
+    // "corp.internal=10.0.0.53,10.0.1.53". The longest matching suffix wins.
+    #[clap(long = "forward", value_parser = parse_name_and_servers)]
+    forwarding_rules: Vec<(Vec<Label>, Vec<String>)>,
+    // Resolve iteratively from the root servers in this hints file instead of
+    // forwarding to a resolver
+    #[clap(long)]
+    root_hints: Option<String>,
+    // Port name servers are queried on in recursive mode
+    #[clap(long, default_value_t = 53)]
+    name_server_port: u16,
+    // Address to serve DNS on, over both UDP and TCP
+    #[clap(long, default_value = "127.0.0.1:2053")]
+    listen: String,
+    // Threads answering UDP queries, so that one waiting on an upstream does
+    // not hold up the others
+    #[clap(long, default_value_t = 16, value_parser = clap::value_parser!(u16).range(1..))]
+    udp_workers: u16,
+    // RFC 1035 master files to serve authoritatively, may be given multiple
+    // times. A journal of changes is read from the same path with ".jnl"
+    // appended if it exists.
+    #[clap(short, long)]
+    zone: Vec<String>,
+    // Zones to keep a copy of from their primaries as a secondary, e.g.
+    // "example.com=192.0.2.1,192.0.2.2:5300". May be given multiple times.
+    #[clap(long = "secondary", value_parser = parse_name_and_servers)]
+    secondary_zones: Vec<(Vec<Label>, Vec<String>)>,
+    // Clients allowed to transfer our zones with AXFR and IXFR, as addresses
+    // or networks such as 192.0.2.0/24. Nobody may without any.
+    #[clap(long, value_delimiter = ',')]
+    allow_transfer: Vec<Network>,
+    // Keys dynamic updates to our zones have to be signed with, as
+    // [hmac-sha256:]NAME:SECRET with the secret in base64. May be given
+    // multiple times. Updates are refused without any.
+    #[clap(long)]
+    tsig_key: Vec<tsig::Key>,
+    // EDNS(0) UDP payload size we advertise and size our receive buffers to.
+    // Source: https://www.dnsflagday.net/2020/
+    #[clap(long, default_value_t = 1232)]
+    udp_payload_size: u16,
+    // Maximum number of RRsets kept from upstream replies, 0 disables the cache
+    #[clap(long, default_value_t = 10000)]
+    cache_size: usize,
+    // How long to wait for each attempt at an upstream query
+    #[clap(long, default_value_t = 2000)]
+    upstream_timeout_ms: u64,
+    // Attempts made after the first one before answering SERVFAIL
+    #[clap(long, default_value_t = 2)]
+    upstream_retries: u32,
+    // Hosts-format or domain-list files of names to sinkhole, may be given
+    // multiple times
+    #[clap(long)]
+    blocklist: Vec<String>,
+    // Names that are never blocked, in the same formats as the blocklists
+    #[clap(long)]
+    allowlist: Vec<String>,
+    // How blocked names are answered: nxdomain, null (0.0.0.0 and ::) or refused
+    #[clap(long, default_value = "nxdomain")]
+    block_response: BlockResponse,
+    // /etc/hosts-format files of static addresses answered locally, may be
+    // given multiple times
+    #[clap(long)]
+    hosts: Vec<String>,
+    // Write a JSON line per query to this file, or to stdout if "-". Every
+    // other message goes to stderr, so stdout holds nothing but the log.
+    #[clap(long)]
+    query_log: Option<String>,
+    // Write every query and response to this file as a dnstap frame stream
+    #[clap(long)]
+    dnstap: Option<String>,
+    // Address to serve Prometheus metrics on at /metrics, e.g. 127.0.0.1:9153
+    #[clap(long)]
+    metrics_listen: Option<String>,
+    // Responses per second each client network gets over UDP for the same
+    // name and type, 0 disables response rate limiting
+    #[clap(long, default_value_t = 0)]
+    rate_limit_responses: u32,
+    // Rate for NXDOMAIN responses per zone, the responses rate if left out
+    #[clap(long)]
+    rate_limit_nxdomains: Option<u32>,
+    // Rate for error responses, the responses rate if left out
+    #[clap(long)]
+    rate_limit_errors: Option<u32>,
+    // Seconds a limited client has to stay under its rate to be forgiven
+    #[clap(long, default_value_t = 15)]
+    rate_limit_window: u32,
+    // Every n-th limited response is sent truncated instead of dropped, so
+    // that clients can retry over TCP. 0 drops them all.
+    #[clap(long, default_value_t = 2)]
+    rate_limit_slip: u32,
+    // Prefix lengths grouping IPv4 and IPv6 clients into networks
+    #[clap(long, default_value_t = 24, value_parser = clap::value_parser!(u8).range(0..=32))]
+    rate_limit_ipv4_prefix: u8,
+    #[clap(long, default_value_t = 56, value_parser = clap::value_parser!(u8).range(0..=128))]
+    rate_limit_ipv6_prefix: u8,
+    // Files of DS or DNSKEY records to validate forwarded answers against,
+    // may be given multiple times. Validation is off without any.
+    #[clap(long)]
+    trust_anchor: Vec<String>,
+}
+
+fn parse_name_and_servers(rule: &str) -> Result<(Vec<Label>, Vec<String>), String> {
+    let (name, servers) = rule
+        .split_once('=')
+        .ok_or_else(|| format!("Expected NAME=SERVER[,SERVER...], got '{}'", rule))?;
+    let servers: Vec<String> = servers
+        .split(',')
+        .filter(|server| !server.is_empty())
+        .map(String::from)
+        .collect();
+    if servers.is_empty() {
+        return Err(format!("No server given for '{}'", name));
+    }
+    let labels = Label::from_domain_name(name)
+        .map_err(|error| format!("Invalid name '{}': {}", name, error))?;
+    return Ok((labels, servers));
+}
+
+// Accepts "address" or "address:port", the port being 53 if left out.
+fn resolve_upstream_address(upstream: &str) -> SocketAddr {
+    return upstream
+        .to_socket_addrs()
+        .or_else(|_| (upstream, 53).to_socket_addrs())
+        .ok()
+        .and_then(|mut addresses| addresses.next())
+        .unwrap_or_else(|| panic!("Failed to resolve upstream address '{}'", upstream));
+}
+
+// Everything needed to answer a request, shared by the UDP loop and the TCP
+// connection threads.
+struct Server {
+    config: Args,
+    // Secondary zones are replaced with newer copies while we serve.
+    authority: RwLock<Authority>,
+    secondaries: Vec<Secondary>,
+    // The journals of the zones loaded from master files, by origin, which
+    // are the zones that take dynamic updates.
+    journals: HashMap<String, String>,
+    transfer_acl: Acl,
+    tsig_keys: Vec<tsig::Key>,
+    recursive_resolver: Option<RecursiveResolver>,
+    forwarder: Option<Forwarder>,
+    cache: Mutex<Cache>,
+    blocklist: Option<Blocklist>,
+    hosts: Hosts,
+    query_log: Option<QueryLog>,
+    metrics: Arc<Metrics>,
+    rate_limiter: Option<RateLimiter>,
+    validator: Option<Validator>,
+}
+
+// How a request was resolved, collected on the way for the query log.
+#[derive(Debug, Default)]
+struct QueryTrace {
+    upstreams: Vec<SocketAddr>,
+    cache_hit: Option<bool>,
 }
 
 // Domain name specification: https://www.rfc-editor.org/rfc/rfc1035
 
-#[allow(dead_code)]
-fn generate_response(dns_request: DnsPacket) -> DnsPacket {
+fn generate_response(dns_request: DnsPacket, authority: &Authority) -> DnsPacket {
+    let dnssec_ok = dns_request.edns.as_ref().is_some_and(|edns| edns.dnssec_ok);
+    let mut dns_header = DnsHeader::from_request_header(dns_request.dns_header);
+    let mut dns_answers = vec![];
+    let mut dns_authorities = vec![];
+    let mut dns_additionals = vec![];
+    if dns_header.response_code == ResponseCode::NoError as u8 {
+        dns_header.authoritative_answer = true;
+        for dns_question in dns_request.dns_questions.iter() {
+            let zone = authority.find_zone_for(dns_question);
+            match zone.map(|zone| (zone, zone.lookup(dns_question))) {
+                Some((zone, ZoneLookup::Answer(answers))) => {
+                    dns_answers.extend(with_signatures(zone, answers, dnssec_ok))
+                }
+                Some((zone, ZoneLookup::NoData)) => dns_authorities.extend(with_signatures(
+                    zone,
+                    zone.negative_soa().into_iter().collect(),
+                    dnssec_ok,
+                )),
+                Some((zone, ZoneLookup::NxDomain(chain))) => {
+                    dns_header.response_code = ResponseCode::NameError as u8;
+                    dns_answers.extend(with_signatures(zone, chain, dnssec_ok));
+                    dns_authorities.extend(with_signatures(
+                        zone,
+                        zone.negative_soa().into_iter().collect(),
+                        dnssec_ok,
+                    ));
+                }
+                Some((_, ZoneLookup::Referral { name_servers, glue })) => {
+                    dns_header.authoritative_answer = false;
+                    dns_authorities.extend(name_servers);
+                    dns_additionals.extend(glue);
+                }
+                None => {
+                    dns_header.authoritative_answer = false;
+                    dns_header.response_code = ResponseCode::Refused as u8;
+                }
+            }
+        }
+    }
+    dns_header.answer_record_count = dns_answers.len() as u16;
+    dns_header.authority_record_count = dns_authorities.len() as u16;
+    return DnsPacket {
+        dns_header,
+        dns_questions: dns_request.dns_questions,
+        dns_answers,
+        dns_authorities,
+        dns_additionals,
+        edns: None,
+    };
+}
+
+// The records followed by the RRSIGs covering them, if the client asked for
+// DNSSEC records with DO.
+// Source: https://www.rfc-editor.org/rfc/rfc4035#section-3.1.1
+fn with_signatures(zone: &Zone, mut records: Vec<DnsAnswer>, dnssec_ok: bool) -> Vec<DnsAnswer> {
+    if !dnssec_ok {
+        return records;
+    }
+    let mut signatures: Vec<DnsAnswer> = vec![];
+    for record in records.iter() {
+        if record.record_type == RecordType::RRSIG {
+            continue;
+        }
+        for signature in zone.signatures(&record.name, record.record_type) {
+            if !signatures.contains(&signature) {
+                signatures.push(signature);
+            }
+        }
+    }
+    records.extend(signatures);
+    return records;
+}
+
+// Names in the hosts file are answered from it alone, an address of another
+// family than the one asked for gives an empty answer rather than going
+// upstream.
+fn hosts_response(dns_request: DnsPacket, hosts: &Hosts) -> DnsPacket {
+    let mut dns_header = DnsHeader::from_request_header(dns_request.dns_header);
+    dns_header.authoritative_answer = true;
+    let dns_answers: Vec<DnsAnswer> = dns_request
+        .dns_questions
+        .iter()
+        .filter_map(|dns_question| hosts.lookup(dns_question))
+        .flatten()
+        .collect();
+    dns_header.answer_record_count = dns_answers.len() as u16;
     return DnsPacket {
-        dns_header: DnsHeader::from_request_header(dns_request.dns_header),
-        dns_questions: dns_request.dns_questions.clone(),
-        dns_answers: dns_request
+        dns_header,
+        dns_questions: dns_request.dns_questions,
+        dns_answers,
+        dns_authorities: vec![],
+        dns_additionals: vec![],
+        edns: None,
+    };
+}
+
+// Answers every question by resolving it from the root down.
+fn recursive_response(dns_request: DnsPacket, recursive_resolver: &RecursiveResolver) -> DnsPacket {
+    let mut dns_header = DnsHeader::from_request_header(dns_request.dns_header.clone());
+    dns_header.recursion_available = true;
+    let mut dns_answers = vec![];
+    let mut dns_authorities = vec![];
+    for dns_question in dns_request.dns_questions.iter() {
+        match recursive_resolver.resolve(dns_question) {
+            Ok(resolution) => {
+                if resolution.response_code != ResponseCode::NoError {
+                    dns_header.response_code = resolution.response_code as u8;
+                }
+                dns_answers.extend(resolution.answers);
+                dns_authorities.extend(resolution.authorities);
+            }
+            Err(e) => {
+                eprintln!(
+                    "Failed to resolve '{}': {:#}",
+                    Label::to_key(&dns_question.labels),
+                    e
+                );
+                let mut dns_response = error_response(
+                    dns_request.dns_header,
+                    dns_request.dns_questions,
+                    ResponseCode::ServerFailure,
+                );
+                dns_response.dns_header.recursion_available = true;
+                return dns_response;
+            }
+        }
+    }
+    return DnsPacket {
+        dns_header,
+        dns_questions: dns_request.dns_questions,
+        dns_answers,
+        dns_authorities,
+        dns_additionals: vec![],
+        edns: None,
+    };
+}
+
+// How long clients may remember the answer for a blocked name.
+const BLOCKED_TIME_TO_LIVE: u32 = 60;
+
+// Blocked names are answered here and never forwarded.
+fn blocked_response(dns_request: DnsPacket, block_response: BlockResponse) -> DnsPacket {
+    let response_code = match block_response {
+        BlockResponse::NxDomain => ResponseCode::NameError,
+        BlockResponse::Refused => ResponseCode::Refused,
+        BlockResponse::Null => ResponseCode::NoError,
+    };
+    let mut dns_response = error_response(
+        dns_request.dns_header,
+        dns_request.dns_questions,
+        response_code,
+    );
+    if block_response == BlockResponse::Null {
+        dns_response.dns_answers = dns_response
             .dns_questions
             .iter()
-            .map(|dns_question| DnsAnswer::from_request_question(&dns_question))
-            .collect(),
+            .filter_map(|dns_question| {
+                let rdata = match dns_question.record_type {
+                    RecordType::A => RData::A(Ipv4Addr::UNSPECIFIED),
+                    RecordType::AAAA => RData::AAAA(Ipv6Addr::UNSPECIFIED),
+                    _ => return None,
+                };
+                Some(DnsAnswer::new(
+                    dns_question.labels.clone(),
+                    dns_question.record_type,
+                    dns_question.class,
+                    BLOCKED_TIME_TO_LIVE,
+                    rdata,
+                ))
+            })
+            .collect();
+    }
+    return dns_response;
+}
+
+// An empty reply to a request we could not process, carrying only the
+// response code.
+fn error_response(
+    request_header: DnsHeader,
+    dns_questions: Vec<DnsQuestion>,
+    response_code: ResponseCode,
+) -> DnsPacket {
+    let mut dns_header = DnsHeader::from_request_header(request_header);
+    dns_header.response_code = response_code as u8;
+    dns_header.question_count = dns_questions.len() as u16;
+    dns_header.answer_record_count = 0;
+    return DnsPacket {
+        dns_header,
+        dns_questions,
+        dns_answers: vec![],
+        dns_authorities: vec![],
+        dns_additionals: vec![],
+        edns: None,
     };
 }
 
+// A reply built from cached records alone, for a request with one question.
+fn cached_response(dns_request: &DnsPacket, cache: &Mutex<Cache>) -> Option<DnsPacket> {
+    let dns_answers = cache
+        .lock()
+        .unwrap()
+        .lookup(dns_request.dns_questions.first()?, Instant::now())?;
+    let mut dns_header = DnsHeader::from_request_header(dns_request.dns_header.clone());
+    dns_header.recursion_available = true;
+    return Some(DnsPacket {
+        dns_header,
+        dns_questions: dns_request.dns_questions.clone(),
+        dns_answers,
+        dns_authorities: vec![],
+        dns_additionals: vec![],
+        edns: None,
+    });
+}
+
+// Questions the cache can answer are taken from it, the rest are forwarded
+// upstream in parallel. A single question left unanswered fails the request.
+// Along with the response come the records of the upstream replies worth
+// caching, which is left to the caller once the response checks out.
 fn resolve_response_upstream(
-    udp_socket: &UdpSocket,
-    upstream_addr: &String,
+    forwarder: &Forwarder,
+    server: &Server,
     dns_request: DnsPacket,
-) -> DnsPacket {
-    let upstream_requests = dns_request.split();
-    let upstream_replies = upstream_requests
+    query_trace: &mut QueryTrace,
+) -> anyhow::Result<(DnsPacket, Vec<DnsAnswer>)> {
+    let mut upstream_replies: Vec<Option<DnsPacket>> = vec![];
+    let mut upstream_requests: Vec<DnsPacket> = vec![];
+    let mut forwarded_questions: Vec<Option<DnsQuestion>> = vec![];
+    for mut upstream_request in dns_request.split() {
+        let cached = cached_response(&upstream_request, &server.cache);
+        server.metrics.record_cache_lookup(cached.is_some());
+        if cached.is_none() {
+            // Options such as client subnet or cookies are passed on as they
+            // came, only the payload size is ours as we receive the reply.
+            if let Some(edns) = upstream_request.edns.as_mut() {
+                edns.udp_payload_size = server.config.udp_payload_size;
+            }
+            // Validating needs the signatures, whether the client wants
+            // them or not.
+            if server.validator.is_some() {
+                upstream_request
+                    .edns
+                    .get_or_insert_with(|| Edns::new(server.config.udp_payload_size))
+                    .dnssec_ok = true;
+            }
+            forwarded_questions.push(upstream_request.dns_questions.first().cloned());
+            upstream_requests.push(upstream_request);
+        }
+        upstream_replies.push(cached);
+    }
+    query_trace.cache_hit = Some(upstream_requests.is_empty());
+    let mut forwarded = forwarder
+        .forward(upstream_requests)
         .into_iter()
-        .map(|upstream_request| {
-            udp_socket
-                .send_to(&upstream_request.encode(), upstream_addr)
-                .expect("Failed to send request upstream");
-            let mut forward_buf = [0; 512];
-            udp_socket
-                .recv_from(&mut forward_buf)
-                .expect("Failed to receive response from upstream");
-            return DnsPacket::decode(forward_buf);
+        .zip(forwarded_questions);
+    let mut records_to_cache: Vec<DnsAnswer> = vec![];
+    let upstream_replies = upstream_replies
+        .into_iter()
+        .map(|cached| {
+            if let Some(dns_response) = cached {
+                return Ok(dns_response);
+            }
+            let (forwarded, dns_question) = forwarded
+                .next()
+                .context("Forwarder returned too few replies")?;
+            let (upstream_reply, upstream) = forwarded?;
+            query_trace.upstreams.push(upstream);
+            let dns_question = dns_question
+                .filter(|_| upstream_reply.dns_header.response_code == ResponseCode::NoError as u8);
+            if let Some(dns_question) = dns_question {
+                records_to_cache.extend(Cache::answer_chain(
+                    &dns_question,
+                    &upstream_reply.dns_answers,
+                    |name| forwarder.shares_upstreams(&dns_question.labels, name),
+                ));
+            }
+            return Ok(upstream_reply);
         })
+        .collect::<anyhow::Result<Vec<DnsPacket>>>()?;
+    let dns_response = DnsPacket::merge(upstream_replies).context("Request has no questions")?;
+    return Ok((dns_response, records_to_cache));
+}
+
+// Looks up the records of a name and type along with their signatures and
+// any proof that there are none, for the validator to follow the chain of
+// trust. None if the upstream failed.
+fn fetch_signed(
+    forwarder: &Forwarder,
+    udp_payload_size: u16,
+    labels: &[Label],
+    record_type: RecordType,
+) -> Option<Vec<DnsAnswer>> {
+    let mut edns = Edns::new(udp_payload_size);
+    edns.dnssec_ok = true;
+    let dns_request = DnsPacket {
+        dns_header: DnsHeader::new_query(rand::random(), true),
+        dns_questions: vec![DnsQuestion {
+            labels: labels.to_vec(),
+            record_type,
+            class: Class::IN,
+        }],
+        dns_answers: vec![],
+        dns_authorities: vec![],
+        dns_additionals: vec![],
+        edns: Some(edns),
+    };
+    let (dns_response, _) = forwarder.forward(vec![dns_request]).pop()?.ok()?;
+    let response_code = dns_response.response_code();
+    if response_code != ResponseCode::NoError as u16
+        && response_code != ResponseCode::NameError as u16
+    {
+        return None;
+    }
+    let mut records = dns_response.dns_answers;
+    records.extend(dns_response.dns_authorities);
+    return Some(records);
+}
+
+// Checks the signatures of a forwarded answer against our trust anchors.
+// Secure answers get AD for clients that show they understand it with DO or
+// AD, bogus ones become SERVFAIL unless the client set CD to get them
+// unchecked. Also tells whether the answer may be cached, which bogus and
+// unchecked ones may not.
+// Source: https://www.rfc-editor.org/rfc/rfc4035#section-3.2.3
+// Source: https://www.rfc-editor.org/rfc/rfc6840#section-5.8
+fn validate_response(
+    server: &Server,
+    forwarder: &Forwarder,
+    request_header: &DnsHeader,
+    dnssec_ok: bool,
+    mut dns_response: DnsPacket,
+) -> (DnsPacket, bool) {
+    let validator = match server.validator.as_ref() {
+        Some(validator) => validator,
+        None => return (dns_response, true),
+    };
+    dns_response.dns_header.set_authentic_data(false);
+    if request_header.checking_disabled() {
+        return (dns_response, false);
+    }
+    let fetch = |labels: &[Label], record_type: RecordType| {
+        fetch_signed(
+            forwarder,
+            server.config.udp_payload_size,
+            labels,
+            record_type,
+        )
+    };
+    match validator.validate(&dns_response, &fetch, SystemTime::now()) {
+        Validation::Secure => dns_response
+            .dns_header
+            .set_authentic_data(dnssec_ok || request_header.authentic_data()),
+        Validation::Insecure => {}
+        Validation::Bogus(reason) => {
+            eprintln!("DNSSEC validation failed: {}", reason);
+            let dns_response = error_response(
+                request_header.clone(),
+                dns_response.dns_questions,
+                ResponseCode::ServerFailure,
+            );
+            return (dns_response, false);
+        }
+    }
+    return (dns_response, true);
+}
+
+// Clients that did not set DO get no DNSSEC records other than those of the
+// types they asked for.
+// Source: https://www.rfc-editor.org/rfc/rfc4035#section-3.2.1
+fn strip_dnssec_records(dns_response: &mut DnsPacket) {
+    let asked_for: Vec<RecordType> = dns_response
+        .dns_questions
+        .iter()
+        .map(|dns_question| dns_question.record_type)
         .collect();
-    return DnsPacket::merge(upstream_replies);
+    for section in [
+        &mut dns_response.dns_answers,
+        &mut dns_response.dns_authorities,
+        &mut dns_response.dns_additionals,
+    ] {
+        section.retain(|record| {
+            !record.record_type.is_dnssec() || asked_for.contains(&record.record_type)
+        });
+    }
 }
 
-fn main() {
-    let udp_socket = UdpSocket::bind("127.0.0.1:2053").expect("Failed to bind to address");
-    let config = Args::parse();
-    let mut buf = [0; 512];
+// A reply only carries OPT if the request did, advertising our own payload
+// size and echoing the DO bit. Options in a forwarded reply are kept.
+// Source: https://www.rfc-editor.org/rfc/rfc6891#section-7
+fn attach_edns(config: &Args, request_edns: Option<&Edns>, dns_response: &mut DnsPacket) {
+    let request_edns = match request_edns {
+        Some(request_edns) => request_edns,
+        None => {
+            dns_response.edns = None;
+            return;
+        }
+    };
+    let response_code = dns_response.response_code();
+    let mut edns = dns_response
+        .edns
+        .take()
+        .unwrap_or_else(|| Edns::new(config.udp_payload_size));
+    edns.udp_payload_size = config.udp_payload_size;
+    edns.version = 0;
+    edns.dnssec_ok = request_edns.dnssec_ok;
+    dns_response.edns = Some(edns);
+    dns_response.set_response_code(response_code);
+}
+
+fn handle_request(
+    server: &Server,
+    dns_request: DnsPacket,
+    query_trace: &mut QueryTrace,
+) -> DnsPacket {
+    let request_edns = dns_request.edns.clone();
+    let mut dns_response = resolve_request(server, dns_request, query_trace);
+    if !request_edns.as_ref().is_some_and(|edns| edns.dnssec_ok) {
+        strip_dnssec_records(&mut dns_response);
+    }
+    attach_edns(&server.config, request_edns.as_ref(), &mut dns_response);
+    return dns_response;
+}
+
+// Questions inside one of our zones are answered authoritatively and names in
+// the hosts file from it, everything else is resolved recursively or goes to
+// the upstream resolver if either is configured.
+fn resolve_request(
+    server: &Server,
+    dns_request: DnsPacket,
+    query_trace: &mut QueryTrace,
+) -> DnsPacket {
+    let config = &server.config;
+    // We only speak EDNS version 0.
+    // Source: https://www.rfc-editor.org/rfc/rfc6891#section-6.1.3
+    if dns_request
+        .edns
+        .as_ref()
+        .is_some_and(|edns| edns.version > 0)
+    {
+        let mut dns_response = error_response(
+            dns_request.dns_header,
+            dns_request.dns_questions,
+            ResponseCode::NoError,
+        );
+        dns_response.edns = Some(Edns::new(config.udp_payload_size));
+        dns_response.set_response_code(ResponseCode::BadVersion as u16);
+        return dns_response;
+    }
+    if dns_request.dns_questions.is_empty() {
+        return error_response(dns_request.dns_header, vec![], ResponseCode::FormatError);
+    }
+    // A secondary zone we never got a copy of, or whose copy expired, can
+    // only be answered with SERVFAIL.
+    // Source: https://www.rfc-editor.org/rfc/rfc1034#section-4.3.5
+    let is_unavailable = dns_request.dns_questions.iter().any(|dns_question| {
+        server.secondaries.iter().any(|secondary| {
+            Label::is_subdomain(&dns_question.labels, &secondary.origin)
+                && !secondary.is_serving(Instant::now())
+        })
+    });
+    if is_unavailable {
+        return error_response(
+            dns_request.dns_header,
+            dns_request.dns_questions,
+            ResponseCode::ServerFailure,
+        );
+    }
+    let authority = server.authority.read().unwrap();
+    let is_authoritative = dns_request
+        .dns_questions
+        .iter()
+        .all(|dns_question| authority.find_zone(&dns_question.labels).is_some());
+    if is_authoritative {
+        return generate_response(dns_request, &authority);
+    }
+    drop(authority);
+    let is_local = dns_request
+        .dns_questions
+        .iter()
+        .all(|dns_question| server.hosts.contains(&dns_question.labels));
+    if is_local {
+        return hosts_response(dns_request, &server.hosts);
+    }
+    if let Some(blocklist) = server.blocklist.as_ref() {
+        let blocked = dns_request
+            .dns_questions
+            .iter()
+            .find(|dns_question| blocklist.is_blocked(&dns_question.labels));
+        if let Some(dns_question) = blocked {
+            eprintln!(
+                "Blocked query for '{}'",
+                Label::to_key(&dns_question.labels)
+            );
+            server.metrics.record_blocked_query();
+            return blocked_response(dns_request, blocklist.response);
+        }
+    }
+    // Forwarding rules take precedence over recursion, so that names such as
+    // internal domains can go to the resolvers that know them.
+    let forwarder = server.forwarder.as_ref().filter(|forwarder| {
+        dns_request
+            .dns_questions
+            .iter()
+            .all(|dns_question| forwarder.routes(&dns_question.labels))
+    });
+    let forwarder = match (forwarder, &server.recursive_resolver) {
+        (Some(forwarder), _) => forwarder,
+        (None, Some(recursive_resolver)) => {
+            return recursive_response(dns_request, recursive_resolver)
+        }
+        (None, None) => return generate_response(dns_request, &server.authority.read().unwrap()),
+    };
+    let request_header = dns_request.dns_header.clone();
+    let dns_questions = dns_request.dns_questions.clone();
+    let dnssec_ok = dns_request.edns.as_ref().is_some_and(|edns| edns.dnssec_ok);
+    match resolve_response_upstream(forwarder, server, dns_request, query_trace) {
+        Ok((dns_response, records_to_cache)) => {
+            let (dns_response, may_cache) =
+                validate_response(server, forwarder, &request_header, dnssec_ok, dns_response);
+            if may_cache {
+                server
+                    .cache
+                    .lock()
+                    .unwrap()
+                    .insert(&records_to_cache, Instant::now());
+            }
+            dns_response
+        }
+        Err(e) => {
+            eprintln!("Failed to resolve upstream: {:#}", e);
+            error_response(request_header, dns_questions, ResponseCode::ServerFailure)
+        }
+    }
+}
+
+// AXFR and IXFR requests, which are answered apart from the rest as they
+// can take more than one message.
+fn is_transfer_request(dns_request: &DnsPacket) -> bool {
+    return dns_request.dns_questions.len() == 1
+        && dns_request.dns_questions[0].record_type.is_zone_transfer();
+}
+
+// Transfers one of our zones to a client the ACL allows. An AXFR has to go
+// over TCP. An IXFR over UDP gets the changes if they fit in one message and
+// the SOA alone otherwise, telling the client to retry over TCP.
+// Source: https://www.rfc-editor.org/rfc/rfc5936#section-4.2
+// Source: https://www.rfc-editor.org/rfc/rfc1995#section-2
+fn transfer_responses(
+    server: &Server,
+    client: SocketAddr,
+    is_tcp: bool,
+    dns_request: DnsPacket,
+) -> Vec<DnsPacket> {
+    let dns_question = &dns_request.dns_questions[0];
+    let name = Label::to_key(&dns_question.labels);
+    let authority = server.authority.read().unwrap();
+    let zone = match authority.zone(&dns_question.labels) {
+        Some(zone) => zone,
+        None => {
+            return vec![error_response(
+                dns_request.dns_header,
+                dns_request.dns_questions,
+                ResponseCode::NotAuth,
+            )]
+        }
+    };
+    if !server.transfer_acl.allows(client.ip()) {
+        eprintln!("Refused transfer of '{}' to {}", name, client);
+        return vec![error_response(
+            dns_request.dns_header,
+            dns_request.dns_questions,
+            ResponseCode::Refused,
+        )];
+    }
+    let records = match dns_question.record_type {
+        RecordType::AXFR if !is_tcp => {
+            let mut dns_response = error_response(
+                dns_request.dns_header,
+                dns_request.dns_questions,
+                ResponseCode::NoError,
+            );
+            dns_response.dns_header.truncation = true;
+            return vec![dns_response];
+        }
+        RecordType::AXFR => zone.transfer_records(),
+        // The client tells the serial it has with an SOA in the authority
+        // section.
+        _ => match dns_request.dns_authorities.iter().find_map(journal::serial) {
+            Some(serial) => zone.incremental_transfer_records(serial),
+            None => {
+                return vec![error_response(
+                    dns_request.dns_header,
+                    dns_request.dns_questions,
+                    ResponseCode::FormatError,
+                )]
+            }
+        },
+    };
+    let max_size = match is_tcp {
+        true => TRANSFER_MESSAGE_SIZE,
+        false => max_udp_response_size(&dns_request),
+    };
+    let mut dns_responses = transfer_messages(&dns_request, records, max_size);
+    if !is_tcp && dns_responses.len() > 1 {
+        dns_responses = transfer_messages(
+            &dns_request,
+            zone.soa().into_iter().cloned().collect(),
+            max_size,
+        );
+    }
+    eprintln!(
+        "Transferring '{}' to {} in {} messages",
+        name,
+        client,
+        dns_responses.len()
+    );
+    return dns_responses;
+}
+
+// A primary telling us that one of our secondary zones changed. The zone is
+// checked right away, the SOA that may come along being only a hint.
+// Source: https://www.rfc-editor.org/rfc/rfc1996#section-3
+fn notify_response(server: &Server, client: SocketAddr, dns_request: DnsPacket) -> DnsPacket {
+    let secondary = match dns_request.dns_questions.as_slice() {
+        [dns_question] => server.secondaries.iter().find(|secondary| {
+            Label::to_key(&secondary.origin) == Label::to_key(&dns_question.labels)
+        }),
+        _ => {
+            return error_response(
+                dns_request.dns_header,
+                dns_request.dns_questions,
+                ResponseCode::FormatError,
+            )
+        }
+    };
+    let response_code = match secondary {
+        None => ResponseCode::NotAuth,
+        Some(secondary) if !secondary.is_primary(client.ip()) => {
+            eprintln!(
+                "Refused NOTIFY for '{}' from {}",
+                Label::to_key(&secondary.origin),
+                client
+            );
+            ResponseCode::Refused
+        }
+        Some(secondary) => {
+            eprintln!(
+                "Received NOTIFY for '{}' from {}",
+                Label::to_key(&secondary.origin),
+                client
+            );
+            secondary.notify(Instant::now());
+            ResponseCode::NoError
+        }
+    };
+    let mut dns_response = error_response(
+        dns_request.dns_header,
+        dns_request.dns_questions,
+        response_code,
+    );
+    dns_response.dns_header.authoritative_answer = response_code == ResponseCode::NoError;
+    return dns_response;
+}
+
+// Dynamic updates have to be signed with one of our TSIG keys, and are
+// answered with the zone section alone. The response is signed with the
+// same key, or carries the TSIG error if the signature did not check out.
+// Source: https://www.rfc-editor.org/rfc/rfc2136#section-3.8
+// Source: https://www.rfc-editor.org/rfc/rfc8945#section-5.3
+fn update_response(
+    server: &Server,
+    client: SocketAddr,
+    request: &[u8],
+    dns_request: DnsPacket,
+    now: u64,
+) -> (DnsPacket, Option<Signer>) {
+    let (response_code, signer) = match tsig::verify(request, &server.tsig_keys, now) {
+        Ok(Verification::Verified(signer)) => {
+            (apply_update(server, client, &dns_request), Some(signer))
+        }
+        Ok(Verification::Failed(signer)) => {
+            eprintln!(
+                "Refused update from {}: {}",
+                client,
+                tsig::error_mnemonic(signer.error)
+            );
+            (ResponseCode::NotAuth, Some(signer))
+        }
+        Ok(Verification::Unsigned) => {
+            eprintln!("Refused unsigned update from {}", client);
+            (ResponseCode::Refused, None)
+        }
+        Err(e) => {
+            eprintln!("Invalid TSIG in update from {}: {}", client, e);
+            (ResponseCode::FormatError, None)
+        }
+    };
+    let dns_response = error_response(
+        dns_request.dns_header,
+        dns_request.dns_questions,
+        response_code,
+    );
+    return (dns_response, signer);
+}
+
+// Makes the update to the zone named in the zone section, which has to be
+// one loaded from a master file. The change is written to its journal before
+// it is served, so that it survives a restart.
+fn apply_update(server: &Server, client: SocketAddr, dns_request: &DnsPacket) -> ResponseCode {
+    let origin = match dns_request.dns_questions.as_slice() {
+        [dns_question] if dns_question.record_type == RecordType::SOA => &dns_question.labels,
+        _ => return ResponseCode::FormatError,
+    };
+    let name = Label::to_key(origin);
+    let journal_path = match server.journals.get(&name) {
+        Some(journal_path) => journal_path,
+        None => return ResponseCode::NotAuth,
+    };
+    let mut authority = server.authority.write().unwrap();
+    let zone = match authority.zone_mut(origin) {
+        Some(zone) => zone,
+        None => return ResponseCode::NotAuth,
+    };
+    let difference =
+        match update::update(zone, &dns_request.dns_answers, &dns_request.dns_authorities) {
+            Ok(Some(difference)) => difference,
+            Ok(None) => return ResponseCode::NoError,
+            Err(response_code) => return response_code,
+        };
+    let serial = difference.new_serial();
+    if let Err(e) = zone_file::append_journal(journal_path, &difference) {
+        eprintln!("Failed to update '{}': {:#}", name, e);
+        return ResponseCode::ServerFailure;
+    }
+    if let Err(e) = zone.apply(difference) {
+        eprintln!("Failed to update '{}': {}", name, e);
+        return ResponseCode::ServerFailure;
+    }
+    eprintln!("Updated '{}' to serial {} for {}", name, serial, client);
+    return ResponseCode::NoError;
+}
+
+// Seconds since the Unix epoch, as TSIG counts time.
+fn unix_time(time: SystemTime) -> u64 {
+    return time
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+}
+
+// Packs the records into as few messages of at most `max_size` bytes as it
+// takes, the question only going into the first one.
+fn transfer_messages(
+    dns_request: &DnsPacket,
+    records: Vec<DnsAnswer>,
+    max_size: usize,
+) -> Vec<DnsPacket> {
+    let mut dns_header = DnsHeader::from_request_header(dns_request.dns_header.clone());
+    dns_header.authoritative_answer = true;
+    let empty_message = |dns_questions: Vec<DnsQuestion>| DnsPacket {
+        dns_header: dns_header.clone(),
+        dns_questions,
+        dns_answers: vec![],
+        dns_authorities: vec![],
+        dns_additionals: vec![],
+        edns: None,
+    };
+    // Room is left for the OPT record added to every message later on.
+    let base_size = |dns_packet: &DnsPacket| {
+        dns_packet.encode().len()
+            + dns_request
+                .edns
+                .as_ref()
+                .map_or(0, |edns| edns.encode().len())
+    };
+    let mut dns_responses: Vec<DnsPacket> = vec![];
+    let mut dns_response = empty_message(dns_request.dns_questions.clone());
+    let mut size = base_size(&dns_response);
+    for record in records {
+        // Its uncompressed encoding is the most a record can take.
+        let record_size = record.encode().len();
+        if size + record_size > max_size && !dns_response.dns_answers.is_empty() {
+            dns_responses.push(dns_response);
+            dns_response = empty_message(vec![]);
+            size = base_size(&dns_response);
+        }
+        size += record_size;
+        dns_response.dns_answers.push(record);
+    }
+    dns_responses.push(dns_response);
+    return dns_responses;
+}
+
+// The largest response a UDP client accepts, 512 bytes unless it said
+// otherwise with EDNS.
+// Source: https://www.rfc-editor.org/rfc/rfc6891#section-6.2.5
+fn max_udp_response_size(dns_request: &DnsPacket) -> usize {
+    return dns_request
+        .edns
+        .as_ref()
+        .map_or(MIN_UDP_PAYLOAD_SIZE as usize, |edns| {
+            edns.max_payload_size()
+        });
+}
+
+// A truncated response without records in place of one over the rate limit,
+// telling a real client to retry over TCP where addresses cannot be spoofed.
+fn slipped_response(dns_response: &DnsPacket) -> DnsPacket {
+    let mut dns_header = dns_response.dns_header.clone();
+    dns_header.truncation = true;
+    return DnsPacket {
+        dns_header,
+        dns_questions: dns_response.dns_questions.clone(),
+        dns_answers: vec![],
+        dns_authorities: vec![],
+        dns_additionals: vec![],
+        edns: dns_response.edns.clone(),
+    };
+}
+
+// Decodes a request, answers it and encodes the replies, which UDP clients
+// get cut down to the size they accept. Zone transfers over TCP take several
+// messages, everything else one. None at all if the request does not even
+// have a complete header with an ID to answer to, or if rate limiting drops
+// the reply.
+fn answer_message(
+    server: &Server,
+    client: SocketAddr,
+    is_tcp: bool,
+    request: &[u8],
+) -> Vec<Vec<u8>> {
+    let time = SystemTime::now();
+    let start = Instant::now();
+    let mut query_trace = QueryTrace::default();
+    let (dns_questions, dns_responses, max_size, signer) = match DnsPacket::decode(request) {
+        Ok(dns_request) => {
+            let max_size = match is_tcp {
+                true => usize::MAX,
+                false => max_udp_response_size(&dns_request),
+            };
+            for dns_question in dns_request.dns_questions.iter() {
+                server.metrics.record_query(dns_question.record_type);
+            }
+            let dns_questions = dns_request.dns_questions.clone();
+            let request_edns = dns_request.edns.clone();
+            let mut signer: Option<Signer> = None;
+            let dns_responses = match dns_request.dns_header.operation_code() {
+                code if code == OperationCode::Notify as u8 => {
+                    let mut dns_response = notify_response(server, client, dns_request);
+                    attach_edns(&server.config, request_edns.as_ref(), &mut dns_response);
+                    vec![dns_response]
+                }
+                code if code == OperationCode::Update as u8 => {
+                    let (mut dns_response, update_signer) =
+                        update_response(server, client, request, dns_request, unix_time(time));
+                    attach_edns(&server.config, request_edns.as_ref(), &mut dns_response);
+                    signer = update_signer;
+                    vec![dns_response]
+                }
+                // Other opcodes are refused before they get near the hosts
+                // file, blocklist or any upstream.
+                code if code != OperationCode::Query as u8 => {
+                    let mut dns_response = error_response(
+                        dns_request.dns_header,
+                        dns_request.dns_questions,
+                        ResponseCode::NotImplemented,
+                    );
+                    attach_edns(&server.config, request_edns.as_ref(), &mut dns_response);
+                    vec![dns_response]
+                }
+                _ if is_transfer_request(&dns_request) => {
+                    let mut dns_responses = transfer_responses(server, client, is_tcp, dns_request);
+                    for dns_response in dns_responses.iter_mut() {
+                        attach_edns(&server.config, request_edns.as_ref(), dns_response);
+                    }
+                    dns_responses
+                }
+                _ => vec![handle_request(server, dns_request, &mut query_trace)],
+            };
+            (dns_questions, dns_responses, max_size, signer)
+        }
+        Err(e) => {
+            eprintln!("Failed to decode request from {}: {}", client, e);
+            server.metrics.record_decode_error();
+            let request_header = match DnsHeader::decode(request) {
+                Ok(request_header) => request_header,
+                Err(_) => return vec![],
+            };
+            let dns_response = error_response(request_header, vec![], ResponseCode::FormatError);
+            (
+                vec![],
+                vec![dns_response],
+                MIN_UDP_PAYLOAD_SIZE as usize,
+                None,
+            )
+        }
+    };
+    // Only UDP responses can be reflected off us to a spoofed address.
+    let rate_limit = match (is_tcp, server.rate_limiter.as_ref()) {
+        (false, Some(rate_limiter)) => {
+            let rate_limit = rate_limiter.check(client.ip(), &dns_responses[0], Instant::now());
+            for event in rate_limiter.take_events() {
+                eprintln!("{}", event);
+            }
+            rate_limit
+        }
+        _ => RateLimitAction::Send,
+    };
+    let dns_responses = match rate_limit {
+        RateLimitAction::Slip => vec![slipped_response(&dns_responses[0])],
+        _ => dns_responses,
+    };
+    let responses: Vec<Vec<u8>> = match rate_limit {
+        RateLimitAction::Drop => vec![],
+        _ => dns_responses
+            .iter()
+            .map(|dns_response| match is_tcp {
+                true => dns_response.encode(),
+                false => dns_response.encode_truncated(max_size),
+            })
+            .map(|response| match signer.as_ref() {
+                Some(signer) => signer.sign(&response, unix_time(SystemTime::now())),
+                None => response,
+            })
+            .collect(),
+    };
+    for dns_response in dns_responses.iter().take(responses.len()) {
+        server.metrics.record_response(dns_response.response_code());
+    }
+    if let Some(query_log) = server.query_log.as_ref() {
+        let query_record = QueryRecord {
+            time,
+            client,
+            is_tcp,
+            dns_questions,
+            response_code: dns_responses[0].response_code(),
+            answer_count: dns_responses
+                .iter()
+                .map(|dns_response| dns_response.dns_answers.len())
+                .sum(),
+            upstreams: query_trace.upstreams,
+            cache_hit: query_trace.cache_hit,
+            rate_limit,
+            latency: start.elapsed(),
+        };
+        // Only the first message of a transfer goes to dnstap.
+        query_log.log(&query_record, request, responses.first().map(Vec::as_slice));
+    }
+    return responses;
+}
+
+// Serves queries from one TCP client until it closes the connection or stays
+// idle for too long, answering them in the order they came in.
+fn serve_tcp_connection(server: &Server, mut tcp_stream: TcpStream) {
+    let peer = match tcp_stream.peer_addr() {
+        Ok(peer) => peer,
+        Err(_) => return,
+    };
+    if let Err(e) = tcp_stream.set_read_timeout(Some(TCP_IDLE_TIMEOUT)) {
+        eprintln!("Failed to set timeout for {}: {}", peer, e);
+        return;
+    }
+    loop {
+        let request = match tcp::read_message(&mut tcp_stream) {
+            Ok(Some(request)) => request,
+            Ok(None) => return,
+            Err(e) => {
+                eprintln!("Error receiving data from {}: {}", peer, e);
+                return;
+            }
+        };
+        eprintln!("Received {} bytes from {} over TCP", request.len(), peer);
+        let responses = answer_message(server, peer, true, &request);
+        if responses.is_empty() {
+            return;
+        }
+        for response in responses {
+            if let Err(e) = tcp::write_message(&mut tcp_stream, &response) {
+                eprintln!("Failed to send response to {}: {}", peer, e);
+                return;
+            }
+        }
+    }
+}
+
+// Answers the datagrams handed over by the receiving thread, replying through
+// this worker's own handle on the socket.
+fn answer_udp(
+    server: &Server,
+    udp_socket: &UdpSocket,
+    receiver: &Mutex<Receiver<(Vec<u8>, SocketAddr)>>,
+) {
+    loop {
+        let received = receiver.lock().unwrap().recv();
+        let (request, source) = match received {
+            Ok(received) => received,
+            Err(_) => return,
+        };
+        eprintln!("Received {} bytes from {}", request.len(), source);
+        // Without a complete header there is no ID to answer to, and
+        // dropped responses are not sent at all.
+        for response in answer_message(server, source, false, &request) {
+            if let Err(e) = udp_socket.send_to(&response, source) {
+                eprintln!("Failed to send response to {}: {}", source, e);
+            }
+        }
+    }
+}
+
+// Receives UDP queries and hands them to a pool of workers, as answering one
+// may take seconds of upstream queries.
+fn serve_udp(server: Arc<Server>, udp_socket: UdpSocket) {
+    let workers = server.config.udp_workers as usize;
+    let (sender, receiver) = mpsc::sync_channel(workers * UDP_QUEUE_PER_WORKER);
+    let receiver = Arc::new(Mutex::new(receiver));
+    for _ in 0..workers {
+        let server = Arc::clone(&server);
+        let receiver = Arc::clone(&receiver);
+        let udp_socket = udp_socket.try_clone().expect("Failed to clone UDP socket");
+        thread::spawn(move || answer_udp(&server, &udp_socket, &receiver));
+    }
+    let mut buf = vec![0; server.config.udp_payload_size.max(MIN_UDP_PAYLOAD_SIZE) as usize];
     loop {
         match udp_socket.recv_from(&mut buf) {
             Ok((size, source)) => {
-                println!("Received {} bytes from {}", size, source);
-                let dns_request = DnsPacket::decode(buf);
-                // let dns_response = generate_response(dns_request);
-                let dns_response =
-                    resolve_response_upstream(&udp_socket, &config.resolver, dns_request);
-                udp_socket
-                    .send_to(&dns_response.encode(), source)
-                    .expect("Failed to send response");
+                if let Err(TrySendError::Full(_)) = sender.try_send((buf[..size].to_vec(), source))
+                {
+                    server.metrics.record_dropped_query();
+                }
             }
             Err(e) => {
                 eprintln!("Error receiving data: {}", e);
-                break;
             }
         }
     }
 }
+
+fn serve_tcp(server: Arc<Server>, tcp_listener: TcpListener) {
+    for tcp_stream in tcp_listener.incoming() {
+        match tcp_stream {
+            Ok(tcp_stream) => {
+                let server = Arc::clone(&server);
+                thread::spawn(move || serve_tcp_connection(&server, tcp_stream));
+            }
+            Err(e) => {
+                eprintln!("Error accepting connection: {}", e);
+            }
+        }
+    }
+}
+
+// Checks the SOA of a secondary zone at its primaries in turn and transfers
+// the zone from the first one that has a newer serial than our copy.
+fn refresh_secondary(server: &Server, secondary: &Secondary) {
+    let name = Label::to_key(&secondary.origin);
+    let current = server
+        .authority
+        .read()
+        .unwrap()
+        .zone(&secondary.origin)
+        .cloned();
+    let current_serial = current.as_ref().and_then(Zone::serial);
+    for primary in secondary.primaries.iter() {
+        let soa = match zone_transfer::query_soa(*primary, &secondary.origin) {
+            Ok(soa) => soa,
+            Err(e) => {
+                eprintln!("Failed to check SOA of '{}' at {}: {:#}", name, primary, e);
+                continue;
+            }
+        };
+        let serial = journal::serial(&soa).unwrap_or(0);
+        if current_serial
+            .is_some_and(|current_serial| !journal::serial_newer(serial, current_serial))
+        {
+            secondary.refreshed(&soa, Instant::now());
+            return;
+        }
+        match zone_transfer::transfer(*primary, &secondary.origin, current.as_ref()) {
+            Ok(zone) => {
+                eprintln!(
+                    "Transferred '{}' at serial {} from {}",
+                    name,
+                    zone.serial().unwrap_or(0),
+                    primary
+                );
+                if let Some(soa) = zone.soa() {
+                    secondary.refreshed(soa, Instant::now());
+                }
+                server.authority.write().unwrap().replace(zone);
+                return;
+            }
+            Err(e) => {
+                eprintln!("Failed to transfer '{}' from {}: {:#}", name, primary, e);
+            }
+        }
+    }
+    secondary.failed(Instant::now());
+    if current.is_some() && !secondary.is_serving(Instant::now()) {
+        eprintln!("Secondary zone '{}' expired", name);
+        server.authority.write().unwrap().remove(&secondary.origin);
+    }
+}
+
+fn maintain_secondaries(server: Arc<Server>) {
+    loop {
+        for secondary in server.secondaries.iter() {
+            if secondary.is_due(Instant::now()) {
+                refresh_secondary(&server, secondary);
+            }
+        }
+        thread::sleep(SECONDARY_POLL_INTERVAL);
+    }
+}
+
+fn main() {
+    let config = Args::parse();
+    let udp_socket = UdpSocket::bind(&config.listen).expect("Failed to bind to address");
+    let tcp_listener = TcpListener::bind(&config.listen).expect("Failed to bind to address");
+    let mut zones = vec![];
+    let mut journals = HashMap::new();
+    for zone_path in config.zone.iter() {
+        let zone = zone_file::load_zone(zone_path).expect("Failed to load zone");
+        journals.insert(
+            Label::to_key(&zone.origin),
+            zone_file::journal_path(zone_path),
+        );
+        zones.push(zone);
+    }
+    let authority = Authority::new(zones);
+    let metrics = Arc::new(Metrics::new());
+    let recursive_resolver = config.root_hints.as_ref().map(|root_hints_path| {
+        let root_hints =
+            zone_file::read_root_hints(root_hints_path).expect("Failed to load root hints");
+        RecursiveResolver::new(&root_hints, config.name_server_port)
+            .expect("Failed to set up recursive resolver")
+    });
+    let forwarder = match config.resolver.is_empty() && config.forwarding_rules.is_empty() {
+        true => None,
+        false => {
+            let upstream_pool = |upstreams: &Vec<String>| {
+                let addresses = upstreams
+                    .iter()
+                    .map(|upstream| resolve_upstream_address(upstream))
+                    .collect();
+                UpstreamPool::new(addresses, config.upstream_strategy)
+            };
+            let default_pool = match config.resolver.is_empty() {
+                true => None,
+                false => Some(upstream_pool(&config.resolver)),
+            };
+            let rules = config
+                .forwarding_rules
+                .iter()
+                .map(|(suffix, upstreams)| (suffix.clone(), upstream_pool(upstreams)))
+                .collect();
+            let forwarder = Forwarder::new(
+                default_pool,
+                rules,
+                Duration::from_millis(config.upstream_timeout_ms),
+                config.upstream_retries,
+                config.udp_payload_size,
+                Arc::clone(&metrics),
+            )
+            .expect("Failed to set up forwarder");
+            Some(forwarder)
+        }
+    };
+    let blocklist = match config.blocklist.is_empty() {
+        true => None,
+        false => {
+            let read_domain_lists = |file_paths: &Vec<String>| {
+                let mut domains = DomainSet::new();
+                for file_path in file_paths {
+                    domains.extend(
+                        blocklist_file::read_domain_list(file_path)
+                            .expect("Failed to load domain list"),
+                    );
+                }
+                domains
+            };
+            let blocked = read_domain_lists(&config.blocklist);
+            let allowed = read_domain_lists(&config.allowlist);
+            eprintln!(
+                "Loaded {} blocked and {} allowed domains",
+                blocked.len(),
+                allowed.len()
+            );
+            Some(Blocklist::new(blocked, allowed, config.block_response))
+        }
+    };
+    let mut hosts = Hosts::new();
+    for hosts_path in config.hosts.iter() {
+        hosts.extend(hosts_file::read_hosts_file(hosts_path).expect("Failed to load hosts file"));
+    }
+    let query_log = match config.query_log.is_some() || config.dnstap.is_some() {
+        true => Some(
+            QueryLog::new(config.query_log.as_deref(), config.dnstap.as_deref())
+                .expect("Failed to open query log"),
+        ),
+        false => None,
+    };
+    let cache = Mutex::new(Cache::new(config.cache_size));
+    let rate_limiter = match config.rate_limit_responses == 0
+        && config.rate_limit_nxdomains.unwrap_or(0) == 0
+        && config.rate_limit_errors.unwrap_or(0) == 0
+    {
+        true => None,
+        false => Some(RateLimiter::new(RateLimitConfig {
+            responses_per_second: config.rate_limit_responses,
+            nxdomains_per_second: config.rate_limit_nxdomains,
+            errors_per_second: config.rate_limit_errors,
+            window: config.rate_limit_window,
+            slip: config.rate_limit_slip,
+            ipv4_prefix_length: config.rate_limit_ipv4_prefix,
+            ipv6_prefix_length: config.rate_limit_ipv6_prefix,
+        })),
+    };
+    let validator = match config.trust_anchor.is_empty() {
+        true => None,
+        false => {
+            let mut trust_anchors = vec![];
+            for trust_anchor_path in config.trust_anchor.iter() {
+                trust_anchors.extend(
+                    zone_file::read_trust_anchors(trust_anchor_path)
+                        .expect("Failed to load trust anchors"),
+                );
+            }
+            eprintln!("Loaded {} trust anchors", trust_anchors.len());
+            Some(Validator::new(trust_anchors))
+        }
+    };
+    let secondaries = config
+        .secondary_zones
+        .iter()
+        .map(|(origin, primaries)| {
+            Secondary::new(
+                origin.clone(),
+                primaries
+                    .iter()
+                    .map(|primary| resolve_upstream_address(primary))
+                    .collect(),
+                Instant::now(),
+            )
+        })
+        .collect();
+    let transfer_acl = Acl::new(config.allow_transfer.clone());
+    let tsig_keys = config.tsig_key.clone();
+    let server = Arc::new(Server {
+        config,
+        authority: RwLock::new(authority),
+        secondaries,
+        journals,
+        transfer_acl,
+        tsig_keys,
+        recursive_resolver,
+        forwarder,
+        cache,
+        blocklist,
+        hosts,
+        query_log,
+        metrics,
+        rate_limiter,
+        validator,
+    });
+    {
+        let server = Arc::clone(&server);
+        thread::spawn(move || serve_tcp(server, tcp_listener));
+    }
+    if !server.secondaries.is_empty() {
+        let server = Arc::clone(&server);
+        thread::spawn(move || maintain_secondaries(server));
+    }
+    if let Some(metrics_listen) = server.config.metrics_listen.as_ref() {
+        let metrics_listener =
+            TcpListener::bind(metrics_listen).expect("Failed to bind metrics address");
+        let metrics = Arc::clone(&server.metrics);
+        thread::spawn(move || metrics_exporter::serve_metrics(metrics_listener, metrics));
+    }
+    serve_udp(server, udp_socket);
+}
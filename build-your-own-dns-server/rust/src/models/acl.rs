@@ -0,0 +1,78 @@
+use std::net::IpAddr;
+use std::str::FromStr;
+
+// A network in CIDR notation, a bare address standing for itself alone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Network {
+    address: IpAddr,
+    prefix_length: u8,
+}
+
+impl Network {
+    pub fn contains(&self, address: IpAddr) -> bool {
+        // Clients on an IPv6 socket show up as IPv4-mapped addresses.
+        match (self.address, address.to_canonical()) {
+            (IpAddr::V4(network), IpAddr::V4(address)) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - self.prefix_length as u32)
+                    .unwrap_or(0);
+                u32::from(network) & mask == u32::from(address) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(address)) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - self.prefix_length as u32)
+                    .unwrap_or(0);
+                u128::from(network) & mask == u128::from(address) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for Network {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Network, String> {
+        let (address, prefix_length) = match text.split_once('/') {
+            Some((address, prefix_length)) => (address, Some(prefix_length)),
+            None => (text, None),
+        };
+        let address = IpAddr::from_str(address)
+            .map_err(|_| format!("Invalid address '{}'", address))?
+            .to_canonical();
+        let max_prefix_length = match address {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        };
+        let prefix_length = match prefix_length {
+            Some(prefix_length) => prefix_length
+                .parse::<u8>()
+                .ok()
+                .filter(|prefix_length| *prefix_length <= max_prefix_length)
+                .ok_or_else(|| format!("Invalid prefix length in '{}'", text))?,
+            None => max_prefix_length,
+        };
+        Ok(Network {
+            address,
+            prefix_length,
+        })
+    }
+}
+
+// The clients allowed to do something, nobody if the list is empty.
+#[derive(Debug, Clone, Default)]
+pub struct Acl {
+    networks: Vec<Network>,
+}
+
+impl Acl {
+    pub fn new(networks: Vec<Network>) -> Acl {
+        Acl { networks }
+    }
+
+    pub fn allows(&self, address: IpAddr) -> bool {
+        self.networks
+            .iter()
+            .any(|network| network.contains(address))
+    }
+}
@@ -0,0 +1,90 @@
+use crate::models::Label;
+use std::collections::HashSet;
+use std::str::FromStr;
+
+// Domains matched by suffix. A plain entry such as "ads.example" covers the
+// name itself and every name below it, a wildcard entry such as
+// "*.ads.example" only the names below it.
+#[derive(Debug, Default)]
+pub struct DomainSet {
+    names: HashSet<String>,
+    wildcards: HashSet<String>,
+}
+
+impl DomainSet {
+    pub fn new() -> DomainSet {
+        DomainSet::default()
+    }
+
+    pub fn insert(&mut self, entry: &str) {
+        let entry = entry.trim_end_matches('.').to_ascii_lowercase();
+        match entry.strip_prefix("*.") {
+            Some(parent) => self.wildcards.insert(parent.to_string()),
+            None => self.names.insert(entry),
+        };
+    }
+
+    pub fn extend(&mut self, other: DomainSet) {
+        self.names.extend(other.names);
+        self.wildcards.extend(other.wildcards);
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len() + self.wildcards.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn contains(&self, labels: &[Label]) -> bool {
+        (0..labels.len()).any(|start| {
+            let suffix = Label::to_key(&labels[start..]);
+            self.names.contains(&suffix) || (start > 0 && self.wildcards.contains(&suffix))
+        })
+    }
+}
+
+// What a blocked name is answered with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockResponse {
+    NxDomain,
+    // 0.0.0.0 for A and :: for AAAA questions, no records for other types.
+    Null,
+    Refused,
+}
+
+impl FromStr for BlockResponse {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<BlockResponse, String> {
+        match name.to_ascii_lowercase().as_str() {
+            "nxdomain" => Ok(BlockResponse::NxDomain),
+            "null" | "0.0.0.0" => Ok(BlockResponse::Null),
+            "refused" => Ok(BlockResponse::Refused),
+            _ => Err(format!("Unknown block response '{}'", name)),
+        }
+    }
+}
+
+// Names we refuse to resolve, with an allowlist that takes precedence.
+#[derive(Debug)]
+pub struct Blocklist {
+    blocked: DomainSet,
+    allowed: DomainSet,
+    pub response: BlockResponse,
+}
+
+impl Blocklist {
+    pub fn new(blocked: DomainSet, allowed: DomainSet, response: BlockResponse) -> Blocklist {
+        Blocklist {
+            blocked,
+            allowed,
+            response,
+        }
+    }
+
+    pub fn is_blocked(&self, labels: &[Label]) -> bool {
+        self.blocked.contains(labels) && !self.allowed.contains(labels)
+    }
+}
@@ -0,0 +1,235 @@
+use crate::models::{Class, DnsAnswer, DnsQuestion, Label, RData, RecordType};
+use std::collections::{BTreeMap, HashMap};
+use std::time::Instant;
+
+// How many CNAMEs a cached answer may go through before we give up on it.
+const MAX_CNAME_CHAIN: usize = 8;
+
+type CacheKey = (String, RecordType, Class);
+
+#[derive(Debug)]
+struct CacheEntry {
+    // All records of the set share the lowest TTL among them.
+    // Source: https://www.rfc-editor.org/rfc/rfc2181#section-5.2
+    records: Vec<DnsAnswer>,
+    // The RRSIGs covering the set, kept and expired along with it.
+    signatures: Vec<DnsAnswer>,
+    time_to_live: u32,
+    inserted_at: Instant,
+    last_used: u64,
+}
+
+// Whole RRsets from upstream replies, keyed by owner name, type and class.
+// TTLs count down from the moment a set is stored, and once full the least
+// recently used set makes room for a new one.
+#[derive(Debug)]
+pub struct Cache {
+    capacity: usize,
+    entries: HashMap<CacheKey, CacheEntry>,
+    // Entries by the tick they were last used at, oldest first.
+    recency: BTreeMap<u64, CacheKey>,
+    tick: u64,
+}
+
+impl Cache {
+    pub fn new(capacity: usize) -> Cache {
+        Cache {
+            capacity,
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            tick: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // Stores the records as RRsets, replacing whatever was cached for them.
+    // RRSIGs go with the set they cover, signatures without one are dropped.
+    pub fn insert(&mut self, records: &[DnsAnswer], now: Instant) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut rrsets: HashMap<CacheKey, (Vec<DnsAnswer>, Vec<DnsAnswer>)> = HashMap::new();
+        for record in records {
+            match record.rdata {
+                RData::RRSIG { type_covered, .. } => rrsets
+                    .entry(Cache::key(&record.name, type_covered, record.class))
+                    .or_default()
+                    .1
+                    .push(record.clone()),
+                _ => rrsets
+                    .entry(Cache::key(&record.name, record.record_type, record.class))
+                    .or_default()
+                    .0
+                    .push(record.clone()),
+            }
+        }
+        for (key, (records, signatures)) in rrsets {
+            let time_to_live = records
+                .iter()
+                .chain(signatures.iter())
+                .map(|record| record.time_to_live)
+                .min()
+                .unwrap_or(0);
+            if records.is_empty() || time_to_live == 0 {
+                continue;
+            }
+            self.remove(&key);
+            while self.entries.len() >= self.capacity {
+                match self.recency.pop_first() {
+                    Some((_, oldest)) => self.entries.remove(&oldest),
+                    None => break,
+                };
+            }
+            let last_used = self.next_tick();
+            self.recency.insert(last_used, key.clone());
+            self.entries.insert(
+                key,
+                CacheEntry {
+                    records,
+                    signatures,
+                    time_to_live,
+                    inserted_at: now,
+                    last_used,
+                },
+            );
+        }
+    }
+
+    // The cached RRset followed by its signatures, with TTLs lowered by the
+    // time it has spent here, or None if there is none or it has expired.
+    pub fn get(
+        &mut self,
+        name: &[Label],
+        record_type: RecordType,
+        class: Class,
+        now: Instant,
+    ) -> Option<Vec<DnsAnswer>> {
+        let key = Cache::key(name, record_type, class);
+        let entry = self.entries.get(&key)?;
+        let elapsed = now.saturating_duration_since(entry.inserted_at).as_secs();
+        if elapsed >= entry.time_to_live as u64 {
+            self.remove(&key);
+            return None;
+        }
+        let time_to_live = entry.time_to_live - elapsed as u32;
+        let last_used = entry.last_used;
+        let records = entry
+            .records
+            .iter()
+            .chain(entry.signatures.iter())
+            .map(|record| {
+                let mut record = record.clone();
+                record.time_to_live = time_to_live;
+                record
+            })
+            .collect();
+        let tick = self.next_tick();
+        self.recency.remove(&last_used);
+        self.recency.insert(tick, key.clone());
+        if let Some(entry) = self.entries.get_mut(&key) {
+            entry.last_used = tick;
+        }
+        Some(records)
+    }
+
+    // Answers a question from the cache alone, following cached CNAMEs. Only
+    // complete answers are returned, a chain that ends in a name we have
+    // nothing for has to go upstream.
+    pub fn lookup(&mut self, dns_question: &DnsQuestion, now: Instant) -> Option<Vec<DnsAnswer>> {
+        let mut answers: Vec<DnsAnswer> = vec![];
+        let mut name = dns_question.labels.clone();
+        for _ in 0..=MAX_CNAME_CHAIN {
+            if let Some(records) =
+                self.get(&name, dns_question.record_type, dns_question.class, now)
+            {
+                answers.extend(records);
+                return Some(answers);
+            }
+            let cname = self.get(&name, RecordType::CNAME, dns_question.class, now)?;
+            name = match &cname.first()?.rdata {
+                RData::CNAME(target) => target.clone(),
+                _ => return None,
+            };
+            answers.extend(cname);
+        }
+        None
+    }
+
+    // The records of an upstream answer that are worth caching: those on the
+    // way from the question name to the RRset asked for, each a CNAME or the
+    // RRset itself, along with their signatures. Anything else in the answer
+    // section has nothing to do with the question and is not trusted, nor is
+    // any part of the chain from a name `may_answer` says the upstream has no
+    // say over.
+    pub fn answer_chain(
+        dns_question: &DnsQuestion,
+        records: &[DnsAnswer],
+        may_answer: impl Fn(&[Label]) -> bool,
+    ) -> Vec<DnsAnswer> {
+        let mut chain: Vec<DnsAnswer> = vec![];
+        let mut name = dns_question.labels.clone();
+        for _ in 0..=MAX_CNAME_CHAIN {
+            if !may_answer(&name) {
+                break;
+            }
+            let rrset = |record_type: RecordType| -> Vec<DnsAnswer> {
+                records
+                    .iter()
+                    .filter(|record| {
+                        let covered = match record.rdata {
+                            RData::RRSIG { type_covered, .. } => type_covered,
+                            _ => record.record_type,
+                        };
+                        covered == record_type
+                            && record.class == dns_question.class
+                            && Label::to_key(&record.name) == Label::to_key(&name)
+                    })
+                    .cloned()
+                    .collect()
+            };
+            let answer = rrset(dns_question.record_type);
+            if answer
+                .iter()
+                .any(|record| record.record_type == dns_question.record_type)
+            {
+                chain.extend(answer);
+                break;
+            }
+            let cname = rrset(RecordType::CNAME);
+            let target = cname.iter().find_map(|record| match &record.rdata {
+                RData::CNAME(target) => Some(target.clone()),
+                _ => None,
+            });
+            match target {
+                Some(target) => {
+                    chain.extend(cname);
+                    name = target;
+                }
+                None => break,
+            }
+        }
+        chain
+    }
+
+    fn key(name: &[Label], record_type: RecordType, class: Class) -> CacheKey {
+        (Label::to_key(name), record_type, class)
+    }
+
+    fn remove(&mut self, key: &CacheKey) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.recency.remove(&entry.last_used);
+        }
+    }
+
+    fn next_tick(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick
+    }
+}
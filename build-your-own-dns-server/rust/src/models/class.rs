@@ -1,26 +1,90 @@
+use crate::models::DnsError;
 use crate::traits::Decodable;
+use nom::number::complete::be_u16;
+use nom::IResult;
+use std::fmt;
+use std::str::FromStr;
 
 // specification: https://www.rfc-editor.org/rfc/rfc1035#section-3.2.4
-#[derive(Debug, Clone)]
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Class {
-    IN = 1, // the Internet
-    CS = 2, // the CSNET class (Obsolete - used only for examples in some obsolete RFCs)
-    CH = 3, // the CHAOS class
-    HS = 4, // Hesiod [Dyer 87]
+    IN, // the Internet
+    CS, // the CSNET class (Obsolete - used only for examples in some obsolete RFCs)
+    CH, // the CHAOS class
+    HS, // Hesiod [Dyer 87]
+    // Classes only found in questions and dynamic updates.
+    NONE, // no class, for records that must not exist (RFC 2136)
+    ANY,  // any class (RFC 1035 calls it *)
+    // Any other class, carried along like a type we do not know.
+    // Source: https://www.rfc-editor.org/rfc/rfc3597#section-2
+    Unknown(u16),
+}
+
+impl Class {
+    pub fn from_u16(value: u16) -> Class {
+        match value {
+            1 => Class::IN,
+            2 => Class::CS,
+            3 => Class::CH,
+            4 => Class::HS,
+            254 => Class::NONE,
+            255 => Class::ANY,
+            _ => Class::Unknown(value),
+        }
+    }
+
+    pub fn to_u16(&self) -> u16 {
+        match self {
+            Class::IN => 1,
+            Class::CS => 2,
+            Class::CH => 3,
+            Class::HS => 4,
+            Class::NONE => 254,
+            Class::ANY => 255,
+            Class::Unknown(value) => *value,
+        }
+    }
 }
 
 impl Decodable for Class {
-    fn decode(buffer: Vec<u8>) -> Result<Class, String> {
-        let u16_value = ((buffer[0] as u16) << 8) | (buffer[1] as u16);
-        match u16_value {
-            1 => Ok(Class::IN),
-            2 => Ok(Class::CS),
-            3 => Ok(Class::CH),
-            4 => Ok(Class::HS),
-            _ => Err("Could not decode RecordType from invalid value {:u16_value}".to_string()),
+    fn parse<'a>(_message: &'a [u8], input: &'a [u8]) -> IResult<&'a [u8], Class, DnsError> {
+        let (input, value) = be_u16(input)?;
+        Ok((input, Class::from_u16(value)))
+    }
+}
+
+// Class mnemonics as they appear in master files, and "CLASS32" for any
+// class by its number.
+// Source: https://www.rfc-editor.org/rfc/rfc3597#section-5
+impl FromStr for Class {
+    type Err = String;
+
+    fn from_str(mnemonic: &str) -> Result<Class, String> {
+        let uppercase = mnemonic.to_ascii_uppercase();
+        if let Some(value) = uppercase.strip_prefix("CLASS") {
+            return value
+                .parse::<u16>()
+                .map(Class::from_u16)
+                .map_err(|_| format!("Unknown class '{}'", mnemonic));
+        }
+        match uppercase.as_str() {
+            "IN" => Ok(Class::IN),
+            "CS" => Ok(Class::CS),
+            "CH" => Ok(Class::CH),
+            "HS" => Ok(Class::HS),
+            "NONE" => Ok(Class::NONE),
+            "ANY" => Ok(Class::ANY),
+            _ => Err(format!("Unknown class '{}'", mnemonic)),
         }
     }
-    fn decode_with_cursor(buffer: Vec<u8>, cursor: &mut usize) -> Result<Self, String> {
-        Self::decode(buffer[*cursor..].to_vec())
+}
+
+impl fmt::Display for Class {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Class::Unknown(value) => write!(f, "CLASS{}", value),
+            _ => write!(f, "{:?}", self),
+        }
     }
 }
@@ -0,0 +1,44 @@
+use crate::models::Label;
+use crate::traits::Encodable;
+use std::collections::HashMap;
+
+// Pointers are 14 bits wide, so names written past this offset can not be
+// pointed at.
+const MAX_POINTER_OFFSET: usize = 0b0011_1111_1111_1111;
+
+// Remembers where each name suffix was first written in a message so later
+// occurrences can be replaced by a two byte pointer.
+// Source: https://www.rfc-editor.org/rfc/rfc1035#section-4.1.4
+#[derive(Debug, Default)]
+pub struct Compression {
+    enabled: bool,
+    offsets: HashMap<Vec<Label>, u16>,
+}
+
+impl Compression {
+    pub fn new(enabled: bool) -> Compression {
+        Compression {
+            enabled,
+            offsets: HashMap::new(),
+        }
+    }
+
+    // Writes the name at the end of the buffer, which must hold the message
+    // from its first byte so recorded offsets are valid pointer targets.
+    pub fn encode_name(&mut self, labels: &[Label], buffer: &mut Vec<u8>) {
+        for index in 0..labels.len() {
+            let suffix = &labels[index..];
+            if self.enabled {
+                if let Some(offset) = self.offsets.get(suffix) {
+                    buffer.extend_from_slice(&(0b1100_0000_0000_0000 | offset).to_be_bytes());
+                    return;
+                }
+                if buffer.len() <= MAX_POINTER_OFFSET {
+                    self.offsets.insert(suffix.to_vec(), buffer.len() as u16);
+                }
+            }
+            buffer.extend(labels[index].encode());
+        }
+        buffer.push(0x00);
+    }
+}
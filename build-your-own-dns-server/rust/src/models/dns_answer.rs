@@ -1,94 +1,133 @@
-use crate::models::{Class, DnsQuestion, Label, RecordType};
+use crate::models::presentation::{self, format_name};
+use crate::models::{Class, Compression, DnsError, Label, RData, RecordType};
 use crate::traits::{Decodable, Encodable};
+use nom::multi::length_data;
+use nom::number::complete::{be_u16, be_u32};
+use nom::IResult;
+use std::fmt;
+use std::str::FromStr;
 
-#[allow(dead_code)]
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct DnsAnswer {
-    name: Vec<Label>,
-    record_type: RecordType,
-    class: Class,
-    time_to_live: u32,
-    length: u16,
-    data: Vec<u8>,
+    pub name: Vec<Label>,
+    pub record_type: RecordType,
+    pub class: Class,
+    pub time_to_live: u32,
+    pub rdata: RData,
 }
 
 impl DnsAnswer {
-    pub fn from_request_question(request_question: &DnsQuestion) -> DnsAnswer {
+    pub fn new(
+        name: Vec<Label>,
+        record_type: RecordType,
+        class: Class,
+        time_to_live: u32,
+        rdata: RData,
+    ) -> DnsAnswer {
         DnsAnswer {
-            name: request_question.labels.clone(),
-            record_type: RecordType::A,
-            class: Class::IN,
-            time_to_live: 60,
-            length: 4,
-            data: vec![8, 8, 8, 8],
+            name,
+            record_type,
+            class,
+            time_to_live,
+            rdata,
         }
     }
+
+    pub fn encode_into(&self, buffer: &mut Vec<u8>, compression: &mut Compression) {
+        compression.encode_name(&self.name, buffer);
+        buffer.extend_from_slice(&self.record_type.to_u16().to_be_bytes());
+        buffer.extend_from_slice(&self.class.to_u16().to_be_bytes());
+        buffer.extend_from_slice(&self.time_to_live.to_be_bytes());
+        // RDLENGTH is only known once the RDATA is written in place, as names
+        // inside it may be compressed against the rest of the message.
+        let length_offset = buffer.len();
+        buffer.extend_from_slice(&[0, 0]);
+        self.rdata.encode_into(buffer, compression);
+        let length = (buffer.len() - length_offset - 2) as u16;
+        buffer[length_offset..length_offset + 2].copy_from_slice(&length.to_be_bytes());
+    }
 }
 
 impl Decodable for DnsAnswer {
-    fn decode(buffer: Vec<u8>) -> Result<DnsAnswer, String> {
-        Self::decode_with_cursor(buffer, &mut 0)
+    fn parse<'a>(message: &'a [u8], input: &'a [u8]) -> IResult<&'a [u8], DnsAnswer, DnsError> {
+        let (input, labels) = Label::parse_name(message, input)?;
+        let (input, record_type) = RecordType::parse(message, input)?;
+        let (input, class) = Class::parse(message, input)?;
+        let (input, time_to_live) = be_u32(input)?;
+        let (input, data) = length_data(be_u16)(input)?;
+        // Updates use records of class ANY or NONE without RDATA to stand
+        // for a whole RRset or name.
+        // Source: https://www.rfc-editor.org/rfc/rfc2136#section-2.4
+        let rdata = match class {
+            Class::ANY | Class::NONE if data.is_empty() => RData::Unknown(vec![]),
+            _ => RData::parse(record_type, message, data).map_err(nom::Err::Failure)?,
+        };
+        return Ok((
+            input,
+            DnsAnswer {
+                name: labels,
+                record_type,
+                class,
+                time_to_live,
+                rdata,
+            },
+        ));
+    }
+}
+
+impl Encodable for DnsAnswer {
+    fn encode(&self) -> Vec<u8> {
+        let mut encoded_dns_answer: Vec<u8> = vec![];
+        self.encode_into(&mut encoded_dns_answer, &mut Compression::new(false));
+        encoded_dns_answer
     }
+}
+
+// A record in master file form, "example.com. 60 IN A 192.0.2.1".
+impl fmt::Display for DnsAnswer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} {} {}",
+            format_name(&self.name),
+            self.time_to_live,
+            self.class,
+            self.record_type,
+            self.rdata
+        )
+    }
+}
 
-    fn decode_with_cursor(buffer: Vec<u8>, cursor: &mut usize) -> Result<DnsAnswer, String> {
-        let mut labels: Vec<Label> = vec![];
-        let mut pointer_cursor: usize = *cursor;
-        let mut is_pointer: bool = false;
+// Parses a single record in master file form. Without an $ORIGIN every name
+// has to be absolute, and the TTL has to be given as there is no $TTL.
+impl FromStr for DnsAnswer {
+    type Err = String;
 
-        while buffer[pointer_cursor] != 0x00 {
-            is_pointer = (buffer[pointer_cursor] & 0b11000000) == 0b11000000;
-            if is_pointer {
-                pointer_cursor = ((((buffer[pointer_cursor] & 0b00111111) as u16) << 8)
-                    | (buffer[pointer_cursor + 1] as u16))
-                    as usize;
-                *cursor += 3;
-            }
-            let length = buffer[pointer_cursor] as usize;
-            pointer_cursor += 1;
-            let label_bytes = &buffer[pointer_cursor..pointer_cursor + length];
-            pointer_cursor += length;
-            let label_string = std::str::from_utf8(label_bytes).unwrap();
-            labels.push(Label::from_string(label_string.to_string()))
+    fn from_str(text: &str) -> Result<DnsAnswer, String> {
+        let mut entries = presentation::tokenize(text)?;
+        if entries.len() != 1 {
+            return Err(format!("expected one record, found {}", entries.len()));
         }
-        if !is_pointer {
-            *cursor = pointer_cursor + 1;
+        let entry = entries.remove(0);
+        let tokens = entry.tokens;
+        if entry.owner_omitted || tokens[0].quoted {
+            return Err("record without an owner name".to_string());
         }
-        *cursor += 4;
-        let record_type = RecordType::decode(buffer[*cursor - 4..*cursor - 2].to_vec()).unwrap();
-        let class = Class::decode(buffer[*cursor - 2..*cursor].to_vec()).unwrap();
-        let time_to_live = (buffer[*cursor] as u32) << 24
-            | (buffer[*cursor + 1] as u32) << 16
-            | (buffer[*cursor + 2] as u32) << 8
-            | (buffer[*cursor + 3] as u32);
-        *cursor += 4;
-        let length = (buffer[*cursor] as u16) << 8 | buffer[*cursor + 1] as u16;
-        let data = vec![
-            buffer[*cursor + 2],
-            buffer[*cursor + 3],
-            buffer[*cursor + 4],
-            buffer[*cursor + 5],
-        ];
-        *cursor += 2 + length as usize;
-        return Ok(DnsAnswer {
-            name: labels,
+        let name = presentation::parse_name(&tokens[0].text, None)?;
+        let (ttl, class, index) = presentation::parse_ttl_and_class(&tokens, 1);
+        let time_to_live = ttl.ok_or_else(|| "record without a TTL".to_string())?;
+        let record_type = tokens
+            .get(index)
+            .ok_or_else(|| "record without a type".to_string())
+            .and_then(|token| RecordType::from_str(&token.text))?;
+        let rdata = presentation::parse_rdata(record_type, &tokens[index + 1..], None)
+            .map_err(|e| format!("invalid {} data: {}", record_type, e))?;
+        return Ok(DnsAnswer::new(
+            name,
             record_type,
-            class,
+            class.unwrap_or(Class::IN),
             time_to_live,
-            length,
-            data,
-        });
-    }
-}
-
-impl Encodable for DnsAnswer {
-    fn encode(&self) -> Vec<u8> {
-        let mut encoded_dns_answer: Vec<u8> =
-            self.name.iter().flat_map(|label| label.encode()).collect();
-        encoded_dns_answer.extend(vec![0x00]);
-        encoded_dns_answer.extend(vec![0, 1, 0, 1]);
-        encoded_dns_answer.extend(Vec::from(self.time_to_live.to_be_bytes()));
-        encoded_dns_answer.extend(Vec::from(self.length.to_be_bytes()));
-        encoded_dns_answer.extend(self.data.clone());
-        encoded_dns_answer
+            rdata,
+        ));
     }
 }
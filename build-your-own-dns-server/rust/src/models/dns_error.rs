@@ -0,0 +1,33 @@
+use nom::error::{ErrorKind, ParseError};
+use thiserror::Error;
+
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
+pub enum DnsError {
+    #[error("packet truncated")]
+    Truncated,
+    // Pointers may only refer to a prior occurrence of a name, anything else
+    // could send the decoder around in circles.
+    // Source: https://www.rfc-editor.org/rfc/rfc1035#section-4.1.4
+    #[error("compression pointer at offset {offset} does not point to an earlier name")]
+    PointerLoop { offset: usize },
+    #[error("label length {0} is above 63 or uses a reserved label type")]
+    LabelTooLong(u8),
+    #[error("name is longer than 255 bytes")]
+    NameTooLong,
+    #[error("more than one OPT record")]
+    DuplicateOpt,
+    #[error("invalid RDATA: {0}")]
+    InvalidRData(String),
+}
+
+// The nom combinators only fail when they run out of input, as every packet
+// is parsed complete that always means it was truncated.
+impl<'a> ParseError<&'a [u8]> for DnsError {
+    fn from_error_kind(_input: &'a [u8], _kind: ErrorKind) -> DnsError {
+        DnsError::Truncated
+    }
+
+    fn append(_input: &'a [u8], _kind: ErrorKind, other: DnsError) -> DnsError {
+        other
+    }
+}
@@ -1,29 +1,78 @@
+use crate::models::{DnsError, OperationCode, ResponseCode};
 use crate::traits::{Decodable, Encodable};
+use nom::bytes::complete::take;
+use nom::multi::count;
+use nom::number::complete::be_u16;
+use nom::sequence::tuple;
+use nom::IResult;
+use std::fmt;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 enum QueryResponse {
     ReplyPacket = 1,
     QuestionPacket = 2,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct DnsHeader {
-    packet_identifier: u16,
+    pub packet_identifier: u16,
     query_response_indicator: QueryResponse,
     operation_code: u8,
-    authoritative_answer: bool,
-    truncation: bool,
-    recursion_desired: bool,
-    recursion_available: bool,
+    pub authoritative_answer: bool,
+    pub truncation: bool,
+    pub recursion_desired: bool,
+    pub recursion_available: bool,
     reserved: u8,
-    response_code: u8,
+    pub response_code: u8,
     pub question_count: u16,
     pub answer_record_count: u16,
-    authority_record_count: u16,
-    additional_record_count: u16,
+    pub authority_record_count: u16,
+    pub additional_record_count: u16,
 }
 
 impl DnsHeader {
+    // The header of a standard query we send ourselves, with the counts left
+    // for the packet encoding to fill in.
+    pub fn new_query(packet_identifier: u16, recursion_desired: bool) -> DnsHeader {
+        return DnsHeader {
+            packet_identifier,
+            query_response_indicator: QueryResponse::QuestionPacket,
+            operation_code: 0,
+            authoritative_answer: false,
+            truncation: false,
+            recursion_desired,
+            recursion_available: false,
+            reserved: 0,
+            response_code: ResponseCode::NoError as u8,
+            question_count: 0,
+            answer_record_count: 0,
+            authority_record_count: 0,
+            additional_record_count: 0,
+        };
+    }
+
+    pub fn is_response(&self) -> bool {
+        return matches!(self.query_response_indicator, QueryResponse::ReplyPacket);
+    }
+
+    pub fn operation_code(&self) -> u8 {
+        return self.operation_code;
+    }
+
+    // The AD and CD bits live in what RFC 1035 left as the Z field.
+    // specification: https://www.rfc-editor.org/rfc/rfc4035#section-3.2
+    pub fn authentic_data(&self) -> bool {
+        return self.reserved & 0b010 != 0;
+    }
+
+    pub fn set_authentic_data(&mut self, authentic_data: bool) {
+        self.reserved = (self.reserved & !0b010) | ((authentic_data as u8) << 1);
+    }
+
+    pub fn checking_disabled(&self) -> bool {
+        return self.reserved & 0b001 != 0;
+    }
+
     pub fn from_request_header(request_header: DnsHeader) -> DnsHeader {
         return DnsHeader {
             packet_identifier: request_header.packet_identifier,
@@ -33,10 +82,14 @@ impl DnsHeader {
             truncation: false,
             recursion_desired: request_header.recursion_desired,
             recursion_available: false,
-            reserved: 0,
+            // CD is copied into the response, AD is only set once validated.
+            // Source: https://www.rfc-editor.org/rfc/rfc4035#section-3.1.6
+            reserved: request_header.reserved & 0b001,
             response_code: match request_header.operation_code {
-                0 => 0,
-                _ => 4,
+                code if code == OperationCode::Query as u8 => ResponseCode::NoError as u8,
+                code if code == OperationCode::Notify as u8 => ResponseCode::NoError as u8,
+                code if code == OperationCode::Update as u8 => ResponseCode::NoError as u8,
+                _ => ResponseCode::NotImplemented as u8,
             },
             question_count: request_header.question_count,
             answer_record_count: request_header.question_count,
@@ -47,49 +100,30 @@ impl DnsHeader {
 }
 
 impl Decodable for DnsHeader {
-    fn decode_with_cursor(buffer: Vec<u8>, _cursor: &mut usize) -> Result<Self, String> {
-        Self::decode(buffer)
-    }
-    fn decode(buffer: Vec<u8>) -> Result<DnsHeader, String> {
-        return Ok(DnsHeader {
-            packet_identifier: (buffer[0] as u16) << 8 | buffer[1] as u16,
-            query_response_indicator: match buffer[2] >> 7 {
-                1 => QueryResponse::ReplyPacket,
-                0 => QueryResponse::QuestionPacket,
-                _ => {
-                    return Err(
-                        "Error when converting buffer to query response indicator".to_string()
-                    )
-                }
-            },
-            operation_code: buffer[2] >> 3 & 0b1111,
-            authoritative_answer: match buffer[2] >> 2 & 0b1 {
-                1 => true,
-                0 => false,
-                _ => return Err("Error when converting buffer to operation code".to_string()),
+    fn parse<'a>(_message: &'a [u8], input: &'a [u8]) -> IResult<&'a [u8], DnsHeader, DnsError> {
+        let (input, (packet_identifier, flags, counts)) =
+            tuple((be_u16, take(2usize), count(be_u16, 4)))(input)?;
+        return Ok((
+            input,
+            DnsHeader {
+                packet_identifier,
+                query_response_indicator: match flags[0] >> 7 {
+                    1 => QueryResponse::ReplyPacket,
+                    _ => QueryResponse::QuestionPacket,
+                },
+                operation_code: flags[0] >> 3 & 0b1111,
+                authoritative_answer: flags[0] >> 2 & 0b1 == 1,
+                truncation: flags[0] >> 1 & 0b1 == 1,
+                recursion_desired: flags[0] & 0b1 == 1,
+                recursion_available: flags[1] >> 7 & 0b1 == 1,
+                reserved: flags[1] >> 4 & 0b111,
+                response_code: flags[1] & 0b1111,
+                question_count: counts[0],
+                answer_record_count: counts[1],
+                authority_record_count: counts[2],
+                additional_record_count: counts[3],
             },
-            truncation: match buffer[2] >> 1 & 0b1 {
-                1 => true,
-                0 => false,
-                _ => return Err("Error when converting buffer to truncation".to_string()),
-            },
-            recursion_desired: match buffer[2] & 0b1 {
-                1 => true,
-                0 => false,
-                _ => return Err("Error when converting buffer to recursion desired".to_string()),
-            },
-            recursion_available: match buffer[3] >> 7 & 0b1 {
-                1 => true,
-                0 => false,
-                _ => return Err("Error when converting buffer to recursion available".to_string()),
-            },
-            reserved: buffer[3] >> 4 & 0b111,
-            response_code: buffer[3] & 0b1111,
-            question_count: (buffer[4] as u16) << 8 | buffer[5] as u16,
-            answer_record_count: (buffer[6] as u16) << 8 | buffer[7] as u16,
-            authority_record_count: (buffer[8] as u16) << 8 | buffer[9] as u16,
-            additional_record_count: (buffer[10] as u16) << 8 | buffer[11] as u16,
-        });
+        ));
     }
 }
 
@@ -99,8 +133,8 @@ impl Encodable for DnsHeader {
 
         buffer.extend_from_slice(&self.packet_identifier.to_be_bytes());
         let qr = match self.query_response_indicator {
-            QueryResponse::ReplyPacket => 1 as u8,
-            QueryResponse::QuestionPacket => 0 as u8,
+            QueryResponse::ReplyPacket => 1_u8,
+            QueryResponse::QuestionPacket => 0_u8,
         } << 7;
         let op_code = (self.operation_code & 0b1111) << 3;
         let aa = (self.authoritative_answer as u8) << 2;
@@ -121,3 +155,61 @@ impl Encodable for DnsHeader {
         buffer
     }
 }
+
+impl DnsHeader {
+    // The two header lines dig prints, with the full RCODE passed in as its
+    // upper bits are only known from the OPT record.
+    pub(crate) fn fmt_with_response_code(
+        &self,
+        f: &mut fmt::Formatter,
+        response_code: u16,
+    ) -> fmt::Result {
+        writeln!(
+            f,
+            ";; ->>HEADER<<- opcode: {}, status: {}, id: {}",
+            opcode_mnemonic(self.operation_code),
+            ResponseCode::mnemonic(response_code),
+            self.packet_identifier
+        )?;
+        let flags: Vec<&str> = [
+            (self.is_response(), "qr"),
+            (self.authoritative_answer, "aa"),
+            (self.truncation, "tc"),
+            (self.recursion_desired, "rd"),
+            (self.recursion_available, "ra"),
+            (self.authentic_data(), "ad"),
+            (self.checking_disabled(), "cd"),
+        ]
+        .iter()
+        .filter(|(set, _)| *set)
+        .map(|(_, name)| *name)
+        .collect();
+        write!(
+            f,
+            ";; flags: {}; QUERY: {}, ANSWER: {}, AUTHORITY: {}, ADDITIONAL: {}",
+            flags.join(" "),
+            self.question_count,
+            self.answer_record_count,
+            self.authority_record_count,
+            self.additional_record_count
+        )
+    }
+}
+
+impl fmt::Display for DnsHeader {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_with_response_code(f, self.response_code as u16)
+    }
+}
+
+// Source: https://www.iana.org/assignments/dns-parameters/dns-parameters.xhtml#dns-parameters-5
+fn opcode_mnemonic(operation_code: u8) -> String {
+    match operation_code {
+        0 => "QUERY".to_string(),
+        1 => "IQUERY".to_string(),
+        2 => "STATUS".to_string(),
+        4 => "NOTIFY".to_string(),
+        5 => "UPDATE".to_string(),
+        _ => operation_code.to_string(),
+    }
+}
@@ -1,37 +1,22 @@
-use crate::models::{DnsAnswer, DnsHeader, DnsQuestion};
+use crate::models::{Compression, DnsAnswer, DnsError, DnsHeader, DnsQuestion, Edns, ResponseCode};
 use crate::traits::{Decodable, Encodable};
+use nom::multi::count;
+use nom::IResult;
+use std::fmt;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct DnsPacket {
     pub dns_header: DnsHeader,
     pub dns_questions: Vec<DnsQuestion>,
     pub dns_answers: Vec<DnsAnswer>,
+    pub dns_authorities: Vec<DnsAnswer>,
+    pub dns_additionals: Vec<DnsAnswer>,
+    // The OPT pseudo-record, kept apart from the additional section it is
+    // carried in on the wire.
+    pub edns: Option<Edns>,
 }
 
 impl DnsPacket {
-    pub fn decode(buffer: [u8; 512]) -> DnsPacket {
-        let dns_header =
-            DnsHeader::decode(buffer.to_vec()).expect("Error when building DnsRequest from bytes");
-        let answer_count = dns_header.answer_record_count as usize;
-        let question_count = dns_header.question_count as usize;
-        let mut cursor = 12;
-        return DnsPacket {
-            dns_header,
-            dns_questions: (0..question_count)
-                .map(|_index| {
-                    DnsQuestion::decode_with_cursor(buffer.to_vec(), &mut cursor)
-                        .expect("Error when building DnsQuestion from bytes")
-                })
-                .collect(),
-            dns_answers: (0..answer_count)
-                .map(|_index| {
-                    DnsAnswer::decode_with_cursor(buffer.to_vec(), &mut cursor)
-                        .expect("Error when building DnsAnswer from bytes")
-                })
-                .collect(),
-        };
-    }
-
     pub fn split(&self) -> Vec<DnsPacket> {
         let mut dns_header = self.dns_header.clone();
         dns_header.question_count = 1;
@@ -43,38 +28,225 @@ impl DnsPacket {
                 dns_header: dns_header.clone(),
                 dns_questions: vec![dns_question],
                 dns_answers: vec![],
+                dns_authorities: vec![],
+                dns_additionals: vec![],
+                edns: self.edns.clone(),
             })
             .collect();
     }
 
-    pub fn merge(dns_packets: Vec<DnsPacket>) -> DnsPacket {
-        let mut dns_header = dns_packets[0].dns_header.clone();
-        dns_header.question_count = dns_packets.len() as u16;
-        dns_header.answer_record_count = dns_packets.len() as u16;
+    // The header and OPT record are those of the first packet, so there is
+    // nothing to merge into without one. The RCODE is the first error any of
+    // them has, so that a failed question is not passed off as answered.
+    pub fn merge(dns_packets: Vec<DnsPacket>) -> Option<DnsPacket> {
+        let first = dns_packets.first()?;
+        let mut dns_header = first.dns_header.clone();
+        let edns = first.edns.clone();
+        let response_code = dns_packets
+            .iter()
+            .map(|dns_packet| dns_packet.response_code())
+            .find(|response_code| *response_code != ResponseCode::NoError as u16)
+            .unwrap_or(ResponseCode::NoError as u16);
         let mut dns_questions: Vec<DnsQuestion> = vec![];
         let mut dns_answers: Vec<DnsAnswer> = vec![];
+        let mut dns_authorities: Vec<DnsAnswer> = vec![];
+        let mut dns_additionals: Vec<DnsAnswer> = vec![];
         dns_packets.into_iter().for_each(|dns_packet| {
             dns_questions.extend(dns_packet.dns_questions);
             dns_answers.extend(dns_packet.dns_answers);
+            // Replies to split questions often carry the same NS or glue
+            // records, those only need to be passed on once.
+            for dns_authority in dns_packet.dns_authorities {
+                if !dns_authorities.contains(&dns_authority) {
+                    dns_authorities.push(dns_authority);
+                }
+            }
+            for dns_additional in dns_packet.dns_additionals {
+                if !dns_additionals.contains(&dns_additional) {
+                    dns_additionals.push(dns_additional);
+                }
+            }
         });
+        dns_header.question_count = dns_questions.len() as u16;
+        dns_header.answer_record_count = dns_answers.len() as u16;
+        dns_header.authority_record_count = dns_authorities.len() as u16;
+        dns_header.additional_record_count = dns_additionals.len() as u16;
 
-        return DnsPacket {
+        let mut dns_packet = DnsPacket {
             dns_header,
             dns_questions,
             dns_answers,
+            dns_authorities,
+            dns_additionals,
+            edns,
         };
+        // Extended RCODEs need an OPT record to be told apart from the plain
+        // ones that share their lower four bits.
+        match response_code > 0b1111 && dns_packet.edns.is_none() {
+            true => dns_packet.set_response_code(ResponseCode::ServerFailure as u16),
+            false => dns_packet.set_response_code(response_code),
+        }
+        return Some(dns_packet);
+    }
+
+    // The full twelve bit RCODE, the upper eight bits of which live in OPT.
+    // Source: https://www.rfc-editor.org/rfc/rfc6891#section-6.1.3
+    pub fn response_code(&self) -> u16 {
+        let extended_rcode = self.edns.as_ref().map_or(0, |edns| edns.extended_rcode);
+        (extended_rcode as u16) << 4 | self.dns_header.response_code as u16
+    }
+
+    pub fn set_response_code(&mut self, response_code: u16) {
+        self.dns_header.response_code = (response_code & 0b1111) as u8;
+        if let Some(edns) = self.edns.as_mut() {
+            edns.extended_rcode = (response_code >> 4) as u8;
+        }
+    }
+}
+
+impl DnsPacket {
+    // Compression can be turned off to get the plain, byte-for-byte predictable
+    // encoding of every name.
+    pub fn encode_with_compression(&self, enabled: bool) -> Vec<u8> {
+        let mut compression = Compression::new(enabled);
+        // The counts written always match the sections that follow, whatever
+        // was left in the header by the code that built this packet.
+        let mut dns_header = self.dns_header.clone();
+        dns_header.question_count = self.dns_questions.len() as u16;
+        dns_header.answer_record_count = self.dns_answers.len() as u16;
+        dns_header.authority_record_count = self.dns_authorities.len() as u16;
+        dns_header.additional_record_count =
+            self.dns_additionals.len() as u16 + self.edns.is_some() as u16;
+        let mut encoded_dns_request: Vec<u8> = dns_header.encode();
+        for dns_question in self.dns_questions.iter() {
+            dns_question.encode_into(&mut encoded_dns_request, &mut compression);
+        }
+        for dns_answer in self
+            .dns_answers
+            .iter()
+            .chain(self.dns_authorities.iter())
+            .chain(self.dns_additionals.iter())
+        {
+            dns_answer.encode_into(&mut encoded_dns_request, &mut compression);
+        }
+        if let Some(edns) = self.edns.as_ref() {
+            encoded_dns_request.extend(edns.encode());
+        }
+        encoded_dns_request
+    }
+
+    // Encodes the packet for a UDP reply of at most `max_size` bytes. The
+    // additional section goes first as it is optional, if the answers still
+    // do not fit they are cut and TC is set so the client retries over TCP.
+    // Source: https://www.rfc-editor.org/rfc/rfc2181#section-9
+    pub fn encode_truncated(&self, max_size: usize) -> Vec<u8> {
+        let mut encoded_dns_response = self.encode();
+        if encoded_dns_response.len() <= max_size {
+            return encoded_dns_response;
+        }
+        let mut dns_packet = self.clone();
+        dns_packet.dns_additionals.clear();
+        encoded_dns_response = dns_packet.encode();
+        if encoded_dns_response.len() <= max_size {
+            return encoded_dns_response;
+        }
+        dns_packet.dns_header.truncation = true;
+        dns_packet.dns_authorities.clear();
+        loop {
+            encoded_dns_response = dns_packet.encode();
+            if encoded_dns_response.len() <= max_size || dns_packet.dns_answers.pop().is_none() {
+                return encoded_dns_response;
+            }
+        }
+    }
+}
+
+impl Decodable for DnsPacket {
+    fn parse<'a>(message: &'a [u8], input: &'a [u8]) -> IResult<&'a [u8], DnsPacket, DnsError> {
+        let (input, dns_header) = DnsHeader::parse(message, input)?;
+        let (input, dns_questions) = count(
+            |input| DnsQuestion::parse(message, input),
+            dns_header.question_count as usize,
+        )(input)?;
+        let (input, dns_answers) = count(
+            |input| DnsAnswer::parse(message, input),
+            dns_header.answer_record_count as usize,
+        )(input)?;
+        let (input, dns_authorities) = count(
+            |input| DnsAnswer::parse(message, input),
+            dns_header.authority_record_count as usize,
+        )(input)?;
+        let mut input = input;
+        let mut dns_additionals: Vec<DnsAnswer> = vec![];
+        let mut edns: Option<Edns> = None;
+        for _ in 0..dns_header.additional_record_count {
+            match Edns::parse(message, input) {
+                Ok((_, _)) if edns.is_some() => {
+                    return Err(nom::Err::Failure(DnsError::DuplicateOpt));
+                }
+                Ok((rest, opt)) => {
+                    edns = Some(opt);
+                    input = rest;
+                }
+                Err(nom::Err::Error(_)) => {
+                    let (rest, dns_additional) = DnsAnswer::parse(message, input)?;
+                    dns_additionals.push(dns_additional);
+                    input = rest;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        return Ok((
+            input,
+            DnsPacket {
+                dns_header,
+                dns_questions,
+                dns_answers,
+                dns_authorities,
+                dns_additionals,
+                edns,
+            },
+        ));
     }
 }
 
 impl Encodable for DnsPacket {
     fn encode(&self) -> Vec<u8> {
-        let mut encoded_dns_request: Vec<u8> = self.dns_header.encode();
+        self.encode_with_compression(true)
+    }
+}
+
+// The whole message section by section, as dig prints a reply.
+impl fmt::Display for DnsPacket {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut dns_header = self.dns_header.clone();
+        dns_header.question_count = self.dns_questions.len() as u16;
+        dns_header.answer_record_count = self.dns_answers.len() as u16;
+        dns_header.authority_record_count = self.dns_authorities.len() as u16;
+        dns_header.additional_record_count =
+            self.dns_additionals.len() as u16 + self.edns.is_some() as u16;
+        dns_header.fmt_with_response_code(f, self.response_code())?;
+        writeln!(f)?;
+        if let Some(edns) = self.edns.as_ref() {
+            write!(f, "\n;; OPT PSEUDOSECTION:\n{}\n", edns)?;
+        }
+        write!(f, "\n;; QUESTION SECTION:\n")?;
         for dns_question in self.dns_questions.iter() {
-            encoded_dns_request.extend(dns_question.encode());
+            writeln!(f, ";{}", dns_question)?;
         }
-        for dns_answer in self.dns_answers.iter() {
-            encoded_dns_request.extend(dns_answer.encode());
+        for (title, dns_answers) in [
+            ("ANSWER", &self.dns_answers),
+            ("AUTHORITY", &self.dns_authorities),
+            ("ADDITIONAL", &self.dns_additionals),
+        ] {
+            if dns_answers.is_empty() {
+                continue;
+            }
+            write!(f, "\n;; {} SECTION:\n", title)?;
+            for dns_answer in dns_answers {
+                writeln!(f, "{}", dns_answer)?;
+            }
         }
-        encoded_dns_request
+        Ok(())
     }
 }
@@ -0,0 +1,827 @@
+use crate::models::{DnsAnswer, DnsPacket, Label, RData, RecordType, ResponseCode};
+use crate::traits::Encodable;
+use ring::digest;
+use ring::signature::{self, RsaPublicKeyComponents, UnparsedPublicKey};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// Checks signatures on the answers we get from upstream, following the chain
+// of DS and DNSKEY records from the signing zone up to a trust anchor.
+// specification: https://www.rfc-editor.org/rfc/rfc4035#section-5
+
+// Signing algorithms we can verify.
+// Source: https://www.iana.org/assignments/dns-sec-alg-numbers/dns-sec-alg-numbers.xhtml
+pub const RSASHA256: u8 = 8;
+pub const ECDSAP256SHA256: u8 = 13;
+pub const ED25519: u8 = 15;
+
+// DS digest types.
+// Source: https://www.iana.org/assignments/ds-rr-types/ds-rr-types.xhtml
+pub const SHA1: u8 = 1;
+pub const SHA256: u8 = 2;
+pub const SHA384: u8 = 4;
+
+// The only NSEC3 hash algorithm, and the flag of gaps that may hide
+// unsigned delegations.
+// Source: https://www.rfc-editor.org/rfc/rfc5155#section-3.1
+const NSEC3_SHA1: u8 = 1;
+const NSEC3_OPT_OUT: u8 = 0x01;
+
+// Only keys with the zone key flag and protocol 3 may verify RRSIGs.
+// Source: https://www.rfc-editor.org/rfc/rfc4034#section-2.1.1
+const ZONE_KEY_FLAG: u16 = 0x0100;
+const DNSSEC_PROTOCOL: u8 = 3;
+
+// Bounds the delegations followed from a signing zone up to a trust anchor.
+const MAX_CHAIN_LENGTH: usize = 16;
+
+// Source: https://www.rfc-editor.org/rfc/rfc4034#appendix-B
+pub fn key_tag(dnskey: &RData) -> u16 {
+    let mut accumulator: u32 = 0;
+    for (index, byte) in dnskey.encode().iter().enumerate() {
+        accumulator += match index % 2 {
+            0 => (*byte as u32) << 8,
+            _ => *byte as u32,
+        };
+    }
+    accumulator += (accumulator >> 16) & 0xffff;
+    (accumulator & 0xffff) as u16
+}
+
+// The digest a DS record holds for a DNSKEY of the zone at `owner`, None for
+// digest types we do not know.
+// Source: https://www.rfc-editor.org/rfc/rfc4034#section-5.1.4
+pub fn ds_digest(owner: &[Label], dnskey: &RData, digest_type: u8) -> Option<Vec<u8>> {
+    let algorithm = match digest_type {
+        SHA1 => &digest::SHA1_FOR_LEGACY_USE_ONLY,
+        SHA256 => &digest::SHA256,
+        SHA384 => &digest::SHA384,
+        _ => return None,
+    };
+    let mut data = Label::encode_canonical_name(owner);
+    data.extend(dnskey.encode());
+    Some(digest::digest(algorithm, &data).as_ref().to_vec())
+}
+
+// Sorts records by owner name in canonical order, then by type and by the
+// canonical form of their RDATA, dropping duplicates on the way.
+// Source: https://www.rfc-editor.org/rfc/rfc4034#section-6.3
+pub fn canonical_order(records: &mut Vec<DnsAnswer>) {
+    records.sort_by(|a, b| {
+        Label::canonical_cmp(&a.name, &b.name)
+            .then(a.record_type.to_u16().cmp(&b.record_type.to_u16()))
+            .then(a.class.to_u16().cmp(&b.class.to_u16()))
+            .then_with(|| a.rdata.encode_canonical().cmp(&b.rdata.encode_canonical()))
+    });
+    records.dedup_by(|a, b| {
+        Label::to_key(&a.name) == Label::to_key(&b.name)
+            && a.record_type == b.record_type
+            && a.class == b.class
+            && a.rdata.encode_canonical() == b.rdata.encode_canonical()
+    });
+}
+
+// What an RRSIG signs: its own fields without the signature, followed by the
+// RRset in canonical form and order with the original TTL. Records expanded
+// from a wildcard are signed under the wildcard name.
+// Source: https://www.rfc-editor.org/rfc/rfc4034#section-3.1.8.1
+pub fn signed_data(rrset: &[DnsAnswer], rrsig: &RData) -> Vec<u8> {
+    let (labels, original_ttl) = match rrsig {
+        RData::RRSIG {
+            labels,
+            original_ttl,
+            ..
+        } => (*labels as usize, *original_ttl),
+        _ => return vec![],
+    };
+    let mut data = rrsig.encode_signed_fields(Label::encode_canonical_name);
+    let mut records = rrset.to_vec();
+    canonical_order(&mut records);
+    for record in records {
+        let owner = match labels < label_count(&record.name) {
+            true => {
+                let mut owner = vec![Label::wildcard()];
+                owner.extend_from_slice(&record.name[record.name.len() - labels..]);
+                owner
+            }
+            false => record.name.clone(),
+        };
+        let rdata = record.rdata.encode_canonical();
+        data.extend(Label::encode_canonical_name(&owner));
+        data.extend_from_slice(&record.record_type.to_u16().to_be_bytes());
+        data.extend_from_slice(&record.class.to_u16().to_be_bytes());
+        data.extend_from_slice(&original_ttl.to_be_bytes());
+        data.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+        data.extend(rdata);
+    }
+    data
+}
+
+// Checks one RRSIG over the RRset with any of the keys that could have made
+// it. `now` is in seconds since the Unix epoch.
+// Source: https://www.rfc-editor.org/rfc/rfc4035#section-5.3
+pub fn verify_signature(
+    rrset: &[DnsAnswer],
+    rrsig: &DnsAnswer,
+    dnskeys: &[DnsAnswer],
+    now: u32,
+) -> Result<(), String> {
+    let (type_covered, algorithm, labels, expiration, inception, tag, signer_name, signature) =
+        match &rrsig.rdata {
+            RData::RRSIG {
+                type_covered,
+                algorithm,
+                labels,
+                expiration,
+                inception,
+                key_tag,
+                signer_name,
+                signature,
+                ..
+            } => (
+                *type_covered,
+                *algorithm,
+                *labels,
+                *expiration,
+                *inception,
+                *key_tag,
+                signer_name,
+                signature,
+            ),
+            _ => return Err("not an RRSIG record".to_string()),
+        };
+    let first = rrset.first().ok_or_else(|| "empty RRset".to_string())?;
+    if type_covered != first.record_type || rrsig.class != first.class {
+        return Err("signature covers another type or class".to_string());
+    }
+    if labels as usize > label_count(&first.name) {
+        return Err("signature has more labels than the owner name".to_string());
+    }
+    // Times compare in serial number arithmetic, so that they wrap in 2106.
+    // Source: https://www.rfc-editor.org/rfc/rfc4034#section-3.1.5
+    if (now.wrapping_sub(inception) as i32) < 0 {
+        return Err("signature is not valid yet".to_string());
+    }
+    if (expiration.wrapping_sub(now) as i32) < 0 {
+        return Err("signature has expired".to_string());
+    }
+    let message = signed_data(rrset, &rrsig.rdata);
+    for dnskey in dnskeys {
+        let (flags, protocol, key_algorithm, public_key) = match &dnskey.rdata {
+            RData::DNSKEY {
+                flags,
+                protocol,
+                algorithm,
+                public_key,
+            } => (*flags, *protocol, *algorithm, public_key),
+            _ => continue,
+        };
+        if key_algorithm != algorithm
+            || protocol != DNSSEC_PROTOCOL
+            || flags & ZONE_KEY_FLAG == 0
+            || key_tag(&dnskey.rdata) != tag
+            || Label::to_key(&dnskey.name) != Label::to_key(signer_name)
+        {
+            continue;
+        }
+        if verify_with_key(algorithm, public_key, &message, signature) {
+            return Ok(());
+        }
+    }
+    Err(format!("no DNSKEY with tag {} verifies the signature", tag))
+}
+
+pub fn is_supported_algorithm(algorithm: u8) -> bool {
+    matches!(algorithm, RSASHA256 | ECDSAP256SHA256 | ED25519)
+}
+
+fn verify_with_key(algorithm: u8, public_key: &[u8], message: &[u8], signature: &[u8]) -> bool {
+    match algorithm {
+        // DNSSEC still allows RSA keys of 1024 bits, below what ring accepts
+        // for new uses.
+        RSASHA256 => match rsa_components(public_key) {
+            Some((exponent, modulus)) => RsaPublicKeyComponents {
+                n: modulus,
+                e: exponent,
+            }
+            .verify(
+                &signature::RSA_PKCS1_1024_8192_SHA256_FOR_LEGACY_USE_ONLY,
+                message,
+                signature,
+            )
+            .is_ok(),
+            None => false,
+        },
+        // The key is the bare point and the signature r followed by s.
+        // Source: https://www.rfc-editor.org/rfc/rfc6605#section-4
+        ECDSAP256SHA256 => {
+            let mut point = vec![0x04];
+            point.extend_from_slice(public_key);
+            UnparsedPublicKey::new(&signature::ECDSA_P256_SHA256_FIXED, point)
+                .verify(message, signature)
+                .is_ok()
+        }
+        // Source: https://www.rfc-editor.org/rfc/rfc8080#section-3
+        ED25519 => UnparsedPublicKey::new(&signature::ED25519, public_key)
+            .verify(message, signature)
+            .is_ok(),
+        _ => false,
+    }
+}
+
+// The exponent and modulus of an RSA key, the exponent length taking one
+// octet or three if the first is zero.
+// Source: https://www.rfc-editor.org/rfc/rfc3110#section-2
+fn rsa_components(public_key: &[u8]) -> Option<(&[u8], &[u8])> {
+    let (length, rest) = match public_key.first()? {
+        0 => (
+            u16::from_be_bytes([*public_key.get(1)?, *public_key.get(2)?]) as usize,
+            &public_key[3..],
+        ),
+        length => (*length as usize, &public_key[1..]),
+    };
+    if length == 0 || rest.len() <= length {
+        return None;
+    }
+    Some(rest.split_at(length))
+}
+
+// Labels of the name as counted by RRSIGs, a leading wildcard label does not
+// count.
+// Source: https://www.rfc-editor.org/rfc/rfc4034#section-3.1.3
+fn label_count(name: &[Label]) -> usize {
+    match name.first() {
+        Some(label) if *label == Label::wildcard() => name.len() - 1,
+        _ => name.len(),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Validation {
+    // Every answer verified along a chain of trust from an anchor.
+    Secure,
+    // Not shown to be secure, such as answers from zones proven to be
+    // unsigned, names outside of our trust anchors and negative answers,
+    // whose proofs of nonexistence we do not check.
+    Insecure,
+    // A signature that should verify does not.
+    Bogus(String),
+}
+
+// Looks up the records of a name and type, from the answer and authority
+// sections so that proofs of nonexistence come along, with their RRSIGs.
+// None if the lookup failed altogether.
+pub type Fetch<'a> = dyn Fn(&[Label], RecordType) -> Option<Vec<DnsAnswer>> + 'a;
+
+// What the signatures of an RRset show.
+enum Verified {
+    // A signature verifies with keys proven along a chain of trust.
+    Secure,
+    // As above, but the RRset was expanded from a wildcard and we do not
+    // check the proof that the name itself does not exist.
+    Expanded,
+    // No signature we can check, or only ones of zones without a chain of
+    // trust.
+    Unsigned,
+}
+
+// What NSEC or NSEC3 records prove about the DS records at a name.
+enum DsDenial {
+    // The name is a delegation to a child zone that is not signed.
+    UnsignedDelegation,
+    // The name is no zone cut at all, so it belongs to the zone above.
+    NoDelegation,
+}
+
+pub struct Validator {
+    // DS or DNSKEY records of the zones we trust without asking anyone.
+    trust_anchors: Vec<DnsAnswer>,
+    // DNSKEY RRsets already validated, by zone, until their TTL runs out.
+    zone_keys: Mutex<HashMap<String, (Vec<DnsAnswer>, Instant)>>,
+    // Delegations proven to be unsigned, until the TTL of the proof runs out.
+    unsigned_zones: Mutex<HashMap<String, Instant>>,
+}
+
+impl Validator {
+    pub fn new(trust_anchors: Vec<DnsAnswer>) -> Validator {
+        Validator {
+            trust_anchors: trust_anchors
+                .into_iter()
+                .filter(|record| matches!(record.rdata, RData::DS { .. } | RData::DNSKEY { .. }))
+                .collect(),
+            zone_keys: Mutex::new(HashMap::new()),
+            unsigned_zones: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // Only positive answers can come out secure. Signatures in the authority
+    // section are checked as well, so that bogus ones are caught, but its
+    // unsigned records such as referral NS sets are fine.
+    pub fn validate(&self, dns_response: &DnsPacket, fetch: &Fetch, now: SystemTime) -> Validation {
+        let now = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() as u32;
+        let mut secure = dns_response.response_code() == ResponseCode::NoError as u16
+            && !dns_response.dns_answers.is_empty();
+        for (is_answer, section) in [
+            (true, &dns_response.dns_answers),
+            (false, &dns_response.dns_authorities),
+        ] {
+            for (rrset, signatures) in signed_rrsets(section) {
+                let verified = match self.verify_rrset(&rrset, &signatures, fetch, now, 0) {
+                    // An answer without signatures we can check is only
+                    // insecure if its zone is proven to be unsigned. In a
+                    // signed zone they were stripped or replaced on the way.
+                    // Source: https://www.rfc-editor.org/rfc/rfc4035#section-5
+                    Ok(Verified::Unsigned) if is_answer => {
+                        match self.enclosing_zone_keys(&rrset[0].name, fetch, now, 0) {
+                            Ok(Some(_)) => Err("no valid signature in a signed zone".to_string()),
+                            Ok(None) => Ok(Verified::Unsigned),
+                            Err(reason) => Err(reason),
+                        }
+                    }
+                    verified => verified,
+                };
+                match verified {
+                    Ok(Verified::Secure) => {}
+                    Ok(_) => secure &= !is_answer,
+                    Err(reason) => {
+                        return Validation::Bogus(format!(
+                            "{} {}: {}",
+                            Label::to_key(&rrset[0].name),
+                            rrset[0].record_type,
+                            reason
+                        ))
+                    }
+                }
+            }
+        }
+        match secure {
+            true => Validation::Secure,
+            false => Validation::Insecure,
+        }
+    }
+
+    // Whether one of the signatures verifies with validated keys of its
+    // signer, unsigned if the signer is outside of our trust anchors.
+    fn verify_rrset(
+        &self,
+        rrset: &[DnsAnswer],
+        signatures: &[DnsAnswer],
+        fetch: &Fetch,
+        now: u32,
+        depth: usize,
+    ) -> Result<Verified, String> {
+        let owner = &rrset[0].name;
+        let mut errors: Vec<String> = vec![];
+        let mut supported = false;
+        for signature in signatures {
+            let (algorithm, labels, signer_name) = match &signature.rdata {
+                RData::RRSIG {
+                    algorithm,
+                    labels,
+                    signer_name,
+                    ..
+                } => (*algorithm, *labels, signer_name),
+                _ => continue,
+            };
+            // Signatures by algorithms we do not know are as good as none.
+            // Source: https://www.rfc-editor.org/rfc/rfc4035#section-5.3.1
+            if !is_supported_algorithm(algorithm) {
+                continue;
+            }
+            supported = true;
+            if !Label::is_subdomain(owner, signer_name) {
+                errors.push("signer is not an ancestor of the owner".to_string());
+                continue;
+            }
+            let dnskeys = match self.zone_keys(signer_name, fetch, now, depth)? {
+                Some(dnskeys) => dnskeys,
+                None => return Ok(Verified::Unsigned),
+            };
+            match verify_signature(rrset, signature, &dnskeys, now) {
+                // An answer expanded from a wildcard is only secure along
+                // with proof that the name itself does not exist, which we
+                // do not check.
+                Ok(()) if (labels as usize) < label_count(owner) => return Ok(Verified::Expanded),
+                Ok(()) => return Ok(Verified::Secure),
+                Err(e) => errors.push(e),
+            }
+        }
+        match supported {
+            true => Err(errors.join("; ")),
+            false => Ok(Verified::Unsigned),
+        }
+    }
+
+    // The DNSKEY RRset of the signed zone a name belongs to, found by
+    // walking down from the closest trust anchor and asking for the DS
+    // records of every name on the way. None if no trust anchor covers the
+    // name or the walk ends in a delegation proven to be unsigned.
+    fn enclosing_zone_keys(
+        &self,
+        name: &[Label],
+        fetch: &Fetch,
+        now: u32,
+        depth: usize,
+    ) -> Result<Option<Vec<DnsAnswer>>, String> {
+        let anchor_length = match self
+            .trust_anchors
+            .iter()
+            .filter(|anchor| Label::is_subdomain(name, &anchor.name))
+            .map(|anchor| anchor.name.len())
+            .max()
+        {
+            Some(anchor_length) => anchor_length,
+            None => return Ok(None),
+        };
+        let anchor = &name[name.len() - anchor_length..];
+        let mut dnskeys = match self.zone_keys(anchor, fetch, now, depth)? {
+            Some(dnskeys) => dnskeys,
+            None => return Ok(None),
+        };
+        for length in anchor_length + 1..=name.len() {
+            let ancestor = &name[name.len() - length..];
+            if self.is_unsigned_zone(ancestor) {
+                return Ok(None);
+            }
+            let key = Label::to_key(ancestor);
+            let records = fetch(ancestor, RecordType::DS)
+                .ok_or_else(|| format!("failed to look up the DS records of '{}'", key))?;
+            let (ds_set, _) = split_rrset(records.clone(), ancestor, RecordType::DS);
+            if !ds_set.is_empty() {
+                dnskeys = match self.zone_keys(ancestor, fetch, now, depth + 1)? {
+                    Some(dnskeys) => dnskeys,
+                    None => return Ok(None),
+                };
+                continue;
+            }
+            match self.denied_ds(ancestor, &records, fetch, now, depth)? {
+                Some(DsDenial::UnsignedDelegation) => return Ok(None),
+                Some(DsDenial::NoDelegation) => {}
+                None => {
+                    return Err(format!(
+                        "no DS records for '{}' and no proof that there are none",
+                        key
+                    ))
+                }
+            }
+        }
+        Ok(Some(dnskeys))
+    }
+
+    // What the signed NSEC and NSEC3 records among the records prove about
+    // the DS records at a name, None if they prove nothing. Only the zone
+    // above the name may sign them.
+    // Source: https://www.rfc-editor.org/rfc/rfc4035#section-5.2
+    fn denied_ds(
+        &self,
+        name: &[Label],
+        records: &[DnsAnswer],
+        fetch: &Fetch,
+        now: u32,
+        depth: usize,
+    ) -> Result<Option<DsDenial>, String> {
+        for (rrset, signatures) in signed_rrsets(records) {
+            let denial = match &rrset[0].rdata {
+                RData::NSEC {
+                    next_domain_name,
+                    types,
+                } => nsec_denial(name, &rrset[0].name, next_domain_name, types),
+                RData::NSEC3 {
+                    hash_algorithm,
+                    flags,
+                    iterations,
+                    salt,
+                    next_hashed_owner,
+                    types,
+                } if *hash_algorithm == NSEC3_SHA1 => nsec3_denial(
+                    name,
+                    &rrset[0].name,
+                    *flags,
+                    &nsec3_hash(name, salt, *iterations),
+                    next_hashed_owner,
+                    types,
+                ),
+                _ => None,
+            };
+            let denial = match denial {
+                Some(denial) => denial,
+                None => continue,
+            };
+            let signatures = self.parent_signatures(signatures, name);
+            if signatures.is_empty() {
+                continue;
+            }
+            match self.verify_rrset(&rrset, &signatures, fetch, now, depth + 1)? {
+                Verified::Secure => {}
+                _ => continue,
+            }
+            if let DsDenial::UnsignedDelegation = denial {
+                let time_to_live = rrset[0].time_to_live as u64;
+                self.unsigned_zones.lock().unwrap().insert(
+                    Label::to_key(name),
+                    Instant::now() + Duration::from_secs(time_to_live),
+                );
+            }
+            return Ok(Some(denial));
+        }
+        Ok(None)
+    }
+
+    fn is_unsigned_zone(&self, zone: &[Label]) -> bool {
+        match self
+            .unsigned_zones
+            .lock()
+            .unwrap()
+            .get(&Label::to_key(zone))
+        {
+            Some(expires_at) => Instant::now() < *expires_at,
+            None => false,
+        }
+    }
+
+    // The signatures a zone above the name made, as only it may sign the DS
+    // records of the name or the proof that there are none. Signers above
+    // our trust anchors cannot vouch for anything below them.
+    fn parent_signatures(&self, signatures: Vec<DnsAnswer>, name: &[Label]) -> Vec<DnsAnswer> {
+        signatures
+            .into_iter()
+            .filter(|signature| match &signature.rdata {
+                RData::RRSIG { signer_name, .. } => {
+                    signer_name.len() < name.len()
+                        && self
+                            .trust_anchors
+                            .iter()
+                            .any(|anchor| Label::is_subdomain(signer_name, &anchor.name))
+                }
+                _ => false,
+            })
+            .collect()
+    }
+
+    // The DNSKEY RRset of a zone once it is proven, either through a trust
+    // anchor for the zone or through a DS RRset its parent signed. None if
+    // no trust anchor covers the zone or the chain ends in a delegation
+    // proven to be unsigned.
+    fn zone_keys(
+        &self,
+        zone: &[Label],
+        fetch: &Fetch,
+        now: u32,
+        depth: usize,
+    ) -> Result<Option<Vec<DnsAnswer>>, String> {
+        if depth > MAX_CHAIN_LENGTH {
+            return Err("chain of trust is too long".to_string());
+        }
+        let key = Label::to_key(zone);
+        if let Some((dnskeys, expires_at)) = self.zone_keys.lock().unwrap().get(&key) {
+            if Instant::now() < *expires_at {
+                return Ok(Some(dnskeys.clone()));
+            }
+        }
+        let anchors: Vec<DnsAnswer> = self
+            .trust_anchors
+            .iter()
+            .filter(|anchor| Label::to_key(&anchor.name) == key)
+            .cloned()
+            .collect();
+        let trusted = match anchors.is_empty() {
+            false => anchors,
+            true => {
+                let covered = self
+                    .trust_anchors
+                    .iter()
+                    .any(|anchor| Label::is_subdomain(zone, &anchor.name));
+                if !covered || self.is_unsigned_zone(zone) {
+                    return Ok(None);
+                }
+                let records = fetch(zone, RecordType::DS)
+                    .ok_or_else(|| format!("failed to look up the DS records of '{}'", key))?;
+                let (ds_set, signatures) = split_rrset(records.clone(), zone, RecordType::DS);
+                if ds_set.is_empty() {
+                    return match self.denied_ds(zone, &records, fetch, now, depth)? {
+                        Some(DsDenial::UnsignedDelegation) => Ok(None),
+                        _ => Err(format!(
+                            "no DS records for '{}' and no proof that it is unsigned",
+                            key
+                        )),
+                    };
+                }
+                // The DS RRset belongs to the parent, which has to sign it.
+                let signatures = self.parent_signatures(signatures, zone);
+                if signatures.is_empty() {
+                    return Err(format!("DS records of '{}' are not signed", key));
+                }
+                match self.verify_rrset(&ds_set, &signatures, fetch, now, depth + 1)? {
+                    Verified::Secure => ds_set,
+                    _ => return Ok(None),
+                }
+            }
+        };
+        // A zone whose DS records all use algorithms or digests we do not
+        // know is treated as unsigned.
+        // Source: https://www.rfc-editor.org/rfc/rfc4035#section-5.2
+        let usable = trusted.iter().any(|anchor| match &anchor.rdata {
+            RData::DS {
+                algorithm,
+                digest_type,
+                ..
+            } => {
+                is_supported_algorithm(*algorithm) && matches!(*digest_type, SHA1 | SHA256 | SHA384)
+            }
+            RData::DNSKEY { algorithm, .. } => is_supported_algorithm(*algorithm),
+            _ => false,
+        });
+        if !usable {
+            return Ok(None);
+        }
+        let records = fetch(zone, RecordType::DNSKEY)
+            .ok_or_else(|| format!("failed to look up the DNSKEY records of '{}'", key))?;
+        let (dnskeys, signatures) = split_rrset(records, zone, RecordType::DNSKEY);
+        let entry_keys: Vec<DnsAnswer> = dnskeys
+            .iter()
+            .filter(|dnskey| {
+                trusted
+                    .iter()
+                    .any(|anchor| matches_anchor(zone, &dnskey.rdata, &anchor.rdata))
+            })
+            .cloned()
+            .collect();
+        if entry_keys.is_empty() {
+            return Err(format!(
+                "no DNSKEY of '{}' matches its DS records or trust anchors",
+                key
+            ));
+        }
+        let self_signed = signatures
+            .iter()
+            .any(|signature| verify_signature(&dnskeys, signature, &entry_keys, now).is_ok());
+        if !self_signed {
+            return Err(format!(
+                "DNSKEY records of '{}' are not signed by a trusted key",
+                key
+            ));
+        }
+        let time_to_live = dnskeys
+            .iter()
+            .map(|dnskey| dnskey.time_to_live)
+            .min()
+            .unwrap_or(0);
+        self.zone_keys.lock().unwrap().insert(
+            key,
+            (
+                dnskeys.clone(),
+                Instant::now() + Duration::from_secs(time_to_live as u64),
+            ),
+        );
+        Ok(Some(dnskeys))
+    }
+}
+
+// What an NSEC record at `owner` proves about the DS records at a name: it
+// either lists the types at the name or covers the gap the name falls in.
+// An NSEC at a delegation says nothing about the names below it, which are
+// in the child zone.
+// Source: https://www.rfc-editor.org/rfc/rfc4035#section-5.4
+fn nsec_denial(
+    name: &[Label],
+    owner: &[Label],
+    next_domain_name: &[Label],
+    types: &[u16],
+) -> Option<DsDenial> {
+    let delegation =
+        types.contains(&RecordType::NS.to_u16()) && !types.contains(&RecordType::SOA.to_u16());
+    if Label::to_key(owner) == Label::to_key(name) {
+        return types_denial(types);
+    }
+    if delegation && Label::is_subdomain(name, owner) {
+        return None;
+    }
+    let after_owner = Label::canonical_cmp(owner, name) == Ordering::Less;
+    let before_next = Label::canonical_cmp(name, next_domain_name) == Ordering::Less;
+    // The last NSEC of the zone points back at the apex.
+    let last = Label::canonical_cmp(next_domain_name, owner) != Ordering::Greater;
+    match (after_owner && before_next) || (last && (after_owner || before_next)) {
+        true => Some(DsDenial::NoDelegation),
+        false => None,
+    }
+}
+
+// The same for an NSEC3 record, by the hash of the name. A gap flagged as
+// opt-out may hide unsigned delegations, any other holds no names at all.
+// Source: https://www.rfc-editor.org/rfc/rfc5155#section-8.6
+fn nsec3_denial(
+    name: &[Label],
+    owner: &[Label],
+    flags: u8,
+    hash: &[u8],
+    next_hashed_owner: &[u8],
+    types: &[u16],
+) -> Option<DsDenial> {
+    if owner.is_empty() || !Label::is_subdomain(name, &owner[1..]) {
+        return None;
+    }
+    let owner_hash = data_encoding::BASE32HEX_NOPAD
+        .decode(&owner.first()?.as_bytes().to_ascii_uppercase())
+        .ok()?;
+    if owner_hash == hash {
+        return types_denial(types);
+    }
+    let after_owner = owner_hash.as_slice() < hash;
+    let before_next = hash < next_hashed_owner;
+    let last = next_hashed_owner <= owner_hash.as_slice();
+    if !((after_owner && before_next) || (last && (after_owner || before_next))) {
+        return None;
+    }
+    match flags & NSEC3_OPT_OUT {
+        0 => Some(DsDenial::NoDelegation),
+        _ => Some(DsDenial::UnsignedDelegation),
+    }
+}
+
+// The types at a name with no DS among them.
+fn types_denial(types: &[u16]) -> Option<DsDenial> {
+    if types.contains(&RecordType::DS.to_u16()) {
+        return None;
+    }
+    match types.contains(&RecordType::NS.to_u16()) && !types.contains(&RecordType::SOA.to_u16()) {
+        true => Some(DsDenial::UnsignedDelegation),
+        false => Some(DsDenial::NoDelegation),
+    }
+}
+
+// The salted and iterated SHA-1 hash NSEC3 records are named by.
+// Source: https://www.rfc-editor.org/rfc/rfc5155#section-5
+fn nsec3_hash(name: &[Label], salt: &[u8], iterations: u16) -> Vec<u8> {
+    let mut hash = Label::encode_canonical_name(name);
+    for _ in 0..=iterations {
+        hash.extend_from_slice(salt);
+        hash = digest::digest(&digest::SHA1_FOR_LEGACY_USE_ONLY, &hash)
+            .as_ref()
+            .to_vec();
+    }
+    hash
+}
+
+fn matches_anchor(zone: &[Label], dnskey: &RData, anchor: &RData) -> bool {
+    match (dnskey, anchor) {
+        (
+            RData::DNSKEY { algorithm, .. },
+            RData::DS {
+                key_tag: tag,
+                algorithm: ds_algorithm,
+                digest_type,
+                digest,
+            },
+        ) => {
+            algorithm == ds_algorithm
+                && key_tag(dnskey) == *tag
+                && ds_digest(zone, dnskey, *digest_type).as_ref() == Some(digest)
+        }
+        (RData::DNSKEY { .. }, RData::DNSKEY { .. }) => dnskey == anchor,
+        _ => false,
+    }
+}
+
+// The records of one type at a name, and the RRSIGs covering them.
+fn split_rrset(
+    records: Vec<DnsAnswer>,
+    name: &[Label],
+    record_type: RecordType,
+) -> (Vec<DnsAnswer>, Vec<DnsAnswer>) {
+    let key = Label::to_key(name);
+    let (rrset, signatures): (Vec<DnsAnswer>, Vec<DnsAnswer>) = records
+        .into_iter()
+        .filter(|record| Label::to_key(&record.name) == key)
+        .filter(|record| {
+            record.record_type == record_type
+                || matches!(record.rdata, RData::RRSIG { type_covered, .. } if type_covered == record_type)
+        })
+        .partition(|record| record.record_type == record_type);
+    (rrset, signatures)
+}
+
+// Groups a section into RRsets, each with the RRSIGs covering it.
+fn signed_rrsets(records: &[DnsAnswer]) -> Vec<(Vec<DnsAnswer>, Vec<DnsAnswer>)> {
+    let mut rrsets: Vec<(Vec<DnsAnswer>, Vec<DnsAnswer>)> = vec![];
+    for record in records
+        .iter()
+        .filter(|record| record.record_type != RecordType::RRSIG)
+    {
+        let existing = rrsets.iter_mut().find(|(rrset, _)| {
+            Label::to_key(&rrset[0].name) == Label::to_key(&record.name)
+                && rrset[0].record_type == record.record_type
+                && rrset[0].class == record.class
+        });
+        match existing {
+            Some((rrset, _)) => rrset.push(record.clone()),
+            None => rrsets.push((vec![record.clone()], vec![])),
+        }
+    }
+    for (rrset, signatures) in rrsets.iter_mut() {
+        let (_, covering) = split_rrset(records.to_vec(), &rrset[0].name, rrset[0].record_type);
+        *signatures = covering;
+    }
+    rrsets
+}
@@ -0,0 +1,132 @@
+use crate::models::presentation::format_hex;
+use crate::models::DnsError;
+use crate::traits::Encodable;
+use nom::multi::{length_data, many0};
+use nom::number::complete::{be_u16, be_u32, be_u8};
+use nom::sequence::tuple;
+use nom::IResult;
+use std::fmt;
+
+// specification: https://www.rfc-editor.org/rfc/rfc6891
+pub const OPT_RECORD_TYPE: u16 = 41;
+// Anything smaller than the classic limit is treated as 512.
+// Source: https://www.rfc-editor.org/rfc/rfc6891#section-6.2.3
+pub const MIN_UDP_PAYLOAD_SIZE: u16 = 512;
+
+// An option carried in the OPT RDATA, such as client subnet (8) or cookie (10).
+// Options are passed through untouched so they survive being forwarded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EdnsOption {
+    pub code: u16,
+    pub data: Vec<u8>,
+}
+
+// The OPT pseudo-record, which reuses the CLASS field for the payload size and
+// the TTL field for the extended RCODE, version and flags.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edns {
+    pub udp_payload_size: u16,
+    // Upper eight bits of the twelve bit RCODE, the lower four stay in the header.
+    pub extended_rcode: u8,
+    pub version: u8,
+    // DNSSEC OK: https://www.rfc-editor.org/rfc/rfc3225
+    pub dnssec_ok: bool,
+    // The remaining, currently unassigned, flag bits.
+    pub z: u16,
+    pub options: Vec<EdnsOption>,
+}
+
+impl Edns {
+    pub fn new(udp_payload_size: u16) -> Edns {
+        Edns {
+            udp_payload_size,
+            extended_rcode: 0,
+            version: 0,
+            dnssec_ok: false,
+            z: 0,
+            options: vec![],
+        }
+    }
+
+    // The largest UDP response the sender of this record accepts.
+    pub fn max_payload_size(&self) -> usize {
+        self.udp_payload_size.max(MIN_UDP_PAYLOAD_SIZE) as usize
+    }
+
+    // Parses an OPT record, failing with a recoverable nom error when the
+    // record at the input is some other type so the caller can try that.
+    pub fn parse<'a>(_message: &'a [u8], input: &'a [u8]) -> IResult<&'a [u8], Edns, DnsError> {
+        let (rest, (name, record_type)) = tuple((be_u8, be_u16))(input)?;
+        if name != 0x00 || record_type != OPT_RECORD_TYPE {
+            return Err(nom::Err::Error(DnsError::Truncated));
+        }
+        let (rest, (udp_payload_size, ttl, data)) =
+            tuple((be_u16, be_u32, length_data(be_u16)))(rest)
+                .map_err(|_: nom::Err<DnsError>| nom::Err::Failure(DnsError::Truncated))?;
+        let (remaining, options) = many0(tuple((be_u16, length_data(be_u16))))(data)
+            .map_err(|_: nom::Err<DnsError>| nom::Err::Failure(DnsError::Truncated))?;
+        if !remaining.is_empty() {
+            return Err(nom::Err::Failure(DnsError::InvalidRData(
+                "OPT options do not match its length".to_string(),
+            )));
+        }
+        return Ok((
+            rest,
+            Edns {
+                udp_payload_size,
+                extended_rcode: (ttl >> 24) as u8,
+                version: (ttl >> 16) as u8,
+                dnssec_ok: ttl & 0x8000 != 0,
+                z: (ttl & 0x7fff) as u16,
+                options: options
+                    .into_iter()
+                    .map(|(code, data)| EdnsOption {
+                        code,
+                        data: data.to_vec(),
+                    })
+                    .collect(),
+            },
+        ));
+    }
+}
+
+impl Encodable for Edns {
+    fn encode(&self) -> Vec<u8> {
+        let mut buffer: Vec<u8> = vec![0x00];
+        buffer.extend_from_slice(&OPT_RECORD_TYPE.to_be_bytes());
+        buffer.extend_from_slice(&self.udp_payload_size.to_be_bytes());
+        let ttl = (self.extended_rcode as u32) << 24
+            | (self.version as u32) << 16
+            | (self.dnssec_ok as u32) << 15
+            | (self.z & 0x7fff) as u32;
+        buffer.extend_from_slice(&ttl.to_be_bytes());
+        let mut rdata: Vec<u8> = vec![];
+        for option in self.options.iter() {
+            rdata.extend_from_slice(&option.code.to_be_bytes());
+            rdata.extend_from_slice(&(option.data.len() as u16).to_be_bytes());
+            rdata.extend_from_slice(&option.data);
+        }
+        buffer.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+        buffer.extend(rdata);
+        buffer
+    }
+}
+
+// The OPT pseudosection the way dig prints it.
+impl fmt::Display for Edns {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let flags = match self.dnssec_ok {
+            true => " do",
+            false => "",
+        };
+        write!(
+            f,
+            "; EDNS: version: {}, flags:{}; udp: {}",
+            self.version, flags, self.udp_payload_size
+        )?;
+        for option in self.options.iter() {
+            write!(f, "\n; OPT={}: {}", option.code, format_hex(&option.data))?;
+        }
+        Ok(())
+    }
+}
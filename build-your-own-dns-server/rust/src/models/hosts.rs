@@ -0,0 +1,120 @@
+use crate::models::{Class, DnsAnswer, DnsQuestion, Label, RData, RecordType};
+use std::collections::HashMap;
+use std::net::IpAddr;
+
+// How long clients may remember an answer from the hosts file.
+const HOSTS_TIME_TO_LIVE: u32 = 60;
+
+// Static addresses for names, answered as A and AAAA records, and the
+// reverse PTR records pointing back at the first name given for an address.
+#[derive(Debug, Default)]
+pub struct Hosts {
+    records: HashMap<String, Vec<DnsAnswer>>,
+}
+
+impl Hosts {
+    pub fn new() -> Hosts {
+        Hosts::default()
+    }
+
+    pub fn insert(&mut self, address: IpAddr, names: &[Vec<Label>]) {
+        let (record_type, rdata) = match address {
+            IpAddr::V4(address) => (RecordType::A, RData::A(address)),
+            IpAddr::V6(address) => (RecordType::AAAA, RData::AAAA(address)),
+        };
+        for name in names {
+            self.insert_record(DnsAnswer::new(
+                name.clone(),
+                record_type,
+                Class::IN,
+                HOSTS_TIME_TO_LIVE,
+                rdata.clone(),
+            ));
+        }
+        if let Some(canonical_name) = names.first() {
+            self.insert_record(DnsAnswer::new(
+                reverse_name(address),
+                RecordType::PTR,
+                Class::IN,
+                HOSTS_TIME_TO_LIVE,
+                RData::PTR(canonical_name.clone()),
+            ));
+        }
+    }
+
+    pub fn extend(&mut self, other: Hosts) {
+        for dns_answer in other.records.into_values().flatten() {
+            self.insert_record(dns_answer);
+        }
+    }
+
+    fn insert_record(&mut self, dns_answer: DnsAnswer) {
+        let records = self
+            .records
+            .entry(Label::to_key(&dns_answer.name))
+            .or_default();
+        if !records.contains(&dns_answer) {
+            records.push(dns_answer);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    pub fn contains(&self, labels: &[Label]) -> bool {
+        self.records.contains_key(&Label::to_key(labels))
+    }
+
+    // The records of the asked type for a name in the hosts file, which may
+    // be none at all, or None if the name is not in the file.
+    pub fn lookup(&self, dns_question: &DnsQuestion) -> Option<Vec<DnsAnswer>> {
+        let records = self.records.get(&Label::to_key(&dns_question.labels))?;
+        Some(
+            records
+                .iter()
+                .filter(|record| record.record_type == dns_question.record_type)
+                .map(|record| DnsAnswer {
+                    // Answer with the name the way it was asked for.
+                    name: dns_question.labels.clone(),
+                    ..record.clone()
+                })
+                .collect(),
+        )
+    }
+}
+
+// The name under in-addr.arpa or ip6.arpa that PTR records for an address
+// are found at.
+// Source: https://www.rfc-editor.org/rfc/rfc1035#section-3.5
+// Source: https://www.rfc-editor.org/rfc/rfc3596#section-2.5
+pub fn reverse_name(address: IpAddr) -> Vec<Label> {
+    let mut labels: Vec<String> = match address {
+        IpAddr::V4(address) => address
+            .octets()
+            .iter()
+            .rev()
+            .map(|octet| octet.to_string())
+            .collect(),
+        IpAddr::V6(address) => address
+            .octets()
+            .iter()
+            .rev()
+            .flat_map(|octet| [octet & 0x0f, octet >> 4])
+            .map(|nibble| format!("{:x}", nibble))
+            .collect(),
+    };
+    match address {
+        IpAddr::V4(_) => labels.push("in-addr".to_string()),
+        IpAddr::V6(_) => labels.push("ip6".to_string()),
+    }
+    labels.push("arpa".to_string());
+    labels
+        .into_iter()
+        .map(|label| Label::from_string(label).expect("reverse name labels are short"))
+        .collect()
+}
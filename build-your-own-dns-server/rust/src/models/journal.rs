@@ -0,0 +1,91 @@
+use crate::models::{DnsAnswer, RData, RecordType};
+
+// One change to a zone, taking it from the version with the old SOA to the
+// one with the new SOA. A journal is a list of these, each continuing from
+// the serial the one before it ended at, and is carried in IXFR replies and
+// journal files as the same sequence of records: the old SOA, the records
+// deleted, the new SOA and the records added.
+// Source: https://www.rfc-editor.org/rfc/rfc1995#section-4
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Difference {
+    pub old_soa: DnsAnswer,
+    pub deleted: Vec<DnsAnswer>,
+    pub new_soa: DnsAnswer,
+    pub added: Vec<DnsAnswer>,
+}
+
+impl Difference {
+    pub fn old_serial(&self) -> u32 {
+        serial(&self.old_soa).unwrap_or(0)
+    }
+
+    pub fn new_serial(&self) -> u32 {
+        serial(&self.new_soa).unwrap_or(0)
+    }
+
+    pub fn to_records(&self) -> Vec<DnsAnswer> {
+        let mut records = vec![self.old_soa.clone()];
+        records.extend(self.deleted.iter().cloned());
+        records.push(self.new_soa.clone());
+        records.extend(self.added.iter().cloned());
+        records
+    }
+
+    // Splits a sequence of records at its SOAs into the differences it is
+    // made of.
+    pub fn from_records(records: &[DnsAnswer]) -> Result<Vec<Difference>, String> {
+        let mut differences: Vec<Difference> = vec![];
+        let mut records = records.iter().peekable();
+        while let Some(old_soa) = records.next() {
+            if old_soa.record_type != RecordType::SOA {
+                return Err(format!(
+                    "expected an SOA to start a difference, got {}",
+                    old_soa.record_type
+                ));
+            }
+            let mut deleted: Vec<DnsAnswer> = vec![];
+            while let Some(record) = records.next_if(|record| record.record_type != RecordType::SOA)
+            {
+                deleted.push(record.clone());
+            }
+            let new_soa = records
+                .next()
+                .ok_or_else(|| "difference without a new SOA".to_string())?;
+            let mut added: Vec<DnsAnswer> = vec![];
+            while let Some(record) = records.next_if(|record| record.record_type != RecordType::SOA)
+            {
+                added.push(record.clone());
+            }
+            let difference = Difference {
+                old_soa: old_soa.clone(),
+                deleted,
+                new_soa: new_soa.clone(),
+                added,
+            };
+            if let Some(previous) = differences.last() {
+                if previous.new_serial() != difference.old_serial() {
+                    return Err(format!(
+                        "difference from serial {} does not follow serial {}",
+                        difference.old_serial(),
+                        previous.new_serial()
+                    ));
+                }
+            }
+            differences.push(difference);
+        }
+        Ok(differences)
+    }
+}
+
+pub fn serial(soa: &DnsAnswer) -> Option<u32> {
+    match soa.rdata {
+        RData::SOA { serial, .. } => Some(serial),
+        _ => None,
+    }
+}
+
+// Whether serial `a` is newer than `b`, serials wrapping around at 2^32.
+// Source: https://www.rfc-editor.org/rfc/rfc1982#section-3.2
+pub fn serial_newer(a: u32, b: u32) -> bool {
+    a != b && (a.wrapping_sub(b) as i32) > 0
+}
@@ -1,28 +1,195 @@
+use crate::models::{presentation, DnsError};
 use crate::traits::Encodable;
+use nom::bytes::complete::take;
+use nom::number::complete::{be_u16, be_u8};
+use nom::{IResult, Offset};
+use std::cmp::Ordering;
+use std::fmt;
 
-#[derive(Clone, Debug)]
+// Labels are octets, which need not be ASCII or even UTF-8.
+// Source: https://www.rfc-editor.org/rfc/rfc2181#section-11
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Label {
-    length: u8,
-    content: String,
+    content: Vec<u8>,
 }
 
+// Limits on the wire length of a label and of a whole name, counting the
+// length octets and the final root label.
+// Source: https://www.rfc-editor.org/rfc/rfc1035#section-2.3.4
+pub const MAX_LABEL_LENGTH: usize = 63;
+pub const MAX_NAME_LENGTH: usize = 255;
+
 impl Label {
-    pub fn from_string(string: String) -> Label {
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Label, String> {
+        // An empty label would be read back as the end of the name.
+        if bytes.is_empty() {
+            return Err("empty label".to_string());
+        }
+        if bytes.len() > MAX_LABEL_LENGTH {
+            return Err(format!(
+                "label '{}' is longer than {} octets",
+                presentation::escape(&bytes, b".\\"),
+                MAX_LABEL_LENGTH
+            ));
+        }
+        Ok(Label { content: bytes })
+    }
+
+    pub fn from_string(string: String) -> Result<Label, String> {
+        Label::from_bytes(string.into_bytes())
+    }
+
+    // The label of wildcard owner names.
+    pub fn wildcard() -> Label {
         Label {
-            length: string.len() as u8,
-            content: string,
+            content: b"*".to_vec(),
         }
     }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.content
+    }
+
+    // Whether `name` is `ancestor` itself or somewhere below it, ignoring case.
+    pub fn is_subdomain(name: &[Label], ancestor: &[Label]) -> bool {
+        name.len() >= ancestor.len()
+            && name[name.len() - ancestor.len()..]
+                .iter()
+                .zip(ancestor)
+                .all(|(a, b)| a.content.eq_ignore_ascii_case(&b.content))
+    }
+
+    // Builds labels from a dotted domain name, a trailing dot and the root
+    // name "." are both accepted.
+    pub fn from_domain_name(domain_name: &str) -> Result<Vec<Label>, String> {
+        let labels = domain_name
+            .split('.')
+            .filter(|label| !label.is_empty())
+            .map(|label| Label::from_string(label.to_string()))
+            .collect::<Result<Vec<Label>, String>>()?;
+        if Label::wire_length(&labels) > MAX_NAME_LENGTH {
+            return Err(format!(
+                "name '{}' is longer than {} octets",
+                domain_name, MAX_NAME_LENGTH
+            ));
+        }
+        Ok(labels)
+    }
+
+    // The length of the name written out in full.
+    pub fn wire_length(labels: &[Label]) -> usize {
+        labels
+            .iter()
+            .map(|label| 1 + label.content.len())
+            .sum::<usize>()
+            + 1
+    }
+
+    // Parses a possibly compressed name from the start of the input, the
+    // remaining input starts just past the name as it appears at that position.
+    // If the two most significant bits are 11 this and the next byte
+    // (Sans the two most significant bits) contain a reference
+    // to an earlier label for compression.
+    // Source: https://www.rfc-editor.org/rfc/rfc1035#section-4.1.4
+    pub fn parse_name<'a>(
+        message: &'a [u8],
+        input: &'a [u8],
+    ) -> IResult<&'a [u8], Vec<Label>, DnsError> {
+        let mut labels: Vec<Label> = vec![];
+        let mut remaining: Option<&[u8]> = None;
+        let mut current = input;
+        // Wire length of the uncompressed name, starting with the root label.
+        let mut name_length: usize = 1;
+
+        loop {
+            let (rest, length) = be_u8(current)?;
+            match length & 0b11000000 {
+                0b11000000 => {
+                    let (rest, pointer) = be_u16(current)?;
+                    let pointer = (pointer & 0b0011_1111_1111_1111) as usize;
+                    let offset = message.offset(current);
+                    if pointer >= offset {
+                        return Err(nom::Err::Failure(DnsError::PointerLoop { offset }));
+                    }
+                    remaining.get_or_insert(rest);
+                    current = &message[pointer..];
+                }
+                0b00000000 if length == 0 => return Ok((remaining.unwrap_or(rest), labels)),
+                0b00000000 => {
+                    name_length += 1 + length as usize;
+                    if name_length > MAX_NAME_LENGTH {
+                        return Err(nom::Err::Failure(DnsError::NameTooLong));
+                    }
+                    let (rest, label_bytes) = take(length)(rest)?;
+                    labels.push(Label {
+                        content: label_bytes.to_vec(),
+                    });
+                    current = rest;
+                }
+                _ => return Err(nom::Err::Failure(DnsError::LabelTooLong(length))),
+            }
+        }
+    }
+
+    pub fn encode_name(labels: &[Label]) -> Vec<u8> {
+        let mut encoded_name: Vec<u8> = labels.iter().flat_map(|label| label.encode()).collect();
+        encoded_name.push(0x00);
+        encoded_name
+    }
+
+    // The wire form of a name with every uppercase ASCII letter lowercased,
+    // as it is hashed and signed.
+    // Source: https://www.rfc-editor.org/rfc/rfc4034#section-6.2
+    pub fn encode_canonical_name(labels: &[Label]) -> Vec<u8> {
+        let mut encoded_name: Vec<u8> = vec![];
+        for label in labels {
+            encoded_name.push(label.content.len() as u8);
+            encoded_name.extend(label.content.to_ascii_lowercase());
+        }
+        encoded_name.push(0x00);
+        encoded_name
+    }
+
+    // Orders names by their labels from the root down, each compared as
+    // lowercased octets, so that a name sorts right before its descendants.
+    // Source: https://www.rfc-editor.org/rfc/rfc4034#section-6.1
+    pub fn canonical_cmp(a: &[Label], b: &[Label]) -> Ordering {
+        let a_labels = a
+            .iter()
+            .rev()
+            .map(|label| label.content.to_ascii_lowercase());
+        let b_labels = b
+            .iter()
+            .rev()
+            .map(|label| label.content.to_ascii_lowercase());
+        a_labels.cmp(b_labels)
+    }
+
+    // Domain names compare case-insensitively, so every lookup goes through
+    // this lowercased, dot separated key without the trailing dot. Dots and
+    // octets that are not printable ASCII inside a label are escaped.
+    // Source: https://www.rfc-editor.org/rfc/rfc1035#section-2.3.3
+    pub fn to_key(labels: &[Label]) -> String {
+        labels
+            .iter()
+            .map(|label| presentation::escape(&label.content.to_ascii_lowercase(), b".\\"))
+            .collect::<Vec<String>>()
+            .join(".")
+    }
 }
+
 impl Encodable for Label {
     fn encode(&self) -> Vec<u8> {
-        let mut encoded_label = vec![self.length];
-        encoded_label.extend(
-            self.content
-                .chars()
-                .flat_map(|c| c.to_string().into_bytes())
-                .collect::<Vec<u8>>(),
-        );
+        let mut encoded_label = vec![self.content.len() as u8];
+        encoded_label.extend_from_slice(&self.content);
         encoded_label
     }
 }
+
+// Characters with a meaning of their own in master files are escaped.
+// specification: https://www.rfc-editor.org/rfc/rfc1035#section-5.1
+impl fmt::Display for Label {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", presentation::escape(&self.content, b".\\\"();@$ "))
+    }
+}
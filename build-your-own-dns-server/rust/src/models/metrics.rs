@@ -0,0 +1,227 @@
+use crate::models::{RecordType, ResponseCode};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+// Upper bounds in seconds of the upstream latency histogram buckets.
+const LATENCY_BUCKETS: [f64; 12] = [
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0,
+];
+
+#[derive(Debug, Default, Clone)]
+struct Histogram {
+    // Observations at or below each bound, not cumulative until rendered.
+    bucket_counts: [u64; LATENCY_BUCKETS.len()],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, value: f64) {
+        if let Some(bucket) = LATENCY_BUCKETS.iter().position(|bound| value <= *bound) {
+            self.bucket_counts[bucket] += 1;
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+}
+
+// Counters about the queries we answer, shared by every thread serving
+// clients and rendered in the Prometheus text format on request.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    queries_by_type: Mutex<BTreeMap<String, u64>>,
+    responses_by_rcode: Mutex<BTreeMap<u16, u64>>,
+    cache_hits: AtomicU64,
+    cache_misses: AtomicU64,
+    decode_errors: AtomicU64,
+    dropped_queries: AtomicU64,
+    blocked_queries: AtomicU64,
+    upstream_latency: Mutex<BTreeMap<SocketAddr, Histogram>>,
+}
+
+impl Metrics {
+    pub fn new() -> Metrics {
+        Metrics::default()
+    }
+
+    pub fn record_query(&self, record_type: RecordType) {
+        *self
+            .queries_by_type
+            .lock()
+            .unwrap()
+            .entry(record_type.to_string())
+            .or_default() += 1;
+    }
+
+    pub fn record_response(&self, response_code: u16) {
+        *self
+            .responses_by_rcode
+            .lock()
+            .unwrap()
+            .entry(response_code)
+            .or_default() += 1;
+    }
+
+    pub fn record_cache_lookup(&self, hit: bool) {
+        match hit {
+            true => self.cache_hits.fetch_add(1, Ordering::Relaxed),
+            false => self.cache_misses.fetch_add(1, Ordering::Relaxed),
+        };
+    }
+
+    pub fn record_decode_error(&self) {
+        self.decode_errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_dropped_query(&self) {
+        self.dropped_queries.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_blocked_query(&self) {
+        self.blocked_queries.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn record_upstream_latency(&self, upstream: SocketAddr, latency: Duration) {
+        self.upstream_latency
+            .lock()
+            .unwrap()
+            .entry(upstream)
+            .or_default()
+            .observe(latency.as_secs_f64());
+    }
+
+    // Source: https://prometheus.io/docs/instrumenting/exposition_formats/#text-based-format
+    pub fn render(&self) -> String {
+        let mut text = String::new();
+        write_header(
+            &mut text,
+            "dns_queries_total",
+            "counter",
+            "Questions received by record type.",
+        );
+        for (record_type, count) in self.queries_by_type.lock().unwrap().iter() {
+            let _ = writeln!(
+                text,
+                "dns_queries_total{{type=\"{}\"}} {}",
+                record_type, count
+            );
+        }
+        write_header(
+            &mut text,
+            "dns_responses_total",
+            "counter",
+            "Responses sent by response code.",
+        );
+        for (response_code, count) in self.responses_by_rcode.lock().unwrap().iter() {
+            let _ = writeln!(
+                text,
+                "dns_responses_total{{rcode=\"{}\"}} {}",
+                ResponseCode::mnemonic(*response_code),
+                count
+            );
+        }
+        let cache_hits = self.cache_hits.load(Ordering::Relaxed);
+        let cache_misses = self.cache_misses.load(Ordering::Relaxed);
+        write_header(
+            &mut text,
+            "dns_cache_hits_total",
+            "counter",
+            "Questions answered from the cache.",
+        );
+        let _ = writeln!(text, "dns_cache_hits_total {}", cache_hits);
+        write_header(
+            &mut text,
+            "dns_cache_misses_total",
+            "counter",
+            "Questions the cache could not answer.",
+        );
+        let _ = writeln!(text, "dns_cache_misses_total {}", cache_misses);
+        write_header(
+            &mut text,
+            "dns_cache_hit_ratio",
+            "gauge",
+            "Share of cache lookups that were hits.",
+        );
+        let lookups = cache_hits + cache_misses;
+        let hit_ratio = match lookups {
+            0 => 0.0,
+            _ => cache_hits as f64 / lookups as f64,
+        };
+        let _ = writeln!(text, "dns_cache_hit_ratio {}", hit_ratio);
+        write_header(
+            &mut text,
+            "dns_decode_errors_total",
+            "counter",
+            "Requests that could not be decoded.",
+        );
+        let _ = writeln!(
+            text,
+            "dns_decode_errors_total {}",
+            self.decode_errors.load(Ordering::Relaxed)
+        );
+        write_header(
+            &mut text,
+            "dns_dropped_queries_total",
+            "counter",
+            "UDP queries dropped because every worker was busy.",
+        );
+        let _ = writeln!(
+            text,
+            "dns_dropped_queries_total {}",
+            self.dropped_queries.load(Ordering::Relaxed)
+        );
+        write_header(
+            &mut text,
+            "dns_blocked_queries_total",
+            "counter",
+            "Questions answered from the blocklist.",
+        );
+        let _ = writeln!(
+            text,
+            "dns_blocked_queries_total {}",
+            self.blocked_queries.load(Ordering::Relaxed)
+        );
+        write_header(
+            &mut text,
+            "dns_upstream_latency_seconds",
+            "histogram",
+            "Round trip time of successful upstream queries.",
+        );
+        for (upstream, histogram) in self.upstream_latency.lock().unwrap().iter() {
+            let mut cumulative = 0;
+            for (bound, count) in LATENCY_BUCKETS.iter().zip(histogram.bucket_counts) {
+                cumulative += count;
+                let _ = writeln!(
+                    text,
+                    "dns_upstream_latency_seconds_bucket{{upstream=\"{}\",le=\"{}\"}} {}",
+                    upstream, bound, cumulative
+                );
+            }
+            let _ = writeln!(
+                text,
+                "dns_upstream_latency_seconds_bucket{{upstream=\"{}\",le=\"+Inf\"}} {}",
+                upstream, histogram.count
+            );
+            let _ = writeln!(
+                text,
+                "dns_upstream_latency_seconds_sum{{upstream=\"{}\"}} {}",
+                upstream, histogram.sum
+            );
+            let _ = writeln!(
+                text,
+                "dns_upstream_latency_seconds_count{{upstream=\"{}\"}} {}",
+                upstream, histogram.count
+            );
+        }
+        text
+    }
+}
+
+fn write_header(text: &mut String, name: &str, metric_type: &str, help: &str) {
+    let _ = writeln!(text, "# HELP {} {}", name, help);
+    let _ = writeln!(text, "# TYPE {} {}", name, metric_type);
+}
@@ -1,15 +1,48 @@
+pub mod acl;
+pub mod blocklist;
+pub mod cache;
 pub mod class;
+pub mod compression;
 pub mod dns_answer;
+pub mod dns_error;
 pub mod dns_header;
 pub mod dns_packet;
 pub mod dns_question;
+pub mod dnssec;
+pub mod edns;
+pub mod hosts;
+pub mod journal;
 pub mod label;
+pub mod metrics;
+pub mod operation_code;
+pub mod presentation;
+pub mod rate_limit;
+pub mod rdata;
 pub mod record_type;
+pub mod response_code;
+pub mod secondary;
+pub mod tsig;
+pub mod update;
+pub mod zone;
 
+pub use acl::{Acl, Network};
+pub use blocklist::{BlockResponse, Blocklist};
+pub use cache::Cache;
 pub use class::Class;
+pub use compression::Compression;
 pub use dns_answer::DnsAnswer;
+pub use dns_error::DnsError;
 pub use dns_header::DnsHeader;
 pub use dns_packet::DnsPacket;
 pub use dns_question::DnsQuestion;
+pub use dnssec::{Validation, Validator};
+pub use edns::{Edns, EdnsOption};
+pub use hosts::Hosts;
 pub use label::Label;
+pub use operation_code::OperationCode;
+pub use rate_limit::{RateLimitAction, RateLimitConfig, RateLimitEvent, RateLimiter};
+pub use rdata::RData;
 pub use record_type::RecordType;
+pub use response_code::ResponseCode;
+pub use secondary::Secondary;
+pub use zone::{Authority, Zone, ZoneLookup};
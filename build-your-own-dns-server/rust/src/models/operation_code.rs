@@ -0,0 +1,8 @@
+// specification: https://www.rfc-editor.org/rfc/rfc1035#section-4.1.1
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OperationCode {
+    Query = 0,  // A standard query
+    Notify = 4, // A primary telling its secondaries that a zone changed (RFC 1996)
+    Update = 5, // A dynamic update of the records in a zone (RFC 2136)
+}
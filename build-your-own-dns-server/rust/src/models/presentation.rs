@@ -0,0 +1,563 @@
+use crate::models::label::{MAX_LABEL_LENGTH, MAX_NAME_LENGTH};
+use crate::models::{Class, Label, RData, RecordType};
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+// The text form of records shared by master files and tools such as dig.
+// specification: https://www.rfc-editor.org/rfc/rfc1035#section-5.1
+
+#[derive(Debug)]
+pub struct Token {
+    // As written, with any backslash escapes still in place.
+    pub text: String,
+    // Quoted tokens are never treated as names, directives or "@".
+    pub quoted: bool,
+}
+
+// One logical entry, records wrapped in parentheses over several lines are
+// joined into a single entry.
+#[derive(Debug)]
+pub struct Entry {
+    pub line_number: usize,
+    pub owner_omitted: bool,
+    pub tokens: Vec<Token>,
+}
+
+pub fn tokenize(contents: &str) -> Result<Vec<Entry>, String> {
+    let mut entries: Vec<Entry> = vec![];
+    let mut tokens: Vec<Token> = vec![];
+    let mut current = String::new();
+    let mut parentheses = 0;
+    let mut line_number = 1;
+    let mut entry_line_number = 1;
+    let mut owner_omitted = false;
+    let mut at_line_start = true;
+    let mut chars = contents.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if at_line_start && parentheses == 0 && tokens.is_empty() {
+            owner_omitted = c == ' ' || c == '\t';
+            entry_line_number = line_number;
+        }
+        at_line_start = false;
+        match c {
+            ';' => {
+                while chars.peek().is_some_and(|c| *c != '\n') {
+                    chars.next();
+                }
+            }
+            '"' => {
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => {
+                            text.push('\\');
+                            if let Some(escaped) = chars.next() {
+                                text.push(escaped);
+                            }
+                        }
+                        Some('\n') => {
+                            line_number += 1;
+                            text.push('\n');
+                        }
+                        Some(c) => text.push(c),
+                        None => {
+                            return Err(format!("line {}: unterminated quoted string", line_number))
+                        }
+                    }
+                }
+                tokens.push(Token { text, quoted: true });
+            }
+            '(' => parentheses += 1,
+            ')' => {
+                if parentheses == 0 {
+                    return Err(format!("line {}: unbalanced ')'", line_number));
+                }
+                parentheses -= 1;
+            }
+            '\\' => {
+                current.push(c);
+                if let Some(escaped) = chars.next() {
+                    current.push(escaped);
+                }
+            }
+            c if c.is_whitespace() => {}
+            c => current.push(c),
+        }
+        let ends_token = chars
+            .peek()
+            .is_none_or(|c| c.is_whitespace() || "();\"".contains(*c));
+        if !current.is_empty() && ends_token {
+            tokens.push(Token {
+                text: std::mem::take(&mut current),
+                quoted: false,
+            });
+        }
+        if c == '\n' {
+            line_number += 1;
+            at_line_start = true;
+            if parentheses == 0 && !tokens.is_empty() {
+                entries.push(Entry {
+                    line_number: entry_line_number,
+                    owner_omitted,
+                    tokens: std::mem::take(&mut tokens),
+                });
+            }
+        }
+    }
+    // Reported where the record with the parenthesis that is never closed
+    // starts, rather than at the end of the file.
+    if parentheses != 0 {
+        return Err(format!("line {}: unbalanced '('", entry_line_number));
+    }
+    if !tokens.is_empty() {
+        entries.push(Entry {
+            line_number: entry_line_number,
+            owner_omitted,
+            tokens,
+        });
+    }
+    Ok(entries)
+}
+
+// Resolves "\X" and "\DDD" escapes into the bytes they stand for.
+pub fn unescape(text: &str) -> Result<Vec<u8>, String> {
+    let mut bytes: Vec<u8> = vec![];
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buffer = [0; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+            continue;
+        }
+        let mut digits = String::new();
+        while digits.len() < 3 && chars.peek().is_some_and(|c| c.is_ascii_digit()) {
+            digits.push(chars.next().unwrap());
+        }
+        if digits.is_empty() {
+            let escaped = chars
+                .next()
+                .ok_or_else(|| format!("dangling escape in '{}'", text))?;
+            let mut buffer = [0; 4];
+            bytes.extend_from_slice(escaped.encode_utf8(&mut buffer).as_bytes());
+        } else if digits.len() == 3 {
+            let byte = digits
+                .parse::<u8>()
+                .map_err(|_| format!("escape '\\{}' is above 255", digits))?;
+            bytes.push(byte);
+        } else {
+            return Err(format!("escape '\\{}' needs three digits", digits));
+        }
+    }
+    Ok(bytes)
+}
+
+// Writes bytes with everything but printable ASCII as "\DDD", and the
+// characters in `special` escaped with a backslash.
+pub fn escape(bytes: &[u8], special: &[u8]) -> String {
+    let mut text = String::new();
+    for byte in bytes {
+        if special.contains(byte) {
+            text.push('\\');
+            text.push(*byte as char);
+        } else if byte.is_ascii_graphic() || *byte == b' ' {
+            text.push(*byte as char);
+        } else {
+            text.push_str(&format!("\\{:03}", byte));
+        }
+    }
+    text
+}
+
+// A name as written, either absolute with a trailing dot or relative to the
+// origin. Dots inside a label are escaped as "\.".
+pub fn parse_name(text: &str, origin: Option<&Vec<Label>>) -> Result<Vec<Label>, String> {
+    if text == "@" {
+        return origin
+            .cloned()
+            .ok_or_else(|| "'@' used without an origin".to_string());
+    }
+    let mut labels: Vec<Label> = vec![];
+    let mut is_absolute = false;
+    if text != "." {
+        let mut label = String::new();
+        let mut escaped = false;
+        for c in text.chars() {
+            if c == '.' && !escaped {
+                labels.push(parse_label(&label, text)?);
+                label.clear();
+                is_absolute = true;
+                continue;
+            }
+            is_absolute = false;
+            escaped = c == '\\' && !escaped;
+            label.push(c);
+        }
+        if !label.is_empty() {
+            labels.push(parse_label(&label, text)?);
+        }
+    } else {
+        is_absolute = true;
+    }
+    if Label::wire_length(&labels) > MAX_NAME_LENGTH {
+        return Err(format!("name '{}' is longer than 255 octets", text));
+    }
+    if !is_absolute {
+        let origin = origin.ok_or_else(|| format!("relative name '{}' without an origin", text))?;
+        labels.extend(origin.iter().cloned());
+    }
+    Ok(labels)
+}
+
+fn parse_label(text: &str, name: &str) -> Result<Label, String> {
+    if text.is_empty() {
+        return Err(format!("empty label in '{}'", name));
+    }
+    let bytes = unescape(text)?;
+    if bytes.len() > MAX_LABEL_LENGTH {
+        return Err(format!("label longer than 63 octets in '{}'", name));
+    }
+    Label::from_bytes(bytes)
+}
+
+// The absolute form of a name with every label escaped, "." for the root.
+pub fn format_name(labels: &[Label]) -> String {
+    if labels.is_empty() {
+        return ".".to_string();
+    }
+    labels.iter().map(|label| format!("{}.", label)).collect()
+}
+
+// Accepts plain seconds as well as the BIND style "1w2d3h4m5s" units.
+pub fn parse_ttl(text: &str) -> Option<u32> {
+    if text.chars().all(|c| c.is_ascii_digit()) {
+        return text.parse().ok();
+    }
+    if !text.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+    let mut total: u32 = 0;
+    let mut value = String::new();
+    for c in text.chars() {
+        if c.is_ascii_digit() {
+            value.push(c);
+            continue;
+        }
+        let multiplier = match c.to_ascii_lowercase() {
+            'w' => 604800,
+            'd' => 86400,
+            'h' => 3600,
+            'm' => 60,
+            's' => 1,
+            _ => return None,
+        };
+        let amount: u32 = std::mem::take(&mut value).parse().ok()?;
+        total = total.checked_add(amount.checked_mul(multiplier)?)?;
+    }
+    if !value.is_empty() {
+        total = total.checked_add(value.parse().ok()?)?;
+    }
+    Some(total)
+}
+
+// TTL and class are both optional and may appear in either order, starting
+// at `index`. Returns them with the index of the token after them.
+pub fn parse_ttl_and_class(
+    tokens: &[Token],
+    mut index: usize,
+) -> (Option<u32>, Option<Class>, usize) {
+    let mut ttl: Option<u32> = None;
+    let mut class: Option<Class> = None;
+    while let Some(token) = tokens.get(index) {
+        if ttl.is_none() && parse_ttl(&token.text).is_some() {
+            ttl = parse_ttl(&token.text);
+        } else if class.is_none() && Class::from_str(&token.text).is_ok() {
+            class = Class::from_str(&token.text).ok();
+        } else {
+            break;
+        }
+        index += 1;
+    }
+    (ttl, class, index)
+}
+
+pub fn parse_rdata(
+    record_type: RecordType,
+    tokens: &[Token],
+    origin: Option<&Vec<Label>>,
+) -> Result<RData, String> {
+    let text = |index: usize| -> Result<&str, String> {
+        tokens
+            .get(index)
+            .map(|token| token.text.as_str())
+            .ok_or_else(|| format!("missing field {}", index + 1))
+    };
+    let name = |index: usize| -> Result<Vec<Label>, String> { parse_name(text(index)?, origin) };
+    let number = |index: usize| -> Result<u32, String> {
+        parse_ttl(text(index)?).ok_or_else(|| format!("invalid number '{}'", text(index).unwrap()))
+    };
+    let short = |index: usize| -> Result<u16, String> {
+        text(index)?
+            .parse::<u16>()
+            .map_err(|_| format!("invalid number '{}'", text(index).unwrap()))
+    };
+    let byte = |index: usize| -> Result<u8, String> {
+        text(index)?
+            .parse::<u8>()
+            .map_err(|_| format!("invalid number '{}'", text(index).unwrap()))
+    };
+    let time = |index: usize| -> Result<u32, String> {
+        parse_time(text(index)?).ok_or_else(|| format!("invalid time '{}'", text(index).unwrap()))
+    };
+    // Keys, signatures and digests may be split over several tokens.
+    let joined = |index: usize| -> String {
+        tokens
+            .get(index..)
+            .unwrap_or_default()
+            .iter()
+            .map(|token| token.text.as_str())
+            .collect()
+    };
+    let base64 = |index: usize| -> Result<Vec<u8>, String> {
+        data_encoding::BASE64
+            .decode(joined(index).as_bytes())
+            .map_err(|_| format!("invalid base64 data '{}'", joined(index)))
+    };
+    let types = |index: usize| -> Result<Vec<u16>, String> {
+        tokens
+            .get(index..)
+            .unwrap_or_default()
+            .iter()
+            .map(|token| parse_type(&token.text))
+            .collect()
+    };
+    let salt = |index: usize| -> Result<Vec<u8>, String> {
+        match text(index)? {
+            "-" => Ok(vec![]),
+            salt => parse_hex(salt),
+        }
+    };
+    let address_error = |e: std::net::AddrParseError| e.to_string();
+
+    // Source: https://www.rfc-editor.org/rfc/rfc3597#section-5
+    if tokens.first().is_some_and(|token| token.text == "\\#") {
+        let length = text(1)?
+            .parse::<usize>()
+            .map_err(|_| format!("invalid length '{}'", text(1).unwrap()))?;
+        let hex: String = tokens[2..]
+            .iter()
+            .map(|token| token.text.as_str())
+            .collect();
+        let data = parse_hex(&hex)?;
+        if data.len() != length {
+            return Err(format!(
+                "{} bytes of data where {} were announced",
+                data.len(),
+                length
+            ));
+        }
+        return RData::parse(record_type, &data, &data).map_err(|e| e.to_string());
+    }
+    let rdata = match record_type {
+        RecordType::A => RData::A(text(0)?.parse::<Ipv4Addr>().map_err(address_error)?),
+        RecordType::AAAA => RData::AAAA(text(0)?.parse::<Ipv6Addr>().map_err(address_error)?),
+        RecordType::NS => RData::NS(name(0)?),
+        RecordType::CNAME => RData::CNAME(name(0)?),
+        RecordType::PTR => RData::PTR(name(0)?),
+        RecordType::MD => RData::MD(name(0)?),
+        RecordType::MF => RData::MF(name(0)?),
+        RecordType::MB => RData::MB(name(0)?),
+        RecordType::MG => RData::MG(name(0)?),
+        RecordType::MR => RData::MR(name(0)?),
+        RecordType::MINFO => RData::MINFO {
+            rmailbx: name(0)?,
+            emailbx: name(1)?,
+        },
+        RecordType::MX => RData::MX {
+            preference: short(0)?,
+            exchange: name(1)?,
+        },
+        RecordType::SOA => RData::SOA {
+            mname: name(0)?,
+            rname: name(1)?,
+            serial: text(2)?
+                .parse::<u32>()
+                .map_err(|_| format!("invalid serial '{}'", text(2).unwrap()))?,
+            refresh: number(3)?,
+            retry: number(4)?,
+            expire: number(5)?,
+            minimum: number(6)?,
+        },
+        RecordType::TXT => {
+            if tokens.is_empty() {
+                return Err("TXT record without strings".to_string());
+            }
+            let strings = tokens
+                .iter()
+                .map(|token| unescape(&token.text))
+                .collect::<Result<Vec<Vec<u8>>, String>>()?;
+            if strings.iter().any(|string| string.len() > 255) {
+                return Err("TXT string longer than 255 octets".to_string());
+            }
+            RData::TXT(strings)
+        }
+        RecordType::SRV => RData::SRV {
+            priority: short(0)?,
+            weight: short(1)?,
+            port: short(2)?,
+            target: name(3)?,
+        },
+        RecordType::DNSKEY => RData::DNSKEY {
+            flags: short(0)?,
+            protocol: byte(1)?,
+            algorithm: byte(2)?,
+            public_key: base64(3)?,
+        },
+        RecordType::RRSIG => RData::RRSIG {
+            type_covered: RecordType::from_str(text(0)?)?,
+            algorithm: byte(1)?,
+            labels: byte(2)?,
+            original_ttl: number(3)?,
+            expiration: time(4)?,
+            inception: time(5)?,
+            key_tag: short(6)?,
+            signer_name: name(7)?,
+            signature: base64(8)?,
+        },
+        RecordType::DS => RData::DS {
+            key_tag: short(0)?,
+            algorithm: byte(1)?,
+            digest_type: byte(2)?,
+            digest: parse_hex(&joined(3))?,
+        },
+        RecordType::NSEC => RData::NSEC {
+            next_domain_name: name(0)?,
+            types: types(1)?,
+        },
+        RecordType::NSEC3 => RData::NSEC3 {
+            hash_algorithm: byte(0)?,
+            flags: byte(1)?,
+            iterations: short(2)?,
+            salt: salt(3)?,
+            next_hashed_owner: data_encoding::BASE32HEX_NOPAD
+                .decode(text(4)?.to_ascii_uppercase().as_bytes())
+                .map_err(|_| format!("invalid base32hex data '{}'", text(4).unwrap()))?,
+            types: types(5)?,
+        },
+        RecordType::NSEC3PARAM => RData::NSEC3PARAM {
+            hash_algorithm: byte(0)?,
+            flags: byte(1)?,
+            iterations: short(2)?,
+            salt: salt(3)?,
+        },
+        _ => {
+            return Err(format!(
+                "record type {} is only supported in the \\# form",
+                record_type
+            ))
+        }
+    };
+    Ok(rdata)
+}
+
+// A type in a type bitmap, by its mnemonic or as "TYPE65534" for types we
+// have no name for.
+// Source: https://www.rfc-editor.org/rfc/rfc3597#section-5
+fn parse_type(text: &str) -> Result<u16, String> {
+    RecordType::from_str(text).map(|record_type| record_type.to_u16())
+}
+
+// The types of a type bitmap, each preceded by a space.
+pub fn format_type_bitmap(types: &[u16]) -> String {
+    types
+        .iter()
+        .map(|value| format!(" {}", RecordType::from_u16(*value)))
+        .collect()
+}
+
+// RRSIG times are written as YYYYMMDDHHmmSS in UTC, but plain seconds since
+// the epoch are accepted as well.
+// Source: https://www.rfc-editor.org/rfc/rfc4034#section-3.2
+pub fn parse_time(text: &str) -> Option<u32> {
+    if text.len() != 14 || !text.chars().all(|c| c.is_ascii_digit()) {
+        return text.parse().ok();
+    }
+    let field = |range: std::ops::Range<usize>| text[range].parse::<i64>().unwrap();
+    let (year, month, day) = (field(0..4), field(4..6), field(6..8));
+    let (hour, minute, second) = (field(8..10), field(10..12), field(12..14));
+    if !(1..=12).contains(&month)
+        || !(1..=31).contains(&day)
+        || hour > 23
+        || minute > 59
+        || second > 59
+    {
+        return None;
+    }
+    let seconds = days_from_civil(year, month, day) * 86400 + hour * 3600 + minute * 60 + second;
+    u32::try_from(seconds).ok()
+}
+
+pub fn format_time(seconds: u32) -> String {
+    let days = seconds as i64 / 86400;
+    let time = seconds as i64 % 86400;
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{:04}{:02}{:02}{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+// Days between the Unix epoch and a date of the proleptic Gregorian calendar.
+// Source: https://howardhinnant.github.io/date_algorithms.html#days_from_civil
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+// Source: https://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400;
+    (if month <= 2 { year + 1 } else { year }, month, day)
+}
+
+pub fn format_base64(data: &[u8]) -> String {
+    data_encoding::BASE64.encode(data)
+}
+
+pub fn format_hex(data: &[u8]) -> String {
+    data.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn parse_hex(hex: &str) -> Result<Vec<u8>, String> {
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return Err(format!("invalid hex data '{}'", hex));
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+        .collect::<Result<Vec<u8>, _>>()
+        .map_err(|_| format!("invalid hex data '{}'", hex))
+}
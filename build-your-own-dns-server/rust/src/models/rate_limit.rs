@@ -0,0 +1,282 @@
+use crate::models::{DnsPacket, Label, RData, RecordType, ResponseCode};
+use std::collections::HashMap;
+use std::fmt;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::Instant;
+
+// Limits on the responses sent to each client network, modelled on BIND's
+// rate-limit statement. Rates are responses per second, 0 meaning no limit.
+// Source: https://kb.isc.org/docs/aa-00994
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimitConfig {
+    pub responses_per_second: u32,
+    // NXDOMAIN and error responses default to the rate of other responses.
+    pub nxdomains_per_second: Option<u32>,
+    pub errors_per_second: Option<u32>,
+    // Seconds of excess a client has to stay under its rate to be forgiven.
+    pub window: u32,
+    // Every slip-th limited response goes out truncated instead of being
+    // dropped, so a real client behind a spoofed address can retry over TCP.
+    // 0 drops them all, 1 truncates them all.
+    pub slip: u32,
+    pub ipv4_prefix_length: u8,
+    pub ipv6_prefix_length: u8,
+}
+
+// What to do with a response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateLimitAction {
+    Send,
+    // Send it with TC set and no records.
+    Slip,
+    Drop,
+}
+
+impl fmt::Display for RateLimitAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RateLimitAction::Send => write!(f, "send"),
+            RateLimitAction::Slip => write!(f, "slip"),
+            RateLimitAction::Drop => write!(f, "drop"),
+        }
+    }
+}
+
+// A client network starting or ceasing to be limited. Only these are worth
+// logging, a line for every response dropped on the way would let an
+// attacker flood the log as well.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RateLimitEvent {
+    Started {
+        network: String,
+        responses: String,
+    },
+    Stopped {
+        network: String,
+        responses: String,
+        limited: u64,
+    },
+}
+
+impl fmt::Display for RateLimitEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RateLimitEvent::Started { network, responses } => {
+                write!(f, "Limiting {} to network {}", responses, network)
+            }
+            RateLimitEvent::Stopped {
+                network,
+                responses,
+                limited,
+            } => write!(
+                f,
+                "Stopped limiting {} to network {} after {} limited responses",
+                responses, network, limited
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum ResponseKind {
+    Answer,
+    NxDomain,
+    Error,
+}
+
+// Responses share a bucket when they go to the same client network and say
+// the same thing: the same answer for a name and type, NXDOMAIN within the
+// same zone, or any error at all.
+type BucketKey = (IpAddr, ResponseKind, String, Option<RecordType>);
+
+#[derive(Debug)]
+struct Bucket {
+    // Responses that may still be sent, negative while over the limit.
+    balance: f64,
+    updated_at: Instant,
+    // Responses limited since the bucket last let one through.
+    limited_count: u64,
+}
+
+#[derive(Debug)]
+pub struct RateLimiter {
+    config: RateLimitConfig,
+    buckets: Mutex<HashMap<BucketKey, Bucket>>,
+    cleaned_at: Mutex<Instant>,
+    // Networks that started or stopped being limited since the last call
+    // to take_events.
+    events: Mutex<Vec<RateLimitEvent>>,
+}
+
+impl RateLimiter {
+    pub fn new(config: RateLimitConfig) -> RateLimiter {
+        RateLimiter {
+            config,
+            buckets: Mutex::new(HashMap::new()),
+            cleaned_at: Mutex::new(Instant::now()),
+            events: Mutex::new(vec![]),
+        }
+    }
+
+    // Takes a token for the response from the client's bucket and says
+    // whether the response may go out.
+    pub fn check(&self, client: IpAddr, dns_response: &DnsPacket, now: Instant) -> RateLimitAction {
+        let (kind, name, record_type) = classify(dns_response);
+        let rate = match kind {
+            ResponseKind::Answer => self.config.responses_per_second,
+            ResponseKind::NxDomain => self
+                .config
+                .nxdomains_per_second
+                .unwrap_or(self.config.responses_per_second),
+            ResponseKind::Error => self
+                .config
+                .errors_per_second
+                .unwrap_or(self.config.responses_per_second),
+        } as f64;
+        if rate == 0.0 {
+            return RateLimitAction::Send;
+        }
+        self.clean(now);
+        let key = (self.network_address(client), kind, name, record_type);
+        let mut buckets = self.buckets.lock().unwrap();
+        let bucket = buckets.entry(key.clone()).or_insert(Bucket {
+            balance: rate,
+            updated_at: now,
+            limited_count: 0,
+        });
+        let elapsed = now.saturating_duration_since(bucket.updated_at);
+        bucket.balance = (bucket.balance + elapsed.as_secs_f64() * rate).min(rate) - 1.0;
+        // The debt is bounded so that a client is forgiven after the window.
+        bucket.balance = bucket.balance.max(-rate * self.config.window as f64);
+        bucket.updated_at = now;
+        if bucket.balance >= 0.0 {
+            if bucket.limited_count > 0 {
+                self.record_event(stopped(self.format_network(key.0), &key, bucket));
+            }
+            bucket.limited_count = 0;
+            return RateLimitAction::Send;
+        }
+        if bucket.limited_count == 0 {
+            self.record_event(RateLimitEvent::Started {
+                network: self.format_network(key.0),
+                responses: describe(&key),
+            });
+        }
+        bucket.limited_count += 1;
+        match self.config.slip != 0 && bucket.limited_count.is_multiple_of(self.config.slip as u64)
+        {
+            true => RateLimitAction::Slip,
+            false => RateLimitAction::Drop,
+        }
+    }
+
+    // The networks that started or stopped being limited since the last
+    // call, oldest first.
+    pub fn take_events(&self) -> Vec<RateLimitEvent> {
+        std::mem::take(&mut *self.events.lock().unwrap())
+    }
+
+    // The client's network in CIDR notation, as it appears in logs.
+    pub fn network(&self, client: IpAddr) -> String {
+        self.format_network(self.network_address(client))
+    }
+
+    fn format_network(&self, network_address: IpAddr) -> String {
+        let prefix_length = match network_address {
+            IpAddr::V4(_) => self.config.ipv4_prefix_length,
+            IpAddr::V6(_) => self.config.ipv6_prefix_length,
+        };
+        format!("{}/{}", network_address, prefix_length)
+    }
+
+    fn record_event(&self, event: RateLimitEvent) {
+        self.events.lock().unwrap().push(event);
+    }
+
+    // The client's network, by which clients share their buckets.
+    fn network_address(&self, client: IpAddr) -> IpAddr {
+        match client {
+            IpAddr::V4(address) => {
+                let mask = u32::MAX
+                    .checked_shl(32 - self.config.ipv4_prefix_length.min(32) as u32)
+                    .unwrap_or(0);
+                IpAddr::V4((u32::from(address) & mask).into())
+            }
+            IpAddr::V6(address) => {
+                let mask = u128::MAX
+                    .checked_shl(128 - self.config.ipv6_prefix_length.min(128) as u32)
+                    .unwrap_or(0);
+                IpAddr::V6((u128::from(address) & mask).into())
+            }
+        }
+    }
+
+    // Forgets the buckets that have been idle for a whole window, as they
+    // would be full again by now, which ends the limiting of those that were
+    // limited. Done at most once per window.
+    fn clean(&self, now: Instant) {
+        let window = self.config.window.max(1) as f64;
+        let mut cleaned_at = self.cleaned_at.lock().unwrap();
+        if now.saturating_duration_since(*cleaned_at).as_secs_f64() < window {
+            return;
+        }
+        *cleaned_at = now;
+        self.buckets.lock().unwrap().retain(|key, bucket| {
+            let idle = now
+                .saturating_duration_since(bucket.updated_at)
+                .as_secs_f64()
+                >= window;
+            if idle && bucket.limited_count > 0 {
+                self.record_event(stopped(self.format_network(key.0), key, bucket));
+            }
+            !idle
+        });
+    }
+}
+
+fn stopped(network: String, key: &BucketKey, bucket: &Bucket) -> RateLimitEvent {
+    RateLimitEvent::Stopped {
+        network,
+        responses: describe(key),
+        limited: bucket.limited_count,
+    }
+}
+
+// The responses a bucket counts, for the log.
+fn describe(key: &BucketKey) -> String {
+    match key {
+        (_, ResponseKind::Answer, name, Some(record_type)) => {
+            format!("answers for {} {}", name, record_type)
+        }
+        (_, ResponseKind::Answer, name, None) => format!("answers for {}", name),
+        (_, ResponseKind::NxDomain, zone, _) => format!("NXDOMAIN responses in {}", zone),
+        (_, ResponseKind::Error, _, _) => "error responses".to_string(),
+    }
+}
+
+fn classify(dns_response: &DnsPacket) -> (ResponseKind, String, Option<RecordType>) {
+    let dns_question = dns_response.dns_questions.first();
+    let qname = dns_question.map_or(String::new(), |dns_question| {
+        Label::to_key(&dns_question.labels)
+    });
+    match dns_response.response_code() {
+        0 => (
+            ResponseKind::Answer,
+            qname,
+            dns_question.map(|dns_question| dns_question.record_type),
+        ),
+        // Random names under a zone would each get a fresh bucket, so
+        // NXDOMAIN is counted per zone, known from the SOA in the authority
+        // section.
+        code if code == ResponseCode::NameError as u16 => {
+            let zone = dns_response
+                .dns_authorities
+                .iter()
+                .find(|record| matches!(record.rdata, RData::SOA { .. }))
+                .map_or(qname, |record| Label::to_key(&record.name));
+            (ResponseKind::NxDomain, zone, None)
+        }
+        _ => (ResponseKind::Error, String::new(), None),
+    }
+}
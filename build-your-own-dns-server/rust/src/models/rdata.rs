@@ -0,0 +1,767 @@
+use crate::models::presentation::{
+    escape, format_base64, format_hex, format_name, format_time, format_type_bitmap,
+};
+use crate::models::{tsig, Compression, DnsError, Label, RecordType};
+use crate::traits::{Decodable, Encodable};
+use nom::combinator::{map, rest};
+use nom::multi::{length_data, many0};
+use nom::number::complete::{be_u128, be_u16, be_u32, be_u8};
+use nom::sequence::tuple;
+use nom::IResult;
+use std::fmt;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+// Typed RDATA for the record types we know how to interpret, anything else
+// is carried as raw bytes so it survives being forwarded.
+// specification: https://www.rfc-editor.org/rfc/rfc1035#section-3.3
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RData {
+    A(Ipv4Addr),
+    // specification: https://www.rfc-editor.org/rfc/rfc3596#section-2.2
+    AAAA(Ipv6Addr),
+    NS(Vec<Label>),
+    CNAME(Vec<Label>),
+    SOA {
+        mname: Vec<Label>,
+        rname: Vec<Label>,
+        serial: u32,
+        refresh: u32,
+        retry: u32,
+        expire: u32,
+        minimum: u32,
+    },
+    PTR(Vec<Label>),
+    // Obsolete and experimental mail types, which nobody should send any
+    // more but whose names may still be compressed.
+    MD(Vec<Label>),
+    MF(Vec<Label>),
+    MB(Vec<Label>),
+    MG(Vec<Label>),
+    MR(Vec<Label>),
+    MINFO {
+        rmailbx: Vec<Label>,
+        emailbx: Vec<Label>,
+    },
+    MX {
+        preference: u16,
+        exchange: Vec<Label>,
+    },
+    // One or more <character-string>s, kept as bytes as they need not be UTF-8.
+    TXT(Vec<Vec<u8>>),
+    // specification: https://www.rfc-editor.org/rfc/rfc2782
+    SRV {
+        priority: u16,
+        weight: u16,
+        port: u16,
+        target: Vec<Label>,
+    },
+    // specification: https://www.rfc-editor.org/rfc/rfc4034#section-2.1
+    DNSKEY {
+        flags: u16,
+        protocol: u8,
+        algorithm: u8,
+        public_key: Vec<u8>,
+    },
+    // Times are seconds since the Unix epoch.
+    // specification: https://www.rfc-editor.org/rfc/rfc4034#section-3.1
+    RRSIG {
+        type_covered: RecordType,
+        algorithm: u8,
+        labels: u8,
+        original_ttl: u32,
+        expiration: u32,
+        inception: u32,
+        key_tag: u16,
+        signer_name: Vec<Label>,
+        signature: Vec<u8>,
+    },
+    // specification: https://www.rfc-editor.org/rfc/rfc4034#section-5.1
+    DS {
+        key_tag: u16,
+        algorithm: u8,
+        digest_type: u8,
+        digest: Vec<u8>,
+    },
+    // Type numbers rather than RecordTypes, as a zone may hold types we do
+    // not know.
+    // specification: https://www.rfc-editor.org/rfc/rfc4034#section-4.1
+    NSEC {
+        next_domain_name: Vec<Label>,
+        types: Vec<u16>,
+    },
+    // specification: https://www.rfc-editor.org/rfc/rfc5155#section-3.2
+    NSEC3 {
+        hash_algorithm: u8,
+        flags: u8,
+        iterations: u16,
+        salt: Vec<u8>,
+        next_hashed_owner: Vec<u8>,
+        types: Vec<u16>,
+    },
+    // specification: https://www.rfc-editor.org/rfc/rfc5155#section-4.2
+    NSEC3PARAM {
+        hash_algorithm: u8,
+        flags: u8,
+        iterations: u16,
+        salt: Vec<u8>,
+    },
+    // Time signed is a 48-bit count of seconds since the Unix epoch.
+    // specification: https://www.rfc-editor.org/rfc/rfc8945#section-4.2
+    TSIG {
+        algorithm: Vec<Label>,
+        time_signed: u64,
+        fudge: u16,
+        mac: Vec<u8>,
+        original_id: u16,
+        error: u16,
+        other_data: Vec<u8>,
+    },
+    Unknown(Vec<u8>),
+}
+
+impl RData {
+    // Parses RDATA of the given type, which has to fill `data` exactly. Names
+    // inside it may point anywhere in the message, so that is needed as well.
+    pub fn parse(record_type: RecordType, message: &[u8], data: &[u8]) -> Result<RData, DnsError> {
+        let name = |input| Label::parse_name(message, input);
+        let parsed: IResult<&[u8], RData, DnsError> = match record_type {
+            RecordType::A => map(be_u32, |address| RData::A(Ipv4Addr::from(address)))(data),
+            RecordType::AAAA => map(be_u128, |address| RData::AAAA(Ipv6Addr::from(address)))(data),
+            RecordType::NS => map(name, RData::NS)(data),
+            RecordType::CNAME => map(name, RData::CNAME)(data),
+            RecordType::PTR => map(name, RData::PTR)(data),
+            RecordType::MD => map(name, RData::MD)(data),
+            RecordType::MF => map(name, RData::MF)(data),
+            RecordType::MB => map(name, RData::MB)(data),
+            RecordType::MG => map(name, RData::MG)(data),
+            RecordType::MR => map(name, RData::MR)(data),
+            RecordType::MINFO => map(tuple((name, name)), |(rmailbx, emailbx)| RData::MINFO {
+                rmailbx,
+                emailbx,
+            })(data),
+            RecordType::SOA => map(
+                tuple((name, name, be_u32, be_u32, be_u32, be_u32, be_u32)),
+                |(mname, rname, serial, refresh, retry, expire, minimum)| RData::SOA {
+                    mname,
+                    rname,
+                    serial,
+                    refresh,
+                    retry,
+                    expire,
+                    minimum,
+                },
+            )(data),
+            RecordType::MX => map(tuple((be_u16, name)), |(preference, exchange)| RData::MX {
+                preference,
+                exchange,
+            })(data),
+            RecordType::TXT => map(many0(length_data(be_u8)), |strings: Vec<&[u8]>| {
+                RData::TXT(strings.into_iter().map(|string| string.to_vec()).collect())
+            })(data),
+            RecordType::SRV => map(
+                tuple((be_u16, be_u16, be_u16, name)),
+                |(priority, weight, port, target)| RData::SRV {
+                    priority,
+                    weight,
+                    port,
+                    target,
+                },
+            )(data),
+            RecordType::DNSKEY => map(
+                tuple((be_u16, be_u8, be_u8, rest)),
+                |(flags, protocol, algorithm, public_key): (u16, u8, u8, &[u8])| RData::DNSKEY {
+                    flags,
+                    protocol,
+                    algorithm,
+                    public_key: public_key.to_vec(),
+                },
+            )(data),
+            RecordType::RRSIG => {
+                RecordType::parse(message, data).and_then(|(input, type_covered)| {
+                    map(
+                        tuple((be_u8, be_u8, be_u32, be_u32, be_u32, be_u16, name, rest)),
+                        |(
+                            algorithm,
+                            labels,
+                            original_ttl,
+                            expiration,
+                            inception,
+                            key_tag,
+                            signer_name,
+                            signature,
+                        )| RData::RRSIG {
+                            type_covered,
+                            algorithm,
+                            labels,
+                            original_ttl,
+                            expiration,
+                            inception,
+                            key_tag,
+                            signer_name,
+                            signature: signature.to_vec(),
+                        },
+                    )(input)
+                })
+            }
+            RecordType::DS => map(
+                tuple((be_u16, be_u8, be_u8, rest)),
+                |(key_tag, algorithm, digest_type, digest): (u16, u8, u8, &[u8])| RData::DS {
+                    key_tag,
+                    algorithm,
+                    digest_type,
+                    digest: digest.to_vec(),
+                },
+            )(data),
+            RecordType::NSEC => map(
+                tuple((name, parse_type_bitmap)),
+                |(next_domain_name, types)| RData::NSEC {
+                    next_domain_name,
+                    types,
+                },
+            )(data),
+            RecordType::NSEC3 => map(
+                tuple((
+                    be_u8,
+                    be_u8,
+                    be_u16,
+                    length_data(be_u8),
+                    length_data(be_u8),
+                    parse_type_bitmap,
+                )),
+                |(hash_algorithm, flags, iterations, salt, next_hashed_owner, types)| {
+                    RData::NSEC3 {
+                        hash_algorithm,
+                        flags,
+                        iterations,
+                        salt: salt.to_vec(),
+                        next_hashed_owner: next_hashed_owner.to_vec(),
+                        types,
+                    }
+                },
+            )(data),
+            RecordType::NSEC3PARAM => map(
+                tuple((be_u8, be_u8, be_u16, length_data(be_u8))),
+                |(hash_algorithm, flags, iterations, salt): (u8, u8, u16, &[u8])| {
+                    RData::NSEC3PARAM {
+                        hash_algorithm,
+                        flags,
+                        iterations,
+                        salt: salt.to_vec(),
+                    }
+                },
+            )(data),
+            RecordType::TSIG => map(
+                tuple((
+                    name,
+                    // The time signed is a 48 bit number of seconds.
+                    map(tuple((be_u16, be_u32)), |(high, low)| {
+                        (high as u64) << 32 | low as u64
+                    }),
+                    be_u16,
+                    length_data(be_u16),
+                    be_u16,
+                    be_u16,
+                    length_data(be_u16),
+                )),
+                |(algorithm, time_signed, fudge, mac, original_id, error, other_data)| {
+                    RData::TSIG {
+                        algorithm,
+                        time_signed,
+                        fudge,
+                        mac: mac.to_vec(),
+                        original_id,
+                        error,
+                        other_data: other_data.to_vec(),
+                    }
+                },
+            )(data),
+            _ => Ok((&data[data.len()..], RData::Unknown(data.to_vec()))),
+        };
+        // Structured RDATA has to fill RDLENGTH exactly, running short or
+        // leaving bytes behind means the length or the data is corrupt.
+        match parsed {
+            Ok(([], rdata)) => Ok(rdata),
+            Err(nom::Err::Failure(e)) => Err(e),
+            _ => Err(DnsError::InvalidRData(format!(
+                "{:?} data does not match its length of {} bytes",
+                record_type,
+                data.len()
+            ))),
+        }
+    }
+
+    // Only the RFC 1035 types may have their names compressed, newer types
+    // such as SRV are always written in full.
+    // Source: https://www.rfc-editor.org/rfc/rfc3597#section-4
+    pub fn encode_into(&self, buffer: &mut Vec<u8>, compression: &mut Compression) {
+        match self {
+            RData::A(address) => buffer.extend(address.octets()),
+            RData::AAAA(address) => buffer.extend(address.octets()),
+            RData::NS(name)
+            | RData::CNAME(name)
+            | RData::PTR(name)
+            | RData::MD(name)
+            | RData::MF(name)
+            | RData::MB(name)
+            | RData::MG(name)
+            | RData::MR(name) => compression.encode_name(name, buffer),
+            RData::MINFO { rmailbx, emailbx } => {
+                compression.encode_name(rmailbx, buffer);
+                compression.encode_name(emailbx, buffer);
+            }
+            RData::SOA {
+                mname,
+                rname,
+                serial,
+                refresh,
+                retry,
+                expire,
+                minimum,
+            } => {
+                compression.encode_name(mname, buffer);
+                compression.encode_name(rname, buffer);
+                for value in [serial, refresh, retry, expire, minimum] {
+                    buffer.extend_from_slice(&value.to_be_bytes());
+                }
+            }
+            RData::MX {
+                preference,
+                exchange,
+            } => {
+                buffer.extend_from_slice(&preference.to_be_bytes());
+                compression.encode_name(exchange, buffer);
+            }
+            RData::TXT(strings) => {
+                for string in strings {
+                    buffer.push(string.len() as u8);
+                    buffer.extend_from_slice(string);
+                }
+            }
+            RData::SRV {
+                priority,
+                weight,
+                port,
+                target,
+            } => {
+                buffer.extend_from_slice(&priority.to_be_bytes());
+                buffer.extend_from_slice(&weight.to_be_bytes());
+                buffer.extend_from_slice(&port.to_be_bytes());
+                buffer.extend(Label::encode_name(target));
+            }
+            RData::DNSKEY {
+                flags,
+                protocol,
+                algorithm,
+                public_key,
+            } => {
+                buffer.extend_from_slice(&flags.to_be_bytes());
+                buffer.push(*protocol);
+                buffer.push(*algorithm);
+                buffer.extend_from_slice(public_key);
+            }
+            RData::RRSIG { signature, .. } => {
+                buffer.extend(self.encode_signed_fields(Label::encode_name));
+                buffer.extend_from_slice(signature);
+            }
+            RData::DS {
+                key_tag,
+                algorithm,
+                digest_type,
+                digest,
+            } => {
+                buffer.extend_from_slice(&key_tag.to_be_bytes());
+                buffer.push(*algorithm);
+                buffer.push(*digest_type);
+                buffer.extend_from_slice(digest);
+            }
+            RData::NSEC {
+                next_domain_name,
+                types,
+            } => {
+                buffer.extend(Label::encode_name(next_domain_name));
+                encode_type_bitmap(types, buffer);
+            }
+            RData::NSEC3 {
+                hash_algorithm,
+                flags,
+                iterations,
+                salt,
+                next_hashed_owner,
+                types,
+            } => {
+                buffer.push(*hash_algorithm);
+                buffer.push(*flags);
+                buffer.extend_from_slice(&iterations.to_be_bytes());
+                buffer.push(salt.len() as u8);
+                buffer.extend_from_slice(salt);
+                buffer.push(next_hashed_owner.len() as u8);
+                buffer.extend_from_slice(next_hashed_owner);
+                encode_type_bitmap(types, buffer);
+            }
+            RData::NSEC3PARAM {
+                hash_algorithm,
+                flags,
+                iterations,
+                salt,
+            } => {
+                buffer.push(*hash_algorithm);
+                buffer.push(*flags);
+                buffer.extend_from_slice(&iterations.to_be_bytes());
+                buffer.push(salt.len() as u8);
+                buffer.extend_from_slice(salt);
+            }
+            RData::TSIG {
+                algorithm,
+                time_signed,
+                fudge,
+                mac,
+                original_id,
+                error,
+                other_data,
+            } => {
+                buffer.extend(Label::encode_name(algorithm));
+                buffer.extend_from_slice(&time_signed.to_be_bytes()[2..]);
+                buffer.extend_from_slice(&fudge.to_be_bytes());
+                buffer.extend_from_slice(&(mac.len() as u16).to_be_bytes());
+                buffer.extend_from_slice(mac);
+                buffer.extend_from_slice(&original_id.to_be_bytes());
+                buffer.extend_from_slice(&error.to_be_bytes());
+                buffer.extend_from_slice(&(other_data.len() as u16).to_be_bytes());
+                buffer.extend_from_slice(other_data);
+            }
+            RData::Unknown(data) => buffer.extend_from_slice(data),
+        }
+    }
+
+    // The RDATA as it is signed, with names written in full and those of the
+    // types listed in RFC 4034 lowercased. NSEC is not among them any more.
+    // Source: https://www.rfc-editor.org/rfc/rfc4034#section-6.2
+    // Source: https://www.rfc-editor.org/rfc/rfc6840#section-5.1
+    pub fn encode_canonical(&self) -> Vec<u8> {
+        let name = Label::encode_canonical_name;
+        let mut buffer: Vec<u8> = vec![];
+        match self {
+            RData::NS(target)
+            | RData::CNAME(target)
+            | RData::PTR(target)
+            | RData::MD(target)
+            | RData::MF(target)
+            | RData::MB(target)
+            | RData::MG(target)
+            | RData::MR(target) => buffer.extend(name(target)),
+            RData::MINFO { rmailbx, emailbx } => {
+                buffer.extend(name(rmailbx));
+                buffer.extend(name(emailbx));
+            }
+            RData::SOA {
+                mname,
+                rname,
+                serial,
+                refresh,
+                retry,
+                expire,
+                minimum,
+            } => {
+                buffer.extend(name(mname));
+                buffer.extend(name(rname));
+                for value in [serial, refresh, retry, expire, minimum] {
+                    buffer.extend_from_slice(&value.to_be_bytes());
+                }
+            }
+            RData::MX {
+                preference,
+                exchange,
+            } => {
+                buffer.extend_from_slice(&preference.to_be_bytes());
+                buffer.extend(name(exchange));
+            }
+            RData::SRV {
+                priority,
+                weight,
+                port,
+                target,
+            } => {
+                buffer.extend_from_slice(&priority.to_be_bytes());
+                buffer.extend_from_slice(&weight.to_be_bytes());
+                buffer.extend_from_slice(&port.to_be_bytes());
+                buffer.extend(name(target));
+            }
+            RData::RRSIG { signature, .. } => {
+                buffer.extend(self.encode_signed_fields(name));
+                buffer.extend_from_slice(signature);
+            }
+            _ => self.encode_into(&mut buffer, &mut Compression::new(false)),
+        }
+        buffer
+    }
+
+    // Every RRSIG field but the signature, which is what the signature
+    // covers ahead of the RRset itself. Empty for other types.
+    // Source: https://www.rfc-editor.org/rfc/rfc4034#section-3.1.8.1
+    pub fn encode_signed_fields(&self, encode_name: fn(&[Label]) -> Vec<u8>) -> Vec<u8> {
+        let mut buffer: Vec<u8> = vec![];
+        if let RData::RRSIG {
+            type_covered,
+            algorithm,
+            labels,
+            original_ttl,
+            expiration,
+            inception,
+            key_tag,
+            signer_name,
+            ..
+        } = self
+        {
+            buffer.extend_from_slice(&type_covered.to_u16().to_be_bytes());
+            buffer.push(*algorithm);
+            buffer.push(*labels);
+            buffer.extend_from_slice(&original_ttl.to_be_bytes());
+            buffer.extend_from_slice(&expiration.to_be_bytes());
+            buffer.extend_from_slice(&inception.to_be_bytes());
+            buffer.extend_from_slice(&key_tag.to_be_bytes());
+            buffer.extend(encode_name(signer_name));
+        }
+        buffer
+    }
+}
+
+// The types present at a name as a list of windows of 256 types each, a
+// window only being written if it has any type in it and only as long as
+// its last non-zero octet.
+// Source: https://www.rfc-editor.org/rfc/rfc4034#section-4.1.2
+fn parse_type_bitmap(input: &[u8]) -> IResult<&[u8], Vec<u16>, DnsError> {
+    let (input, windows) = many0(tuple((be_u8, length_data(be_u8))))(input)?;
+    let mut types: Vec<u16> = vec![];
+    let mut last_window: Option<u8> = None;
+    for (window, bitmap) in windows {
+        if bitmap.is_empty() || bitmap.len() > 32 || last_window.is_some_and(|last| last >= window)
+        {
+            return Err(nom::Err::Failure(DnsError::InvalidRData(
+                "malformed type bitmap".to_string(),
+            )));
+        }
+        last_window = Some(window);
+        for (index, octet) in bitmap.iter().enumerate() {
+            for bit in 0..8 {
+                if octet & (0x80 >> bit) != 0 {
+                    types.push((window as u16) << 8 | (index * 8 + bit) as u16);
+                }
+            }
+        }
+    }
+    Ok((input, types))
+}
+
+fn encode_type_bitmap(types: &[u16], buffer: &mut Vec<u8>) {
+    let mut types = types.to_vec();
+    types.sort_unstable();
+    types.dedup();
+    let mut index = 0;
+    while index < types.len() {
+        let window = (types[index] >> 8) as u8;
+        let mut bitmap = [0u8; 32];
+        let mut length = 0;
+        while index < types.len() && (types[index] >> 8) as u8 == window {
+            let low = (types[index] & 0xff) as usize;
+            bitmap[low / 8] |= 0x80 >> (low % 8);
+            length = low / 8 + 1;
+            index += 1;
+        }
+        buffer.push(window);
+        buffer.push(length as u8);
+        buffer.extend_from_slice(&bitmap[..length]);
+    }
+}
+
+impl Encodable for RData {
+    fn encode(&self) -> Vec<u8> {
+        let mut buffer: Vec<u8> = vec![];
+        self.encode_into(&mut buffer, &mut Compression::new(false));
+        buffer
+    }
+}
+
+impl fmt::Display for RData {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RData::A(address) => write!(f, "{}", address),
+            RData::AAAA(address) => write!(f, "{}", address),
+            RData::NS(name)
+            | RData::CNAME(name)
+            | RData::PTR(name)
+            | RData::MD(name)
+            | RData::MF(name)
+            | RData::MB(name)
+            | RData::MG(name)
+            | RData::MR(name) => write!(f, "{}", format_name(name)),
+            RData::MINFO { rmailbx, emailbx } => {
+                write!(f, "{} {}", format_name(rmailbx), format_name(emailbx))
+            }
+            RData::SOA {
+                mname,
+                rname,
+                serial,
+                refresh,
+                retry,
+                expire,
+                minimum,
+            } => write!(
+                f,
+                "{} {} {} {} {} {} {}",
+                format_name(mname),
+                format_name(rname),
+                serial,
+                refresh,
+                retry,
+                expire,
+                minimum
+            ),
+            RData::MX {
+                preference,
+                exchange,
+            } => write!(f, "{} {}", preference, format_name(exchange)),
+            RData::TXT(strings) => {
+                let strings: Vec<String> = strings
+                    .iter()
+                    .map(|string| format!("\"{}\"", escape(string, b"\"\\")))
+                    .collect();
+                write!(f, "{}", strings.join(" "))
+            }
+            RData::SRV {
+                priority,
+                weight,
+                port,
+                target,
+            } => write!(
+                f,
+                "{} {} {} {}",
+                priority,
+                weight,
+                port,
+                format_name(target)
+            ),
+            RData::DNSKEY {
+                flags,
+                protocol,
+                algorithm,
+                public_key,
+            } => write!(
+                f,
+                "{} {} {} {}",
+                flags,
+                protocol,
+                algorithm,
+                format_base64(public_key)
+            ),
+            RData::RRSIG {
+                type_covered,
+                algorithm,
+                labels,
+                original_ttl,
+                expiration,
+                inception,
+                key_tag,
+                signer_name,
+                signature,
+            } => write!(
+                f,
+                "{} {} {} {} {} {} {} {} {}",
+                type_covered,
+                algorithm,
+                labels,
+                original_ttl,
+                format_time(*expiration),
+                format_time(*inception),
+                key_tag,
+                format_name(signer_name),
+                format_base64(signature)
+            ),
+            RData::DS {
+                key_tag,
+                algorithm,
+                digest_type,
+                digest,
+            } => write!(
+                f,
+                "{} {} {} {}",
+                key_tag,
+                algorithm,
+                digest_type,
+                format_hex(digest).to_uppercase()
+            ),
+            RData::NSEC {
+                next_domain_name,
+                types,
+            } => write!(
+                f,
+                "{}{}",
+                format_name(next_domain_name),
+                format_type_bitmap(types)
+            ),
+            RData::NSEC3 {
+                hash_algorithm,
+                flags,
+                iterations,
+                salt,
+                next_hashed_owner,
+                types,
+            } => write!(
+                f,
+                "{} {} {} {} {}{}",
+                hash_algorithm,
+                flags,
+                iterations,
+                format_salt(salt),
+                data_encoding::BASE32HEX_NOPAD.encode(next_hashed_owner),
+                format_type_bitmap(types)
+            ),
+            RData::NSEC3PARAM {
+                hash_algorithm,
+                flags,
+                iterations,
+                salt,
+            } => write!(
+                f,
+                "{} {} {} {}",
+                hash_algorithm,
+                flags,
+                iterations,
+                format_salt(salt)
+            ),
+            // As dig shows it, TSIG having no master file form.
+            RData::TSIG {
+                algorithm,
+                time_signed,
+                fudge,
+                mac,
+                original_id,
+                error,
+                other_data,
+            } => write!(
+                f,
+                "{} {} {} {} {} {} {} {}",
+                format_name(algorithm),
+                time_signed,
+                fudge,
+                mac.len(),
+                format_base64(mac),
+                original_id,
+                tsig::error_mnemonic(*error),
+                other_data.len()
+            ),
+            // Source: https://www.rfc-editor.org/rfc/rfc3597#section-5
+            RData::Unknown(data) if data.is_empty() => write!(f, "\\# 0"),
+            RData::Unknown(data) => write!(f, "\\# {} {}", data.len(), format_hex(data)),
+        }
+    }
+}
+
+// An empty salt is written as a single "-".
+// Source: https://www.rfc-editor.org/rfc/rfc5155#section-3.3
+fn format_salt(salt: &[u8]) -> String {
+    match salt.is_empty() {
+        true => "-".to_string(),
+        false => format_hex(salt).to_uppercase(),
+    }
+}
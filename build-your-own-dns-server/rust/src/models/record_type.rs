@@ -1,49 +1,204 @@
+use crate::models::DnsError;
 use crate::traits::Decodable;
+use nom::number::complete::be_u16;
+use nom::IResult;
+use std::fmt;
+use std::str::FromStr;
 
 // specification: https://www.rfc-editor.org/rfc/rfc1035#section-3.2.2
-#[allow(dead_code)]
-#[derive(Debug, Clone)]
+#[allow(dead_code, clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u16)]
 pub enum RecordType {
-    A = 1,      // a host address
-    NS = 2,     // an authoritative name server
-    MD = 3,     // a mail destination (Obsolete - use MX)
-    MF = 4,     // a mail forwarder (Obsolete - use MX)
-    CNAME = 5,  // the canonical name for an alias
-    SOA = 6,    // marks the start of a zone of authority
-    MB = 7,     // a mailbox domain name (EXPERIMENTAL)
-    MG = 8,     // a mail group member (EXPERIMENTAL)
-    MR = 9,     // a mail rename domain name (EXPERIMENTAL)
-    NULL = 10,  // a null RR (EXPERIMENTAL)
-    WKS = 11,   // a well known service description
-    PTR = 12,   // a domain name pointer
-    HINFO = 13, // host information
-    MINFO = 14, // mailbox or mail list information
-    MX = 15,    // mail exchange
-    TXT = 16,   // text strings}
+    A = 1,           // a host address
+    NS = 2,          // an authoritative name server
+    MD = 3,          // a mail destination (Obsolete - use MX)
+    MF = 4,          // a mail forwarder (Obsolete - use MX)
+    CNAME = 5,       // the canonical name for an alias
+    SOA = 6,         // marks the start of a zone of authority
+    MB = 7,          // a mailbox domain name (EXPERIMENTAL)
+    MG = 8,          // a mail group member (EXPERIMENTAL)
+    MR = 9,          // a mail rename domain name (EXPERIMENTAL)
+    NULL = 10,       // a null RR (EXPERIMENTAL)
+    WKS = 11,        // a well known service description
+    PTR = 12,        // a domain name pointer
+    HINFO = 13,      // host information
+    MINFO = 14,      // mailbox or mail list information
+    MX = 15,         // mail exchange
+    TXT = 16,        // text strings
+    AAAA = 28,       // an IPv6 host address (RFC 3596)
+    SRV = 33,        // a service location (RFC 2782)
+    DS = 43,         // a delegation signer (RFC 4034)
+    RRSIG = 46,      // a signature over an RRset (RFC 4034)
+    NSEC = 47,       // the next name in the zone and its types (RFC 4034)
+    DNSKEY = 48,     // a public key of the zone (RFC 4034)
+    NSEC3 = 50,      // hashed authenticated denial of existence (RFC 5155)
+    NSEC3PARAM = 51, // the NSEC3 parameters of the zone (RFC 5155)
+    // Types only found in questions, and TSIG which signs a message rather
+    // than holding data.
+    TSIG = 250, // a transaction signature (RFC 8945)
+    IXFR = 251, // an incremental transfer of a zone (RFC 1995)
+    AXFR = 252, // a transfer of an entire zone (RFC 5936)
+    ANY = 255,  // all records at a name (RFC 1035 calls it *)
+    // Any other type, whose data is carried along without being understood.
+    // Source: https://www.rfc-editor.org/rfc/rfc3597#section-2
+    Unknown(u16),
+}
+
+impl RecordType {
+    pub fn from_u16(value: u16) -> RecordType {
+        match value {
+            1 => RecordType::A,
+            2 => RecordType::NS,
+            3 => RecordType::MD,
+            4 => RecordType::MF,
+            5 => RecordType::CNAME,
+            6 => RecordType::SOA,
+            7 => RecordType::MB,
+            8 => RecordType::MG,
+            9 => RecordType::MR,
+            10 => RecordType::NULL,
+            11 => RecordType::WKS,
+            12 => RecordType::PTR,
+            13 => RecordType::HINFO,
+            14 => RecordType::MINFO,
+            15 => RecordType::MX,
+            16 => RecordType::TXT,
+            28 => RecordType::AAAA,
+            33 => RecordType::SRV,
+            43 => RecordType::DS,
+            46 => RecordType::RRSIG,
+            47 => RecordType::NSEC,
+            48 => RecordType::DNSKEY,
+            50 => RecordType::NSEC3,
+            51 => RecordType::NSEC3PARAM,
+            250 => RecordType::TSIG,
+            251 => RecordType::IXFR,
+            252 => RecordType::AXFR,
+            255 => RecordType::ANY,
+            _ => RecordType::Unknown(value),
+        }
+    }
+
+    pub fn to_u16(&self) -> u16 {
+        match self {
+            RecordType::A => 1,
+            RecordType::NS => 2,
+            RecordType::MD => 3,
+            RecordType::MF => 4,
+            RecordType::CNAME => 5,
+            RecordType::SOA => 6,
+            RecordType::MB => 7,
+            RecordType::MG => 8,
+            RecordType::MR => 9,
+            RecordType::NULL => 10,
+            RecordType::WKS => 11,
+            RecordType::PTR => 12,
+            RecordType::HINFO => 13,
+            RecordType::MINFO => 14,
+            RecordType::MX => 15,
+            RecordType::TXT => 16,
+            RecordType::AAAA => 28,
+            RecordType::SRV => 33,
+            RecordType::DS => 43,
+            RecordType::RRSIG => 46,
+            RecordType::NSEC => 47,
+            RecordType::DNSKEY => 48,
+            RecordType::NSEC3 => 50,
+            RecordType::NSEC3PARAM => 51,
+            RecordType::TSIG => 250,
+            RecordType::IXFR => 251,
+            RecordType::AXFR => 252,
+            RecordType::ANY => 255,
+            RecordType::Unknown(value) => *value,
+        }
+    }
+
+    // Types that stand for something other than a set of records, and so
+    // can never be added to a zone.
+    pub fn is_meta(&self) -> bool {
+        matches!(
+            self,
+            RecordType::TSIG | RecordType::IXFR | RecordType::AXFR | RecordType::ANY
+        )
+    }
+
+    pub fn is_zone_transfer(&self) -> bool {
+        matches!(self, RecordType::AXFR | RecordType::IXFR)
+    }
+
+    // Records that only exist to secure other records, left out of replies
+    // to clients that did not ask for them with the DO bit.
+    // Source: https://www.rfc-editor.org/rfc/rfc4035#section-3.2.1
+    pub fn is_dnssec(&self) -> bool {
+        matches!(
+            self,
+            RecordType::RRSIG | RecordType::NSEC | RecordType::NSEC3
+        )
+    }
 }
 
 impl Decodable for RecordType {
-    fn decode(buffer: Vec<u8>) -> Result<RecordType, String> {
-        let u16_value = ((buffer[0] as u16) << 8) | (buffer[1] as u16);
-        match u16_value {
-            1 => Ok(RecordType::A),
-            2 => Ok(RecordType::NS),
-            5 => Ok(RecordType::CNAME),
-            6 => Ok(RecordType::SOA),
-            7 => Ok(RecordType::MB),
-            8 => Ok(RecordType::MG),
-            9 => Ok(RecordType::MR),
-            10 => Ok(RecordType::NULL),
-            11 => Ok(RecordType::WKS),
-            12 => Ok(RecordType::PTR),
-            13 => Ok(RecordType::HINFO),
-            14 => Ok(RecordType::HINFO),
-            15 => Ok(RecordType::MX),
-            16 => Ok(RecordType::TXT),
-            _ => Err("Could not decode RecordType from invalid value {:u16_value}".to_string()),
+    fn parse<'a>(_message: &'a [u8], input: &'a [u8]) -> IResult<&'a [u8], RecordType, DnsError> {
+        let (input, value) = be_u16(input)?;
+        Ok((input, RecordType::from_u16(value)))
+    }
+}
+
+// Record type mnemonics as they appear in master files, and "TYPE65" for
+// any type by its number.
+// specification: https://www.rfc-editor.org/rfc/rfc1035#section-5.1
+// Source: https://www.rfc-editor.org/rfc/rfc3597#section-5
+impl FromStr for RecordType {
+    type Err = String;
+
+    fn from_str(mnemonic: &str) -> Result<RecordType, String> {
+        let uppercase = mnemonic.to_ascii_uppercase();
+        if let Some(value) = uppercase.strip_prefix("TYPE") {
+            return value
+                .parse::<u16>()
+                .map(RecordType::from_u16)
+                .map_err(|_| format!("Unknown record type '{}'", mnemonic));
+        }
+        match uppercase.as_str() {
+            "A" => Ok(RecordType::A),
+            "NS" => Ok(RecordType::NS),
+            "MD" => Ok(RecordType::MD),
+            "MF" => Ok(RecordType::MF),
+            "CNAME" => Ok(RecordType::CNAME),
+            "SOA" => Ok(RecordType::SOA),
+            "MB" => Ok(RecordType::MB),
+            "MG" => Ok(RecordType::MG),
+            "MR" => Ok(RecordType::MR),
+            "NULL" => Ok(RecordType::NULL),
+            "WKS" => Ok(RecordType::WKS),
+            "PTR" => Ok(RecordType::PTR),
+            "HINFO" => Ok(RecordType::HINFO),
+            "MINFO" => Ok(RecordType::MINFO),
+            "MX" => Ok(RecordType::MX),
+            "TXT" => Ok(RecordType::TXT),
+            "AAAA" => Ok(RecordType::AAAA),
+            "SRV" => Ok(RecordType::SRV),
+            "DS" => Ok(RecordType::DS),
+            "RRSIG" => Ok(RecordType::RRSIG),
+            "NSEC" => Ok(RecordType::NSEC),
+            "DNSKEY" => Ok(RecordType::DNSKEY),
+            "NSEC3" => Ok(RecordType::NSEC3),
+            "NSEC3PARAM" => Ok(RecordType::NSEC3PARAM),
+            "TSIG" => Ok(RecordType::TSIG),
+            "IXFR" => Ok(RecordType::IXFR),
+            "AXFR" => Ok(RecordType::AXFR),
+            "ANY" => Ok(RecordType::ANY),
+            _ => Err(format!("Unknown record type '{}'", mnemonic)),
         }
     }
-    fn decode_with_cursor(buffer: Vec<u8>, cursor: &mut usize) -> Result<Self, String> {
-        Self::decode(buffer[*cursor..].to_vec())
+}
+
+impl fmt::Display for RecordType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RecordType::Unknown(value) => write!(f, "TYPE{}", value),
+            _ => write!(f, "{:?}", self),
+        }
     }
 }
@@ -0,0 +1,38 @@
+// specification: https://www.rfc-editor.org/rfc/rfc1035#section-4.1.1
+#[allow(dead_code)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResponseCode {
+    NoError = 0,        // No error condition
+    FormatError = 1,    // The name server was unable to interpret the query
+    ServerFailure = 2,  // The name server was unable to process this query
+    NameError = 3,      // The domain name referenced in the query does not exist (NXDOMAIN)
+    NotImplemented = 4, // The name server does not support the requested kind of query
+    Refused = 5,        // The name server refuses to perform the specified operation
+    YxDomain = 6,       // A name exists that should not (RFC 2136)
+    YxRrSet = 7,        // An RRset exists that should not (RFC 2136)
+    NxRrSet = 8,        // An RRset that should exist does not (RFC 2136)
+    NotAuth = 9,        // The name server is not authoritative for the zone (RFC 2136)
+    NotZone = 10,       // A name is not within the zone of the update (RFC 2136)
+    BadVersion = 16,    // The requested EDNS version is not implemented (RFC 6891)
+}
+
+impl ResponseCode {
+    // Source: https://www.iana.org/assignments/dns-parameters/dns-parameters.xhtml#dns-parameters-6
+    pub fn mnemonic(response_code: u16) -> String {
+        match response_code {
+            0 => "NOERROR".to_string(),
+            1 => "FORMERR".to_string(),
+            2 => "SERVFAIL".to_string(),
+            3 => "NXDOMAIN".to_string(),
+            4 => "NOTIMP".to_string(),
+            5 => "REFUSED".to_string(),
+            6 => "YXDOMAIN".to_string(),
+            7 => "YXRRSET".to_string(),
+            8 => "NXRRSET".to_string(),
+            9 => "NOTAUTH".to_string(),
+            10 => "NOTZONE".to_string(),
+            16 => "BADVERS".to_string(),
+            _ => response_code.to_string(),
+        }
+    }
+}
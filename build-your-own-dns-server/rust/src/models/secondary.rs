@@ -0,0 +1,90 @@
+use crate::models::{DnsAnswer, Label, RData};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+// How long to wait before trying again to load a zone we never had, whose
+// SOA timers we do not know yet.
+const INITIAL_RETRY: Duration = Duration::from_secs(60);
+
+// When the SOA of the zone is next checked at its primaries, and until when
+// our copy may be served without a successful check.
+#[derive(Debug)]
+struct Timers {
+    next_check: Instant,
+    retry: Duration,
+    expires_at: Option<Instant>,
+}
+
+// A zone we keep a copy of from its primaries. Its serial is checked every
+// SOA REFRESH seconds, every RETRY seconds after a check failed, and the
+// copy is no longer served once checks have failed for EXPIRE seconds. A
+// NOTIFY from a primary has it checked right away.
+// Source: https://www.rfc-editor.org/rfc/rfc1034#section-4.3.5
+// Source: https://www.rfc-editor.org/rfc/rfc1996#section-4
+#[derive(Debug)]
+pub struct Secondary {
+    pub origin: Vec<Label>,
+    pub primaries: Vec<SocketAddr>,
+    timers: Mutex<Timers>,
+}
+
+impl Secondary {
+    pub fn new(origin: Vec<Label>, primaries: Vec<SocketAddr>, now: Instant) -> Secondary {
+        Secondary {
+            origin,
+            primaries,
+            timers: Mutex::new(Timers {
+                next_check: now,
+                retry: INITIAL_RETRY,
+                expires_at: None,
+            }),
+        }
+    }
+
+    pub fn is_due(&self, now: Instant) -> bool {
+        self.timers.lock().unwrap().next_check <= now
+    }
+
+    // Whether a copy of the zone was loaded and has not expired since.
+    pub fn is_serving(&self, now: Instant) -> bool {
+        self.timers
+            .lock()
+            .unwrap()
+            .expires_at
+            .is_some_and(|expires_at| now < expires_at)
+    }
+
+    pub fn is_primary(&self, address: IpAddr) -> bool {
+        self.primaries
+            .iter()
+            .any(|primary| primary.ip().to_canonical() == address.to_canonical())
+    }
+
+    pub fn notify(&self, now: Instant) {
+        self.timers.lock().unwrap().next_check = now;
+    }
+
+    // Restarts the timers from the SOA the primary has, after either finding
+    // our copy up to date or transferring a newer one.
+    pub fn refreshed(&self, soa: &DnsAnswer, now: Instant) {
+        let (refresh, retry, expire) = match soa.rdata {
+            RData::SOA {
+                refresh,
+                retry,
+                expire,
+                ..
+            } => (refresh, retry, expire),
+            _ => return,
+        };
+        let mut timers = self.timers.lock().unwrap();
+        timers.next_check = now + Duration::from_secs(refresh as u64);
+        timers.retry = Duration::from_secs(retry as u64);
+        timers.expires_at = Some(now + Duration::from_secs(expire as u64));
+    }
+
+    pub fn failed(&self, now: Instant) {
+        let mut timers = self.timers.lock().unwrap();
+        timers.next_check = now + timers.retry;
+    }
+}
@@ -0,0 +1,325 @@
+use crate::models::{
+    Class, DnsAnswer, DnsError, DnsHeader, DnsQuestion, Edns, Label, RData, RecordType,
+    ResponseCode,
+};
+use crate::traits::{Decodable, Encodable};
+use ring::hmac;
+use std::str::FromStr;
+
+// Transaction signatures: a message is signed with a secret shared by client
+// and server, an HMAC over the message and the fields of the TSIG record
+// appended to it. The response is signed in turn, over the MAC of the
+// request as well.
+// specification: https://www.rfc-editor.org/rfc/rfc8945
+
+// TSIG errors, given in the TSIG record of a NOTAUTH response.
+// Source: https://www.rfc-editor.org/rfc/rfc8945#section-3
+pub const BAD_SIG: u16 = 16;
+pub const BAD_KEY: u16 = 17;
+pub const BAD_TIME: u16 = 18;
+
+// How far the time a message was signed at may be from our clock, the
+// value the specification recommends.
+// Source: https://www.rfc-editor.org/rfc/rfc8945#section-10
+const FUDGE: u16 = 300;
+
+// The only algorithm we accept, and the one every implementation has to.
+// Source: https://www.rfc-editor.org/rfc/rfc8945#section-6
+const HMAC_SHA256: &str = "hmac-sha256";
+
+pub fn error_mnemonic(error: u16) -> String {
+    match error {
+        BAD_SIG => "BADSIG".to_string(),
+        BAD_KEY => "BADKEY".to_string(),
+        BAD_TIME => "BADTIME".to_string(),
+        _ => ResponseCode::mnemonic(error),
+    }
+}
+
+// A shared HMAC-SHA256 secret and the name both sides know it by.
+#[derive(Debug, Clone)]
+pub struct Key {
+    pub name: Vec<Label>,
+    secret: Vec<u8>,
+}
+
+impl Key {
+    pub fn new(name: Vec<Label>, secret: Vec<u8>) -> Key {
+        Key { name, secret }
+    }
+
+    fn hmac_key(&self) -> hmac::Key {
+        hmac::Key::new(hmac::HMAC_SHA256, &self.secret)
+    }
+}
+
+// Parses "[hmac-sha256:]name:secret" with the secret in base64, the form
+// dig -y and nsupdate -y take.
+impl FromStr for Key {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Key, String> {
+        let fields: Vec<&str> = text.split(':').collect();
+        let (name, secret) = match fields.as_slice() {
+            [algorithm, name, secret] if algorithm.eq_ignore_ascii_case(HMAC_SHA256) => {
+                (name, secret)
+            }
+            [algorithm, _, _] => return Err(format!("Unsupported algorithm '{}'", algorithm)),
+            [name, secret] => (name, secret),
+            _ => {
+                return Err(format!(
+                    "Expected [hmac-sha256:]NAME:SECRET, got '{}'",
+                    text
+                ))
+            }
+        };
+        let secret = data_encoding::BASE64
+            .decode(secret.as_bytes())
+            .map_err(|_| format!("Invalid base64 secret for key '{}'", name))?;
+        let name = Label::from_domain_name(name)
+            .map_err(|e| format!("Invalid name for key '{}': {}", name, e))?;
+        Ok(Key::new(name, secret))
+    }
+}
+
+// What checking the TSIG of a request found.
+#[derive(Debug)]
+pub enum Verification {
+    Unsigned,
+    // Signed with one of our keys, the response gets signed with it too.
+    Verified(Signer),
+    // To be answered with NOTAUTH and the TSIG error of the signer.
+    Failed(Signer),
+}
+
+// Signs the response to a request that came with a TSIG. Responses to
+// requests with an unknown key or a wrong MAC carry the error alone, as
+// there is nothing they could be signed with that the client would trust.
+// Source: https://www.rfc-editor.org/rfc/rfc8945#section-5.3
+#[derive(Debug, Clone)]
+pub struct Signer {
+    key_name: Vec<Label>,
+    algorithm: Vec<Label>,
+    key: Option<Key>,
+    // None when signing a request, which has no MAC before it to chain to.
+    request_mac: Option<Vec<u8>>,
+    pub error: u16,
+    // A BADTIME response repeats the time of the request.
+    time_signed: Option<u64>,
+}
+
+impl Signer {
+    fn unsigned(record: &DnsAnswer, algorithm: &[Label], error: u16) -> Signer {
+        Signer {
+            key_name: record.name.clone(),
+            algorithm: algorithm.to_vec(),
+            key: None,
+            request_mac: None,
+            error,
+            time_signed: None,
+        }
+    }
+
+    // Appends the TSIG record to an encoded response.
+    pub fn sign(&self, message: &[u8], now: u64) -> Vec<u8> {
+        let time_signed = self.time_signed.unwrap_or(now);
+        // BADTIME tells the client our time.
+        let other_data = match self.error {
+            BAD_TIME => now.to_be_bytes()[2..].to_vec(),
+            _ => vec![],
+        };
+        let mac = match self.key.as_ref() {
+            Some(key) => {
+                let mut context = hmac::Context::with_key(&key.hmac_key());
+                if let Some(request_mac) = self.request_mac.as_ref() {
+                    context.update(&(request_mac.len() as u16).to_be_bytes());
+                    context.update(request_mac);
+                }
+                context.update(message);
+                context.update(&variables(
+                    &self.key_name,
+                    &self.algorithm,
+                    time_signed,
+                    FUDGE,
+                    self.error,
+                    &other_data,
+                ));
+                context.sign().as_ref().to_vec()
+            }
+            None => vec![],
+        };
+        let record = DnsAnswer::new(
+            self.key_name.clone(),
+            RecordType::TSIG,
+            Class::ANY,
+            0,
+            RData::TSIG {
+                algorithm: self.algorithm.clone(),
+                time_signed,
+                fudge: FUDGE,
+                mac,
+                original_id: u16::from_be_bytes([message[0], message[1]]),
+                error: self.error,
+                other_data,
+            },
+        );
+        let mut signed = message.to_vec();
+        add_to_additional_count(&mut signed, 1);
+        signed.extend(record.encode());
+        signed
+    }
+}
+
+// Signs a request we send, such as an update, with the key.
+pub fn sign_request(message: &[u8], key: &Key, now: u64) -> Vec<u8> {
+    let signer = Signer {
+        key_name: key.name.clone(),
+        algorithm: Label::from_domain_name(HMAC_SHA256).expect("algorithm name is valid"),
+        key: Some(key.clone()),
+        request_mac: None,
+        error: 0,
+        time_signed: None,
+    };
+    signer.sign(message, now)
+}
+
+// Checks the TSIG of a request, which has to be its last record. Keys are
+// looked up first, then the MAC is checked and only then the time, so that
+// only a client holding the key learns our clock.
+// Source: https://www.rfc-editor.org/rfc/rfc8945#section-5.2
+pub fn verify(message: &[u8], keys: &[Key], now: u64) -> Result<Verification, DnsError> {
+    let (offset, record) = match find_tsig(message)? {
+        Some(tsig) => tsig,
+        None => return Ok(Verification::Unsigned),
+    };
+    let (algorithm, time_signed, fudge, mac, original_id, error, other_data) = match &record.rdata {
+        RData::TSIG {
+            algorithm,
+            time_signed,
+            fudge,
+            mac,
+            original_id,
+            error,
+            other_data,
+        } => (
+            algorithm,
+            *time_signed,
+            *fudge,
+            mac,
+            *original_id,
+            *error,
+            other_data,
+        ),
+        _ => {
+            return Err(DnsError::InvalidRData(
+                "TSIG without its fields".to_string(),
+            ))
+        }
+    };
+    let key = keys.iter().find(|key| {
+        Label::to_key(&key.name) == Label::to_key(&record.name)
+            && Label::to_key(algorithm) == HMAC_SHA256
+    });
+    let key = match key {
+        Some(key) => key,
+        None => {
+            return Ok(Verification::Failed(Signer::unsigned(
+                &record, algorithm, BAD_KEY,
+            )))
+        }
+    };
+    // The MAC covers the message as it was before the TSIG was added, with
+    // the ID it had then.
+    let mut signed_data = message[..offset].to_vec();
+    signed_data[..2].copy_from_slice(&original_id.to_be_bytes());
+    add_to_additional_count(&mut signed_data, -1);
+    signed_data.extend(variables(
+        &record.name,
+        algorithm,
+        time_signed,
+        fudge,
+        error,
+        other_data,
+    ));
+    if hmac::verify(&key.hmac_key(), &signed_data, mac).is_err() {
+        return Ok(Verification::Failed(Signer::unsigned(
+            &record, algorithm, BAD_SIG,
+        )));
+    }
+    let mut signer = Signer {
+        key_name: record.name.clone(),
+        algorithm: algorithm.clone(),
+        key: Some(key.clone()),
+        request_mac: Some(mac.clone()),
+        error: 0,
+        time_signed: None,
+    };
+    if now.abs_diff(time_signed) > fudge as u64 {
+        signer.error = BAD_TIME;
+        signer.time_signed = Some(time_signed);
+        return Ok(Verification::Failed(signer));
+    }
+    Ok(Verification::Verified(signer))
+}
+
+// The fields of the TSIG record that are signed along with the message.
+// Source: https://www.rfc-editor.org/rfc/rfc8945#section-4.3.3
+fn variables(
+    key_name: &[Label],
+    algorithm: &[Label],
+    time_signed: u64,
+    fudge: u16,
+    error: u16,
+    other_data: &[u8],
+) -> Vec<u8> {
+    let mut buffer = Label::encode_canonical_name(key_name);
+    buffer.extend_from_slice(&Class::ANY.to_u16().to_be_bytes());
+    buffer.extend_from_slice(&0u32.to_be_bytes());
+    buffer.extend(Label::encode_canonical_name(algorithm));
+    buffer.extend_from_slice(&time_signed.to_be_bytes()[2..]);
+    buffer.extend_from_slice(&fudge.to_be_bytes());
+    buffer.extend_from_slice(&error.to_be_bytes());
+    buffer.extend_from_slice(&(other_data.len() as u16).to_be_bytes());
+    buffer.extend_from_slice(other_data);
+    buffer
+}
+
+fn add_to_additional_count(message: &mut [u8], delta: i32) {
+    let count = u16::from_be_bytes([message[10], message[11]]) as i32 + delta;
+    message[10..12].copy_from_slice(&(count as u16).to_be_bytes());
+}
+
+// Walks the message record by record to find where its TSIG starts, an
+// error if there is one anywhere but at the very end.
+fn find_tsig(message: &[u8]) -> Result<Option<(usize, DnsAnswer)>, DnsError> {
+    let failure = |e: nom::Err<DnsError>| match e {
+        nom::Err::Error(e) | nom::Err::Failure(e) => e,
+        nom::Err::Incomplete(_) => DnsError::InvalidRData("message ends early".to_string()),
+    };
+    let (mut input, dns_header) = DnsHeader::parse(message, message).map_err(failure)?;
+    for _ in 0..dns_header.question_count {
+        input = DnsQuestion::parse(message, input).map_err(failure)?.0;
+    }
+    let record_count = dns_header.answer_record_count as usize
+        + dns_header.authority_record_count as usize
+        + dns_header.additional_record_count as usize;
+    let mut tsig: Option<(usize, DnsAnswer)> = None;
+    for index in 0..record_count {
+        let offset = message.len() - input.len();
+        if let Ok((rest, _)) = Edns::parse(message, input) {
+            input = rest;
+            continue;
+        }
+        let (rest, record) = DnsAnswer::parse(message, input).map_err(failure)?;
+        input = rest;
+        if record.record_type == RecordType::TSIG {
+            if index + 1 != record_count {
+                return Err(DnsError::InvalidRData(
+                    "TSIG is not the last record".to_string(),
+                ));
+            }
+            tsig = Some((offset, record));
+        }
+    }
+    Ok(tsig)
+}
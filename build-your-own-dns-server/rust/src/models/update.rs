@@ -0,0 +1,213 @@
+use crate::models::dnssec::canonical_order;
+use crate::models::journal::{serial, serial_newer, Difference};
+use crate::models::zone::same_record;
+use crate::models::{Class, DnsAnswer, Label, RData, RecordType, ResponseCode, Zone};
+
+// Works out the change a dynamic update makes to the zone: the prerequisites
+// in the answer section have to hold, then the updates in the authority
+// section are made in order. None if they leave the zone as it was. The SOA
+// serial goes up by one unless an update set a newer SOA itself.
+// specification: https://www.rfc-editor.org/rfc/rfc2136#section-3
+pub fn update(
+    zone: &Zone,
+    prerequisites: &[DnsAnswer],
+    updates: &[DnsAnswer],
+) -> Result<Option<Difference>, ResponseCode> {
+    check_prerequisites(zone, prerequisites)?;
+    prescan(zone, updates)?;
+    let old_soa = zone.soa().ok_or(ResponseCode::ServerFailure)?.clone();
+    let mut updated = zone.clone();
+    let mut new_soa: Option<DnsAnswer> = None;
+    for record in updates {
+        // An SOA is only ever replaced, and only by one with a newer serial.
+        // Source: https://www.rfc-editor.org/rfc/rfc2136#section-3.4.2.2
+        if record.record_type == RecordType::SOA && record.class == Class::IN {
+            let current = new_soa.as_ref().unwrap_or(&old_soa);
+            let is_newer = serial(record)
+                .zip(serial(current))
+                .is_some_and(|(serial, current)| serial_newer(serial, current));
+            if is_at_apex(zone, record) && is_newer {
+                new_soa = Some(record.clone());
+            }
+            continue;
+        }
+        apply(&mut updated, record);
+    }
+    let mut deleted: Vec<DnsAnswer> = zone
+        .records()
+        .filter(|record| !updated.records_at(&record.name).contains(record))
+        .cloned()
+        .collect();
+    let mut added: Vec<DnsAnswer> = updated
+        .records()
+        .filter(|record| !zone.records_at(&record.name).contains(record))
+        .cloned()
+        .collect();
+    if deleted.is_empty() && added.is_empty() && new_soa.is_none() {
+        return Ok(None);
+    }
+    canonical_order(&mut deleted);
+    canonical_order(&mut added);
+    let new_soa = new_soa.unwrap_or_else(|| {
+        let mut soa = old_soa.clone();
+        if let RData::SOA { serial, .. } = &mut soa.rdata {
+            *serial = serial.wrapping_add(1);
+        }
+        soa
+    });
+    Ok(Some(Difference {
+        old_soa,
+        deleted,
+        new_soa,
+        added,
+    }))
+}
+
+// Prerequisites of class ANY or NONE ask whether a name or RRset exists or
+// not, those of the zone's class that an RRset is exactly the given records.
+// Source: https://www.rfc-editor.org/rfc/rfc2136#section-3.2
+fn check_prerequisites(zone: &Zone, prerequisites: &[DnsAnswer]) -> Result<(), ResponseCode> {
+    let mut rrsets: Vec<&DnsAnswer> = vec![];
+    for record in prerequisites {
+        if record.time_to_live != 0 {
+            return Err(ResponseCode::FormatError);
+        }
+        if !zone.contains(&record.name) {
+            return Err(ResponseCode::NotZone);
+        }
+        let records = zone.records_at(&record.name);
+        let rrset_exists = records
+            .iter()
+            .any(|existing| existing.record_type == record.record_type);
+        let is_name = record.record_type == RecordType::ANY;
+        match record.class {
+            Class::ANY | Class::NONE if !is_empty(&record.rdata) => {
+                return Err(ResponseCode::FormatError)
+            }
+            Class::ANY if is_name && records.is_empty() => return Err(ResponseCode::NameError),
+            Class::ANY if !is_name && !rrset_exists => return Err(ResponseCode::NxRrSet),
+            Class::NONE if is_name && !records.is_empty() => return Err(ResponseCode::YxDomain),
+            Class::NONE if !is_name && rrset_exists => return Err(ResponseCode::YxRrSet),
+            Class::ANY | Class::NONE => {}
+            Class::IN if !record.record_type.is_meta() => rrsets.push(record),
+            _ => return Err(ResponseCode::FormatError),
+        }
+    }
+    for record in rrsets.iter() {
+        let is_same_rrset = |other: &&DnsAnswer| {
+            other.record_type == record.record_type
+                && Label::to_key(&other.name) == Label::to_key(&record.name)
+        };
+        let expected: Vec<&DnsAnswer> = rrsets.iter().copied().filter(is_same_rrset).collect();
+        let actual: Vec<&DnsAnswer> = zone
+            .records_at(&record.name)
+            .iter()
+            .filter(is_same_rrset)
+            .collect();
+        let matches = expected
+            .iter()
+            .all(|a| actual.iter().any(|b| same_record(a, b)))
+            && actual
+                .iter()
+                .all(|a| expected.iter().any(|b| same_record(a, b)));
+        if !matches {
+            return Err(ResponseCode::NxRrSet);
+        }
+    }
+    Ok(())
+}
+
+// Updates are all checked before any is made, so that a bad one leaves the
+// zone untouched.
+// Source: https://www.rfc-editor.org/rfc/rfc2136#section-3.4.1
+fn prescan(zone: &Zone, updates: &[DnsAnswer]) -> Result<(), ResponseCode> {
+    for record in updates {
+        if !zone.contains(&record.name) {
+            return Err(ResponseCode::NotZone);
+        }
+        let is_valid = match record.class {
+            Class::IN => !record.record_type.is_meta(),
+            Class::ANY => {
+                record.time_to_live == 0
+                    && is_empty(&record.rdata)
+                    && (record.record_type == RecordType::ANY || !record.record_type.is_meta())
+            }
+            Class::NONE => record.time_to_live == 0 && !record.record_type.is_meta(),
+            _ => false,
+        };
+        if !is_valid {
+            return Err(ResponseCode::FormatError);
+        }
+    }
+    Ok(())
+}
+
+// Makes one update: class ANY deletes an RRset or every RRset at a name,
+// class NONE a single record and the zone's class adds one. The SOA and NS
+// records at the apex are kept, the zone being broken without them.
+// Source: https://www.rfc-editor.org/rfc/rfc2136#section-3.4.2
+fn apply(zone: &mut Zone, record: &DnsAnswer) {
+    let at_apex = is_at_apex(zone, record);
+    let records = zone.records_at(&record.name).to_vec();
+    let is_kept = |existing: &DnsAnswer| {
+        at_apex && matches!(existing.record_type, RecordType::SOA | RecordType::NS)
+    };
+    match record.class {
+        Class::ANY => {
+            for existing in records.iter().filter(|existing| {
+                (record.record_type == RecordType::ANY
+                    || existing.record_type == record.record_type)
+                    && !is_kept(existing)
+            }) {
+                zone.remove(existing);
+            }
+        }
+        Class::NONE => {
+            let name_servers = records
+                .iter()
+                .filter(|existing| existing.record_type == RecordType::NS)
+                .count();
+            let is_last_name_server =
+                at_apex && record.record_type == RecordType::NS && name_servers <= 1;
+            if record.record_type == RecordType::SOA || is_last_name_server {
+                return;
+            }
+            let mut deleted = record.clone();
+            deleted.class = Class::IN;
+            zone.remove(&deleted);
+        }
+        _ => {
+            // A CNAME cannot share its name with other records. A new CNAME
+            // replaces the old one, anything else next to one is ignored.
+            let has_cname = records
+                .iter()
+                .any(|existing| existing.record_type == RecordType::CNAME);
+            let has_others = records
+                .iter()
+                .any(|existing| existing.record_type != RecordType::CNAME);
+            match record.record_type {
+                RecordType::CNAME if has_others => return,
+                RecordType::CNAME => {
+                    for existing in records.iter() {
+                        zone.remove(existing);
+                    }
+                }
+                _ if has_cname => return,
+                // A record that only differs in its TTL is replaced.
+                _ => {
+                    zone.remove(record);
+                }
+            }
+            zone.insert(record.clone());
+        }
+    }
+}
+
+fn is_at_apex(zone: &Zone, record: &DnsAnswer) -> bool {
+    Label::to_key(&record.name) == Label::to_key(&zone.origin)
+}
+
+// Records of class ANY and NONE stand for an RRset or a name without RDATA.
+fn is_empty(rdata: &RData) -> bool {
+    matches!(rdata, RData::Unknown(data) if data.is_empty())
+}
@@ -0,0 +1,434 @@
+use crate::models::journal::{serial_newer, Difference};
+use crate::models::{DnsAnswer, DnsQuestion, Label, RData, RecordType};
+use std::collections::HashMap;
+
+// The outcome of looking a question up in a zone we are authoritative for.
+// specification: https://www.rfc-editor.org/rfc/rfc1034#section-4.3.2
+#[derive(Debug)]
+pub enum ZoneLookup {
+    Answer(Vec<DnsAnswer>),
+    NoData,
+    // The name does not exist, or the CNAME records leading away from it
+    // end at a name in this zone that does not, in which case they are still
+    // part of the answer.
+    // Source: https://www.rfc-editor.org/rfc/rfc6604#section-2.1
+    NxDomain(Vec<DnsAnswer>),
+    // The name lies below a zone cut, so all we can give are the name
+    // servers of the child zone and the addresses of those inside ours.
+    Referral {
+        name_servers: Vec<DnsAnswer>,
+        glue: Vec<DnsAnswer>,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct Zone {
+    pub origin: Vec<Label>,
+    records: HashMap<String, Vec<DnsAnswer>>,
+    // The changes that led up to the current version, oldest first, for
+    // incremental transfers.
+    journal: Vec<Difference>,
+}
+
+impl Zone {
+    pub fn new(origin: Vec<Label>) -> Zone {
+        Zone {
+            origin,
+            records: HashMap::new(),
+            journal: vec![],
+        }
+    }
+
+    // A zone of the records read from a master file or a full transfer,
+    // whose origin is the owner of the SOA.
+    pub fn from_records(records: Vec<DnsAnswer>) -> Result<Zone, String> {
+        let soa = records
+            .iter()
+            .find(|record| record.record_type == RecordType::SOA)
+            .ok_or_else(|| "zone has no SOA record".to_string())?;
+        let mut zone = Zone::new(soa.name.clone());
+        for record in records {
+            if !zone.contains(&record.name) {
+                return Err(format!(
+                    "record for '{}' is outside of zone '{}'",
+                    Label::to_key(&record.name),
+                    Label::to_key(&zone.origin)
+                ));
+            }
+            zone.insert(record);
+        }
+        Ok(zone)
+    }
+
+    pub fn insert(&mut self, dns_answer: DnsAnswer) {
+        self.records
+            .entry(Label::to_key(&dns_answer.name))
+            .or_default()
+            .push(dns_answer);
+    }
+
+    // Removes a record equal to the given one but for its TTL, returning
+    // whether there was one.
+    pub fn remove(&mut self, dns_answer: &DnsAnswer) -> bool {
+        let key = Label::to_key(&dns_answer.name);
+        let records = match self.records.get_mut(&key) {
+            Some(records) => records,
+            None => return false,
+        };
+        let count = records.len();
+        records.retain(|record| !same_record(record, dns_answer));
+        let removed = records.len() < count;
+        if records.is_empty() {
+            self.records.remove(&key);
+        }
+        removed
+    }
+
+    pub fn records_at(&self, name: &[Label]) -> &[DnsAnswer] {
+        self.records
+            .get(&Label::to_key(name))
+            .map_or(&[], |records| records.as_slice())
+    }
+
+    pub fn records(&self) -> impl Iterator<Item = &DnsAnswer> {
+        self.records.values().flatten()
+    }
+
+    pub fn soa(&self) -> Option<&DnsAnswer> {
+        self.records
+            .get(&Label::to_key(&self.origin))?
+            .iter()
+            .find(|record| record.record_type == RecordType::SOA)
+    }
+
+    pub fn serial(&self) -> Option<u32> {
+        match self.soa()?.rdata {
+            RData::SOA { serial, .. } => Some(serial),
+            _ => None,
+        }
+    }
+
+    // Makes the change and keeps it in the journal. The difference has to
+    // start from the current serial.
+    pub fn apply(&mut self, difference: Difference) -> Result<(), String> {
+        if self.serial() != Some(difference.old_serial()) {
+            return Err(format!(
+                "difference from serial {} does not apply to serial {}",
+                difference.old_serial(),
+                self.serial()
+                    .map_or("none".to_string(), |serial| serial.to_string())
+            ));
+        }
+        for record in difference.deleted.iter() {
+            self.remove(record);
+        }
+        if let Some(soa) = self.soa().cloned() {
+            self.remove(&soa);
+        }
+        self.insert(difference.new_soa.clone());
+        for record in difference.added.iter() {
+            let exists = self
+                .records
+                .get(&Label::to_key(&record.name))
+                .is_some_and(|records| {
+                    records.iter().any(|existing| same_record(existing, record))
+                });
+            if !exists {
+                self.insert(record.clone());
+            }
+        }
+        self.journal.push(difference);
+        Ok(())
+    }
+
+    // Brings the zone up to date with a journal read back from disk. Changes
+    // the zone already has are only kept as history, the rest are applied.
+    pub fn replay(&mut self, journal: Vec<Difference>) -> Result<(), String> {
+        for difference in journal {
+            let serial = self.serial().unwrap_or(0);
+            if difference.old_serial() == serial {
+                self.apply(difference)?;
+            } else if !serial_newer(difference.new_serial(), serial) {
+                self.journal.push(difference);
+            } else {
+                return Err(format!(
+                    "journal skips from serial {} to {}",
+                    serial,
+                    difference.old_serial()
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    // The records of a full transfer: the SOA, every other record in the
+    // zone and the SOA again to mark the end.
+    // Source: https://www.rfc-editor.org/rfc/rfc5936#section-2.2
+    pub fn transfer_records(&self) -> Vec<DnsAnswer> {
+        let soa = match self.soa() {
+            Some(soa) => soa.clone(),
+            None => return vec![],
+        };
+        let mut names: Vec<&Vec<DnsAnswer>> = self.records.values().collect();
+        names.sort_by(|a, b| Label::canonical_cmp(&a[0].name, &b[0].name));
+        let mut records = vec![soa.clone()];
+        records.extend(
+            names
+                .into_iter()
+                .flatten()
+                .filter(|record| record.record_type != RecordType::SOA)
+                .cloned(),
+        );
+        records.push(soa);
+        records
+    }
+
+    // The records of an incremental transfer to a client at the given
+    // serial: just the SOA if it is up to date, the changes since its serial
+    // between two copies of the SOA if the journal goes back that far, and a
+    // full transfer otherwise.
+    // Source: https://www.rfc-editor.org/rfc/rfc1995#section-4
+    pub fn incremental_transfer_records(&self, serial: u32) -> Vec<DnsAnswer> {
+        let soa = match self.soa() {
+            Some(soa) => soa.clone(),
+            None => return vec![],
+        };
+        let current = self.serial().unwrap_or(0);
+        if !serial_newer(current, serial) {
+            return vec![soa];
+        }
+        let start = match self
+            .journal
+            .iter()
+            .rposition(|difference| difference.old_serial() == serial)
+        {
+            Some(start) => start,
+            None => return self.transfer_records(),
+        };
+        let mut records = vec![soa.clone()];
+        for difference in self.journal[start..].iter() {
+            records.extend(difference.to_records());
+        }
+        records.push(soa);
+        records
+    }
+
+    pub fn contains(&self, labels: &[Label]) -> bool {
+        Label::is_subdomain(labels, &self.origin)
+    }
+
+    pub fn lookup(&self, dns_question: &DnsQuestion) -> ZoneLookup {
+        if let Some(referral) = self.find_delegation(&dns_question.labels, dns_question.record_type)
+        {
+            return referral;
+        }
+        let mut answers: Vec<DnsAnswer> = vec![];
+        let mut name = Label::to_key(&dns_question.labels);
+        // Follow CNAME chains as long as they stay inside this zone, the
+        // visited list guards against CNAME loops in the master file.
+        let mut visited: Vec<String> = vec![];
+        loop {
+            let records = match self.records.get(&name) {
+                Some(records) => records,
+                None if self.is_empty_non_terminal(&name) && !answers.is_empty() => {
+                    return ZoneLookup::Answer(answers)
+                }
+                None if self.is_empty_non_terminal(&name) => return ZoneLookup::NoData,
+                None => return ZoneLookup::NxDomain(answers),
+            };
+            // ANY is answered with every RRset at the name, a CNAME included,
+            // which is then not followed.
+            // Source: https://www.rfc-editor.org/rfc/rfc1034#section-4.3.2
+            let matching: Vec<DnsAnswer> = records
+                .iter()
+                .filter(|record| {
+                    dns_question.record_type == RecordType::ANY
+                        || record.record_type == dns_question.record_type
+                })
+                .cloned()
+                .collect();
+            if !matching.is_empty() {
+                answers.extend(matching);
+                return ZoneLookup::Answer(answers);
+            }
+            let cname = records
+                .iter()
+                .find(|record| record.record_type == RecordType::CNAME);
+            match cname {
+                Some(cname) if !visited.contains(&name) => {
+                    visited.push(name);
+                    answers.push(cname.clone());
+                    // Targets beyond a zone cut are for the child zone to
+                    // answer for, just like those outside this zone.
+                    let target = match &cname.rdata {
+                        RData::CNAME(target)
+                            if self.contains(target)
+                                && self
+                                    .find_delegation(target, dns_question.record_type)
+                                    .is_none() =>
+                        {
+                            target
+                        }
+                        _ => return ZoneLookup::Answer(answers),
+                    };
+                    name = Label::to_key(target);
+                }
+                _ if !answers.is_empty() => return ZoneLookup::Answer(answers),
+                _ => return ZoneLookup::NoData,
+            }
+        }
+    }
+
+    // Looks for NS records between the origin and the name, the topmost of
+    // which is where our authority ends. The DS records of a child zone are
+    // ours though, so a DS question for the cut itself is not referred.
+    // Source: https://www.rfc-editor.org/rfc/rfc1034#section-4.3.2
+    // Source: https://www.rfc-editor.org/rfc/rfc4035#section-3.1.4.1
+    fn find_delegation(&self, labels: &[Label], record_type: RecordType) -> Option<ZoneLookup> {
+        let deepest = match record_type {
+            RecordType::DS => labels.len().saturating_sub(1),
+            _ => labels.len(),
+        };
+        for depth in (self.origin.len() + 1)..=deepest {
+            let cut = &labels[labels.len() - depth..];
+            let name_servers: Vec<DnsAnswer> = match self.records.get(&Label::to_key(cut)) {
+                Some(records) => records
+                    .iter()
+                    .filter(|record| record.record_type == RecordType::NS)
+                    .cloned()
+                    .collect(),
+                None => continue,
+            };
+            if name_servers.is_empty() {
+                continue;
+            }
+            let glue = name_servers
+                .iter()
+                .filter_map(|record| match &record.rdata {
+                    RData::NS(target) if self.contains(target) => {
+                        self.records.get(&Label::to_key(target))
+                    }
+                    _ => None,
+                })
+                .flatten()
+                .filter(|record| matches!(record.record_type, RecordType::A | RecordType::AAAA))
+                .cloned()
+                .collect();
+            return Some(ZoneLookup::Referral { name_servers, glue });
+        }
+        None
+    }
+
+    // The SOA record placed in the authority section of negative answers, with
+    // its TTL capped by the SOA MINIMUM field.
+    // Source: https://www.rfc-editor.org/rfc/rfc2308#section-3
+    pub fn negative_soa(&self) -> Option<DnsAnswer> {
+        let mut soa = self
+            .records
+            .get(&Label::to_key(&self.origin))?
+            .iter()
+            .find(|record| record.record_type == RecordType::SOA)?
+            .clone();
+        if let RData::SOA { minimum, .. } = soa.rdata {
+            soa.time_to_live = soa.time_to_live.min(minimum);
+        }
+        Some(soa)
+    }
+
+    // The RRSIGs at the name covering the type, for requests with DO set.
+    // Source: https://www.rfc-editor.org/rfc/rfc4035#section-3.1.1
+    pub fn signatures(&self, name: &[Label], record_type: RecordType) -> Vec<DnsAnswer> {
+        self.records
+            .get(&Label::to_key(name))
+            .into_iter()
+            .flatten()
+            .filter(|record| {
+                matches!(record.rdata, RData::RRSIG { type_covered, .. } if type_covered == record_type)
+            })
+            .cloned()
+            .collect()
+    }
+
+    // A name that owns no records but has descendants that do exists in the
+    // tree, so questions for it are answered with NODATA rather than NXDOMAIN.
+    fn is_empty_non_terminal(&self, name: &str) -> bool {
+        let suffix = format!(".{}", name);
+        self.records.keys().any(|key| key.ends_with(&suffix))
+    }
+}
+
+// Records are the same if they only differ in their TTL or in the case of
+// the names in them.
+pub fn same_record(a: &DnsAnswer, b: &DnsAnswer) -> bool {
+    Label::to_key(&a.name) == Label::to_key(&b.name)
+        && a.record_type == b.record_type
+        && a.class == b.class
+        && a.rdata.encode_canonical() == b.rdata.encode_canonical()
+}
+
+// All zones this server is authoritative for.
+#[derive(Debug, Clone, Default)]
+pub struct Authority {
+    zones: Vec<Zone>,
+}
+
+impl Authority {
+    pub fn new(zones: Vec<Zone>) -> Authority {
+        Authority { zones }
+    }
+
+    // The zone whose origin is the name, if we have it.
+    pub fn zone(&self, origin: &[Label]) -> Option<&Zone> {
+        let key = Label::to_key(origin);
+        self.zones
+            .iter()
+            .find(|zone| Label::to_key(&zone.origin) == key)
+    }
+
+    pub fn zone_mut(&mut self, origin: &[Label]) -> Option<&mut Zone> {
+        let key = Label::to_key(origin);
+        self.zones
+            .iter_mut()
+            .find(|zone| Label::to_key(&zone.origin) == key)
+    }
+
+    // Puts the zone in place of the one with the same origin, such as a
+    // newer copy of a secondary zone, or adds it if there is none.
+    pub fn replace(&mut self, zone: Zone) {
+        let key = Label::to_key(&zone.origin);
+        self.zones
+            .retain(|existing| Label::to_key(&existing.origin) != key);
+        self.zones.push(zone);
+    }
+
+    pub fn remove(&mut self, origin: &[Label]) -> Option<Zone> {
+        let key = Label::to_key(origin);
+        let index = self
+            .zones
+            .iter()
+            .position(|zone| Label::to_key(&zone.origin) == key)?;
+        Some(self.zones.remove(index))
+    }
+
+    // Picks the closest enclosing zone, so a delegated child zone loaded
+    // alongside its parent answers for its own names.
+    pub fn find_zone(&self, labels: &[Label]) -> Option<&Zone> {
+        self.zones
+            .iter()
+            .filter(|zone| zone.contains(labels))
+            .max_by_key(|zone| zone.origin.len())
+    }
+
+    // The zone answering a question, which for DS is the parent side of the
+    // zone cut when we have it.
+    // Source: https://www.rfc-editor.org/rfc/rfc4035#section-3.1.4.1
+    pub fn find_zone_for(&self, dns_question: &DnsQuestion) -> Option<&Zone> {
+        let labels = &dns_question.labels;
+        match dns_question.record_type {
+            RecordType::DS if !labels.is_empty() => self
+                .find_zone(&labels[1..])
+                .or_else(|| self.find_zone(labels)),
+            _ => self.find_zone(labels),
+        }
+    }
+}
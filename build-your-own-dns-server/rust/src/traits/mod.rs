@@ -1,13 +1,21 @@
+use crate::models::DnsError;
+use nom::IResult;
+
 pub trait Encodable {
     fn encode(&self) -> Vec<u8>;
 }
 
-pub trait Decodable {
-    fn decode(buffer: Vec<u8>) -> Result<Self, String>
-    where
-        Self: Sized;
+pub trait Decodable: Sized {
+    // Parses a value from the start of `input`, which is a slice of `message`.
+    // The whole message is passed along as compressed names may point back
+    // anywhere in it.
+    fn parse<'a>(message: &'a [u8], input: &'a [u8]) -> IResult<&'a [u8], Self, DnsError>;
 
-    fn decode_with_cursor(buffer: Vec<u8>, cursor: &mut usize) -> Result<Self, String>
-    where
-        Self: Sized;
+    fn decode(buffer: &[u8]) -> Result<Self, DnsError> {
+        match Self::parse(buffer, buffer) {
+            Ok((_remaining, value)) => Ok(value),
+            Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => Err(e),
+            Err(nom::Err::Incomplete(_)) => Err(DnsError::Truncated),
+        }
+    }
 }
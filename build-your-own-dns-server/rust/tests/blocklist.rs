@@ -0,0 +1,85 @@
+// Which names a blocklist catches, as read from hosts-format and plain
+// domain lists.
+
+use dns_starter_rust::adapters::blocklist_file::parse_domain_list;
+use dns_starter_rust::models::presentation::parse_name;
+use dns_starter_rust::models::{BlockResponse, Blocklist};
+
+fn read_lists(blocked: &str, allowed: &str) -> Blocklist {
+    Blocklist::new(
+        parse_domain_list(blocked),
+        parse_domain_list(allowed),
+        BlockResponse::NxDomain,
+    )
+}
+
+fn is_blocked(blocklist: &Blocklist, name: &str) -> bool {
+    blocklist.is_blocked(&parse_name(name, None).unwrap())
+}
+
+#[test]
+fn plain_entries_block_the_name_and_everything_below() {
+    let blocklist = read_lists("ads.example\n", "");
+    assert!(is_blocked(&blocklist, "ads.example."));
+    assert!(is_blocked(&blocklist, "cdn.ads.example."));
+    assert!(is_blocked(&blocklist, "CDN.Ads.Example."));
+    assert!(!is_blocked(&blocklist, "example."));
+    assert!(!is_blocked(&blocklist, "badads.example."));
+    assert!(!is_blocked(&blocklist, "ads.example.org."));
+}
+
+#[test]
+fn wildcards_block_only_the_names_below() {
+    let blocklist = read_lists("*.track.example\n", "");
+    assert!(!is_blocked(&blocklist, "track.example."));
+    assert!(is_blocked(&blocklist, "a.track.example."));
+    assert!(is_blocked(&blocklist, "a.b.track.example."));
+}
+
+#[test]
+fn allowlist_wins_over_the_blocklist() {
+    let blocklist = read_lists(
+        "ads.example\n*.cdn.example\n",
+        "good.ads.example\nimages.cdn.example\n",
+    );
+    assert!(is_blocked(&blocklist, "ads.example."));
+    assert!(!is_blocked(&blocklist, "good.ads.example."));
+    assert!(!is_blocked(&blocklist, "www.good.ads.example."));
+    assert!(is_blocked(&blocklist, "video.cdn.example."));
+    assert!(!is_blocked(&blocklist, "images.cdn.example."));
+
+    // An allowed wildcard leaves the parent name blocked.
+    let blocklist = read_lists("ads.example\n", "*.ads.example\n");
+    assert!(is_blocked(&blocklist, "ads.example."));
+    assert!(!is_blocked(&blocklist, "x.ads.example."));
+}
+
+#[test]
+fn hosts_and_domain_list_lines_mix() {
+    let domains = parse_domain_list(
+        "# A hosts-format header\n\
+         127.0.0.1 localhost\n\
+         ::1 ip6-localhost ip6-loopback\n\
+         0.0.0.0 ads.example tracker.example # two at once\n\
+         \n\
+         metrics.example.\n\
+         *.pixel.example\n",
+    );
+    assert_eq!(domains.len(), 4);
+    let blocklist = Blocklist::new(domains, parse_domain_list(""), BlockResponse::Null);
+    assert!(is_blocked(&blocklist, "tracker.example."));
+    assert!(is_blocked(&blocklist, "metrics.example."));
+    assert!(is_blocked(&blocklist, "a.pixel.example."));
+    // The machine's own names in the header are never blocked.
+    assert!(!is_blocked(&blocklist, "localhost."));
+    assert!(!is_blocked(&blocklist, "ip6-loopback."));
+}
+
+#[test]
+fn block_responses_are_named_as_on_the_command_line() {
+    assert_eq!("NXDOMAIN".parse(), Ok(BlockResponse::NxDomain));
+    assert_eq!("0.0.0.0".parse(), Ok(BlockResponse::Null));
+    assert_eq!("null".parse(), Ok(BlockResponse::Null));
+    assert_eq!("refused".parse(), Ok(BlockResponse::Refused));
+    assert!("servfail".parse::<BlockResponse>().is_err());
+}
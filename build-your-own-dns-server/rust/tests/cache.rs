@@ -0,0 +1,173 @@
+// The response cache: which upstream records make it in, how long they stay
+// and which make room for new ones.
+
+use dns_starter_rust::models::presentation::parse_name;
+use dns_starter_rust::models::{Cache, Class, DnsAnswer, DnsQuestion, Label, RecordType};
+use std::time::{Duration, Instant};
+
+fn record(text: &str) -> DnsAnswer {
+    text.parse().unwrap()
+}
+
+fn question(name: &str, record_type: RecordType) -> DnsQuestion {
+    DnsQuestion {
+        labels: parse_name(name, None).unwrap(),
+        record_type,
+        class: Class::IN,
+    }
+}
+
+fn names(records: &[DnsAnswer]) -> Vec<String> {
+    records.iter().map(|record| record.to_string()).collect()
+}
+
+#[test]
+fn answer_chain_follows_cnames_from_the_question() {
+    let records = [
+        record("www.example.com. 300 IN CNAME web.example.com."),
+        record("web.example.com. 300 IN A 192.0.2.1"),
+        record("web.example.com. 300 IN RRSIG A 13 3 300 20300101000000 20240101000000 1 example.com. AAAA"),
+        // Unrelated to the question, as an upstream trying to poison the
+        // cache would add them.
+        record("bank.example. 300 IN A 198.51.100.66"),
+        record("www.example.com. 300 IN AAAA 2001:db8::1"),
+    ];
+    let chain = Cache::answer_chain(
+        &question("WWW.example.com.", RecordType::A),
+        &records,
+        |_| true,
+    );
+    assert_eq!(names(&chain), names(&records[..3]));
+}
+
+#[test]
+fn answer_chain_stops_where_the_upstream_has_no_say() {
+    let records = [
+        record("host.corp.internal. 300 IN CNAME www.example.com."),
+        record("www.example.com. 300 IN A 198.51.100.66"),
+    ];
+    let corp = parse_name("corp.internal.", None).unwrap();
+    let chain = Cache::answer_chain(
+        &question("host.corp.internal.", RecordType::A),
+        &records,
+        |name| Label::is_subdomain(name, &corp),
+    );
+    assert_eq!(names(&chain), names(&records[..1]));
+}
+
+#[test]
+fn answer_chain_gives_up_on_cname_loops() {
+    let records = [
+        record("a.example. 300 IN CNAME b.example."),
+        record("b.example. 300 IN CNAME a.example."),
+    ];
+    let chain = Cache::answer_chain(&question("a.example.", RecordType::A), &records, |_| true);
+    // The loop is cut off after a bounded number of steps.
+    assert!(chain.len() <= 10);
+    assert!(chain
+        .iter()
+        .all(|record| record.record_type == RecordType::CNAME));
+    assert!(
+        Cache::answer_chain(&question("c.example.", RecordType::A), &records, |_| true).is_empty()
+    );
+}
+
+#[test]
+fn ttls_count_down_until_the_rrset_expires() {
+    let mut cache = Cache::new(10);
+    let start = Instant::now();
+    cache.insert(
+        &[
+            record("www.example. 300 IN A 192.0.2.1"),
+            record("www.example. 60 IN A 192.0.2.2"),
+        ],
+        start,
+    );
+    let www = question("www.example.", RecordType::A);
+
+    // The set shares the lowest TTL among its records.
+    assert_eq!(
+        cache.lookup(&www, start),
+        Some(vec![
+            record("www.example. 60 IN A 192.0.2.1"),
+            record("www.example. 60 IN A 192.0.2.2"),
+        ])
+    );
+    assert_eq!(
+        names(
+            &cache
+                .lookup(&www, start + Duration::from_millis(45_500))
+                .unwrap()
+        ),
+        [
+            "www.example. 15 IN A 192.0.2.1",
+            "www.example. 15 IN A 192.0.2.2",
+        ]
+    );
+    assert_eq!(cache.lookup(&www, start + Duration::from_secs(60)), None);
+    assert!(cache.is_empty());
+}
+
+#[test]
+fn records_without_a_ttl_are_not_kept() {
+    let mut cache = Cache::new(10);
+    cache.insert(&[record("www.example. 0 IN A 192.0.2.1")], Instant::now());
+    assert!(cache.is_empty());
+}
+
+#[test]
+fn least_recently_used_rrset_makes_room() {
+    let mut cache = Cache::new(2);
+    let now = Instant::now();
+    cache.insert(&[record("a.example. 300 IN A 192.0.2.1")], now);
+    cache.insert(&[record("b.example. 300 IN A 192.0.2.2")], now);
+    // Using a. makes b. the least recently used.
+    assert!(cache
+        .lookup(&question("a.example.", RecordType::A), now)
+        .is_some());
+    cache.insert(&[record("c.example. 300 IN A 192.0.2.3")], now);
+
+    assert_eq!(cache.len(), 2);
+    assert!(cache
+        .lookup(&question("b.example.", RecordType::A), now)
+        .is_none());
+    assert!(cache
+        .lookup(&question("a.example.", RecordType::A), now)
+        .is_some());
+    assert!(cache
+        .lookup(&question("c.example.", RecordType::A), now)
+        .is_some());
+
+    // Replacing a set that is already cached evicts nothing.
+    cache.insert(&[record("c.example. 300 IN A 192.0.2.4")], now);
+    assert_eq!(cache.len(), 2);
+    assert!(cache
+        .lookup(&question("a.example.", RecordType::A), now)
+        .is_some());
+}
+
+#[test]
+fn cached_cnames_are_followed() {
+    let mut cache = Cache::new(10);
+    let now = Instant::now();
+    cache.insert(
+        &[
+            record("www.example. 300 IN CNAME web.example."),
+            record("web.example. 300 IN A 192.0.2.1"),
+        ],
+        now,
+    );
+    assert_eq!(
+        cache
+            .lookup(&question("www.example.", RecordType::A), now)
+            .map(|records| names(&records)),
+        Some(vec![
+            "www.example. 300 IN CNAME web.example.".to_string(),
+            "web.example. 300 IN A 192.0.2.1".to_string(),
+        ])
+    );
+    // Half a chain is no answer.
+    assert!(cache
+        .lookup(&question("www.example.", RecordType::AAAA), now)
+        .is_none());
+}
@@ -0,0 +1,570 @@
+// Validates answers from zones signed offline, with keys that were thrown
+// away after signing:
+//
+//   example.             RSA/SHA-256 KSK and ZSK, the trust anchor is its DS
+//   ec.example.          ECDSA P-256, SHA-256 DS in example.
+//   ed.example.          Ed25519, SHA-384 DS in example.
+//   insecure.example.    unsigned, without DS in example.
+//
+// Every signed zone has an NSEC chain, so that the missing DS of
+// insecure.example. is proven. The signatures are valid from 2024 until
+// 2080, as times compare within 68 years of each other.
+
+use dns_starter_rust::adapters::zone_file;
+use dns_starter_rust::models::dnssec::{canonical_order, ds_digest, key_tag};
+use dns_starter_rust::models::presentation::parse_name;
+use dns_starter_rust::models::{
+    Authority, Class, DnsAnswer, DnsHeader, DnsPacket, DnsQuestion, Label, RData, RecordType,
+    ResponseCode, Validation, Validator, Zone, ZoneLookup,
+};
+use dns_starter_rust::traits::{Decodable, Encodable};
+use std::cmp::Ordering;
+use std::net::{Ipv4Addr, UdpSocket};
+use std::process::{Child, Command};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const FORWARDER: &str = "127.0.0.1:2155";
+
+fn data_path(file_name: &str) -> String {
+    format!(
+        "{}/tests/data/dnssec/{}",
+        env!("CARGO_MANIFEST_DIR"),
+        file_name
+    )
+}
+
+fn authority() -> Authority {
+    Authority::new(
+        [
+            "example.zone",
+            "ec.example.zone",
+            "ed.example.zone",
+            "insecure.example.zone",
+        ]
+        .iter()
+        .map(|file_name| zone_file::read_zone_file(&data_path(file_name)).unwrap())
+        .collect(),
+    )
+}
+
+fn trust_anchors() -> Vec<DnsAnswer> {
+    zone_file::read_trust_anchors(&data_path("trust-anchor")).unwrap()
+}
+
+// 2026-01-01, well inside the validity of every signature.
+fn now() -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(1_767_225_600)
+}
+
+// The records a DNSSEC aware name server gives for the question, RRSIGs
+// included, with the NSEC record proving there are none in place of an
+// answer.
+fn lookup(authority: &Authority, labels: &[Label], record_type: RecordType) -> Vec<DnsAnswer> {
+    let dns_question = DnsQuestion {
+        labels: labels.to_vec(),
+        record_type,
+        class: Class::IN,
+    };
+    let zone = match authority.find_zone_for(&dns_question) {
+        Some(zone) => zone,
+        None => return vec![],
+    };
+    let mut records = match zone.lookup(&dns_question) {
+        ZoneLookup::Answer(answers) => answers,
+        ZoneLookup::NoData | ZoneLookup::NxDomain(_) => denial(zone, labels),
+        ZoneLookup::Referral { .. } => return vec![],
+    };
+    let mut signatures: Vec<DnsAnswer> = vec![];
+    for record in records.iter() {
+        for signature in zone.signatures(&record.name, record.record_type) {
+            if !signatures.contains(&signature) {
+                signatures.push(signature);
+            }
+        }
+    }
+    records.extend(signatures);
+    records
+}
+
+// The NSEC record at the name, or the one whose gap the name falls in.
+fn denial(zone: &Zone, labels: &[Label]) -> Vec<DnsAnswer> {
+    zone.records()
+        .filter(|record| match &record.rdata {
+            RData::NSEC {
+                next_domain_name, ..
+            } => {
+                Label::to_key(&record.name) == Label::to_key(labels)
+                    || (Label::canonical_cmp(&record.name, labels) == Ordering::Less
+                        && (Label::canonical_cmp(labels, next_domain_name) == Ordering::Less
+                            || Label::to_key(next_domain_name) == Label::to_key(&zone.origin)))
+            }
+            _ => false,
+        })
+        .take(1)
+        .cloned()
+        .collect()
+}
+
+fn response(dns_answers: Vec<DnsAnswer>) -> DnsPacket {
+    let mut dns_header = DnsHeader::new_query(1, true);
+    dns_header.answer_record_count = dns_answers.len() as u16;
+    DnsPacket {
+        dns_header,
+        dns_questions: vec![],
+        dns_answers,
+        dns_authorities: vec![],
+        dns_additionals: vec![],
+        edns: None,
+    }
+}
+
+fn validate(
+    dns_response: &DnsPacket,
+    trust_anchors: Vec<DnsAnswer>,
+    now: SystemTime,
+) -> Validation {
+    let authority = authority();
+    let fetch =
+        |labels: &[Label], record_type: RecordType| Some(lookup(&authority, labels, record_type));
+    Validator::new(trust_anchors).validate(dns_response, &fetch, now)
+}
+
+fn signed_answer(name: &str, record_type: RecordType) -> DnsPacket {
+    let answers = lookup(
+        &authority(),
+        &Label::from_domain_name(name).unwrap(),
+        record_type,
+    );
+    assert!(
+        !answers.is_empty(),
+        "no {} records for {}",
+        record_type,
+        name
+    );
+    response(answers)
+}
+
+fn is_bogus(validation: &Validation) -> bool {
+    matches!(validation, Validation::Bogus(_))
+}
+
+#[test]
+fn trust_anchor_matches_the_key_signing_key() {
+    let authority = authority();
+    let origin = Label::from_domain_name("example").unwrap();
+    let dnskeys = lookup(&authority, &origin, RecordType::DNSKEY);
+    for anchor in trust_anchors() {
+        let (tag, digest_type, digest) = match anchor.rdata {
+            RData::DS {
+                key_tag,
+                digest_type,
+                digest,
+                ..
+            } => (key_tag, digest_type, digest),
+            _ => panic!("trust anchor is not a DS record"),
+        };
+        let dnskey = dnskeys
+            .iter()
+            .find(|dnskey| {
+                matches!(dnskey.rdata, RData::DNSKEY { .. }) && key_tag(&dnskey.rdata) == tag
+            })
+            .expect("no DNSKEY with the key tag of the trust anchor");
+        assert_eq!(ds_digest(&origin, &dnskey.rdata, digest_type), Some(digest));
+    }
+}
+
+#[test]
+fn rsa_sha256_answer_is_secure() {
+    let dns_response = signed_answer("example", RecordType::MX);
+    assert_eq!(
+        validate(&dns_response, trust_anchors(), now()),
+        Validation::Secure
+    );
+}
+
+// The owner name is in mixed case in the zone and signed in lowercase.
+#[test]
+fn mixed_case_owner_is_secure() {
+    let dns_response = signed_answer("mail.example", RecordType::A);
+    assert_eq!(
+        validate(&dns_response, trust_anchors(), now()),
+        Validation::Secure
+    );
+}
+
+#[test]
+fn ecdsa_p256_answer_is_secure_through_the_parent() {
+    let mut dns_response = signed_answer("www.ec.example", RecordType::A);
+    assert_eq!(dns_response.dns_answers.len(), 4);
+    assert_eq!(
+        validate(&dns_response, trust_anchors(), now()),
+        Validation::Secure
+    );
+    // Servers may return the records of a set in any order.
+    dns_response.dns_answers.reverse();
+    assert_eq!(
+        validate(&dns_response, trust_anchors(), now()),
+        Validation::Secure
+    );
+}
+
+#[test]
+fn ed25519_answer_is_secure_through_the_parent() {
+    for record_type in [RecordType::AAAA, RecordType::TXT] {
+        let dns_response = signed_answer("www.ed.example", record_type);
+        assert_eq!(
+            validate(&dns_response, trust_anchors(), now()),
+            Validation::Secure
+        );
+    }
+}
+
+#[test]
+fn dnskey_trust_anchor_is_accepted() {
+    let dnskeys = lookup(
+        &authority(),
+        &Label::from_domain_name("example").unwrap(),
+        RecordType::DNSKEY,
+    )
+    .into_iter()
+    .filter(|record| record.record_type == RecordType::DNSKEY)
+    .collect();
+    let dns_response = signed_answer("www.ec.example", RecordType::A);
+    assert_eq!(validate(&dns_response, dnskeys, now()), Validation::Secure);
+}
+
+#[test]
+fn tampered_record_is_bogus() {
+    let mut dns_response = signed_answer("www.ec.example", RecordType::A);
+    dns_response.dns_answers[0].rdata = RData::A(Ipv4Addr::new(203, 0, 113, 1));
+    assert!(is_bogus(&validate(&dns_response, trust_anchors(), now())));
+}
+
+#[test]
+fn tampered_signature_is_bogus() {
+    let mut dns_response = signed_answer("www.ed.example", RecordType::AAAA);
+    for record in dns_response.dns_answers.iter_mut() {
+        if let RData::RRSIG { signature, .. } = &mut record.rdata {
+            signature[0] ^= 1;
+        }
+    }
+    assert!(is_bogus(&validate(&dns_response, trust_anchors(), now())));
+}
+
+#[test]
+fn expired_signature_is_bogus() {
+    let dns_response = signed_answer("example", RecordType::MX);
+    // 2081-01-01, after the signatures expired.
+    let later = UNIX_EPOCH + Duration::from_secs(3_502_915_200);
+    assert!(is_bogus(&validate(&dns_response, trust_anchors(), later)));
+    // 2023-01-01, before they were made.
+    let earlier = UNIX_EPOCH + Duration::from_secs(1_672_531_200);
+    assert!(is_bogus(&validate(&dns_response, trust_anchors(), earlier)));
+}
+
+#[test]
+fn wrong_trust_anchor_is_bogus() {
+    let mut trust_anchors = trust_anchors();
+    for anchor in trust_anchors.iter_mut() {
+        if let RData::DS { digest, .. } = &mut anchor.rdata {
+            digest[0] ^= 1;
+        }
+    }
+    let dns_response = signed_answer("example", RecordType::MX);
+    assert!(is_bogus(&validate(&dns_response, trust_anchors, now())));
+}
+
+#[test]
+fn unsigned_answers_are_insecure() {
+    // Below a delegation without DS, proven by the NSEC record at it.
+    let dns_response = signed_answer("www.insecure.example", RecordType::A);
+    assert_eq!(
+        validate(&dns_response, trust_anchors(), now()),
+        Validation::Insecure
+    );
+}
+
+#[test]
+fn unsigned_delegation_without_proof_is_bogus() {
+    let dns_response = signed_answer("www.insecure.example", RecordType::A);
+    let authority = authority();
+    let fetch = |labels: &[Label], record_type: RecordType| {
+        let mut records = lookup(&authority, labels, record_type);
+        records.retain(|record| {
+            record.record_type != RecordType::NSEC
+                && !matches!(record.rdata, RData::RRSIG { type_covered, .. } if type_covered == RecordType::NSEC)
+        });
+        Some(records)
+    };
+    let validation = Validator::new(trust_anchors()).validate(&dns_response, &fetch, now());
+    assert!(is_bogus(&validation));
+}
+
+// An attacker could otherwise pass off forged answers from signed zones as
+// merely insecure.
+#[test]
+fn stripped_signatures_are_bogus() {
+    for (name, record_type) in [
+        ("example", RecordType::MX),
+        ("www.ec.example", RecordType::A),
+    ] {
+        let mut dns_response = signed_answer(name, record_type);
+        dns_response
+            .dns_answers
+            .retain(|record| record.record_type != RecordType::RRSIG);
+        assert!(is_bogus(&validate(&dns_response, trust_anchors(), now())));
+    }
+}
+
+#[test]
+fn signatures_by_unknown_algorithms_alone_are_bogus() {
+    let mut dns_response = signed_answer("www.ed.example", RecordType::AAAA);
+    for record in dns_response.dns_answers.iter_mut() {
+        if let RData::RRSIG { algorithm, .. } = &mut record.rdata {
+            *algorithm = 253;
+        }
+    }
+    assert!(is_bogus(&validate(&dns_response, trust_anchors(), now())));
+}
+
+// Signers above the trust anchor cannot vouch for names below it.
+#[test]
+fn signatures_by_zones_above_the_trust_anchor_are_bogus() {
+    let mut dns_response = signed_answer("example", RecordType::MX);
+    for record in dns_response.dns_answers.iter_mut() {
+        if let RData::RRSIG { signer_name, .. } = &mut record.rdata {
+            *signer_name = vec![];
+        }
+    }
+    assert!(is_bogus(&validate(&dns_response, trust_anchors(), now())));
+}
+
+#[test]
+fn names_outside_of_the_trust_anchors_are_insecure() {
+    let dns_response = signed_answer("example", RecordType::MX);
+    let mut trust_anchors = trust_anchors();
+    for anchor in trust_anchors.iter_mut() {
+        anchor.name = Label::from_domain_name("elsewhere").unwrap();
+    }
+    assert_eq!(
+        validate(&dns_response, trust_anchors, now()),
+        Validation::Insecure
+    );
+}
+
+// Without checking the NSEC proof that the name itself does not exist, an
+// answer synthesized from a wildcard cannot be secure.
+#[test]
+fn wildcard_expansion_is_insecure() {
+    let mut dns_response = signed_answer("*.wild.example", RecordType::A);
+    assert_eq!(
+        validate(&dns_response, trust_anchors(), now()),
+        Validation::Secure
+    );
+    for record in dns_response.dns_answers.iter_mut() {
+        record.name = Label::from_domain_name("host.wild.example").unwrap();
+    }
+    assert_eq!(
+        validate(&dns_response, trust_anchors(), now()),
+        Validation::Insecure
+    );
+}
+
+#[test]
+fn negative_answers_are_insecure() {
+    let mut dns_response = response(vec![]);
+    dns_response.set_response_code(ResponseCode::NameError as u16);
+    assert_eq!(
+        validate(&dns_response, trust_anchors(), now()),
+        Validation::Insecure
+    );
+}
+
+// The example from the specification.
+// Source: https://www.rfc-editor.org/rfc/rfc4034#section-6.1
+#[test]
+fn names_sort_in_canonical_order() {
+    let names = [
+        "example.",
+        "a.example.",
+        "yljkjljk.a.example.",
+        "Z.a.example.",
+        "zABC.a.EXAMPLE.",
+        "z.example.",
+        "\\001.z.example.",
+        "*.z.example.",
+    ];
+    let mut records: Vec<DnsAnswer> = names
+        .iter()
+        .rev()
+        .map(|name| {
+            DnsAnswer::new(
+                parse_name(name, None).unwrap(),
+                RecordType::A,
+                Class::IN,
+                3600,
+                RData::A(Ipv4Addr::LOCALHOST),
+            )
+        })
+        .collect();
+    canonical_order(&mut records);
+    let sorted: Vec<Vec<Label>> = records.into_iter().map(|record| record.name).collect();
+    let expected: Vec<Vec<Label>> = names
+        .iter()
+        .map(|name| parse_name(name, None).unwrap())
+        .collect();
+    assert_eq!(sorted, expected);
+}
+
+#[test]
+fn canonical_order_sorts_rdata_and_drops_duplicates() {
+    let record = |name: &str, address: [u8; 4]| {
+        DnsAnswer::new(
+            Label::from_domain_name(name).unwrap(),
+            RecordType::A,
+            Class::IN,
+            3600,
+            RData::A(Ipv4Addr::from(address)),
+        )
+    };
+    let mut records = vec![
+        record("www.example", [192, 0, 2, 3]),
+        record("WWW.example", [192, 0, 2, 1]),
+        record("www.example", [192, 0, 2, 3]),
+        record("www.example", [10, 0, 0, 1]),
+    ];
+    canonical_order(&mut records);
+    let addresses: Vec<RData> = records.into_iter().map(|record| record.rdata).collect();
+    assert_eq!(
+        addresses,
+        vec![
+            RData::A(Ipv4Addr::new(10, 0, 0, 1)),
+            RData::A(Ipv4Addr::new(192, 0, 2, 1)),
+            RData::A(Ipv4Addr::new(192, 0, 2, 3)),
+        ]
+    );
+}
+
+// Kills the server process when the test is done, passed or not.
+struct Server(Child);
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+// Answers from the signed zones as a DNSSEC aware resolver would, except
+// that the RRSIGs in answers to the `stripped` question go missing on the way.
+fn spawn_upstream(stripped: (&'static str, RecordType)) -> String {
+    let udp_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let address = udp_socket.local_addr().unwrap().to_string();
+    thread::spawn(move || {
+        let authority = authority();
+        let mut buf = [0; 1232];
+        loop {
+            let (size, source) = udp_socket.recv_from(&mut buf).unwrap();
+            let dns_request = match DnsPacket::decode(&buf[..size]) {
+                Ok(dns_request) => dns_request,
+                Err(_) => continue,
+            };
+            let dns_question = dns_request.dns_questions[0].clone();
+            let mut records = lookup(&authority, &dns_question.labels, dns_question.record_type);
+            let is_answer = records.iter().any(|record| {
+                Label::to_key(&record.name) == Label::to_key(&dns_question.labels)
+                    && record.record_type == dns_question.record_type
+            });
+            if (
+                Label::to_key(&dns_question.labels).as_str(),
+                dns_question.record_type,
+            ) == stripped
+            {
+                records.retain(|record| record.record_type != RecordType::RRSIG);
+            }
+            let mut dns_response = response(vec![]);
+            dns_response.dns_header = DnsHeader::from_request_header(dns_request.dns_header);
+            dns_response.dns_questions = vec![dns_question];
+            match is_answer {
+                true => dns_response.dns_answers = records,
+                false => dns_response.dns_authorities = records,
+            }
+            dns_response.dns_header.question_count = 1;
+            dns_response.dns_header.answer_record_count = dns_response.dns_answers.len() as u16;
+            dns_response.dns_header.authority_record_count =
+                dns_response.dns_authorities.len() as u16;
+            dns_response.edns = dns_request.edns;
+            let _ = udp_socket.send_to(&dns_response.encode(), source);
+        }
+    });
+    address
+}
+
+// Sets AD, so that the forwarder tells whether it validated the answer.
+fn query(name: &str, record_type: RecordType) -> Option<DnsPacket> {
+    let mut dns_header = DnsHeader::new_query(0x4242, true);
+    dns_header.set_authentic_data(true);
+    let dns_request = DnsPacket {
+        dns_header,
+        dns_questions: vec![DnsQuestion {
+            labels: Label::from_domain_name(name).unwrap(),
+            record_type,
+            class: Class::IN,
+        }],
+        dns_answers: vec![],
+        dns_authorities: vec![],
+        dns_additionals: vec![],
+        edns: None,
+    };
+    let udp_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    udp_socket
+        .set_read_timeout(Some(Duration::from_millis(500)))
+        .unwrap();
+    udp_socket.send_to(&dns_request.encode(), FORWARDER).ok()?;
+    let mut buf = [0; 1232];
+    let size = udp_socket.recv(&mut buf).ok()?;
+    DnsPacket::decode(&buf[..size]).ok()
+}
+
+#[test]
+fn stripped_signatures_are_answered_with_servfail() {
+    let upstream = spawn_upstream(("example", RecordType::MX));
+    let trust_anchor = data_path("trust-anchor");
+    let _server = Server(
+        Command::new(env!("CARGO_BIN_EXE_dns-starter-rust"))
+            .args([
+                "--listen",
+                FORWARDER,
+                "--resolver",
+                &upstream,
+                "--trust-anchor",
+                &trust_anchor,
+            ])
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .spawn()
+            .unwrap(),
+    );
+    let start = Instant::now();
+    let dns_response = loop {
+        match query("www.ec.example", RecordType::A) {
+            Some(dns_response) => break dns_response,
+            None if start.elapsed() < Duration::from_secs(10) => continue,
+            None => panic!("forwarder did not come up"),
+        }
+    };
+    assert_eq!(dns_response.response_code(), ResponseCode::NoError as u16);
+    assert!(dns_response.dns_header.authentic_data());
+
+    let dns_response = query("www.insecure.example", RecordType::A).unwrap();
+    assert_eq!(dns_response.response_code(), ResponseCode::NoError as u16);
+    assert!(!dns_response.dns_header.authentic_data());
+
+    let dns_response = query("example", RecordType::MX).unwrap();
+    assert_eq!(
+        dns_response.response_code(),
+        ResponseCode::ServerFailure as u16
+    );
+    assert!(dns_response.dns_answers.is_empty());
+}
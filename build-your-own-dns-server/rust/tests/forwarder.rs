@@ -0,0 +1,127 @@
+// Forwarding to stand-in upstreams on loopback addresses, each answering
+// every question the same way and reporting the questions it got.
+
+use dns_starter_rust::adapters::forwarder::Forwarder;
+use dns_starter_rust::adapters::upstream_pool::{Strategy, UpstreamPool};
+use dns_starter_rust::models::metrics::Metrics;
+use dns_starter_rust::models::{
+    Class, DnsHeader, DnsPacket, DnsQuestion, Label, RecordType, ResponseCode,
+};
+use dns_starter_rust::traits::{Decodable, Encodable};
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+fn spawn_upstream(response_code: ResponseCode) -> (SocketAddr, Receiver<DnsQuestion>) {
+    let udp_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let address = udp_socket.local_addr().unwrap();
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut buf = [0; 512];
+        loop {
+            let (size, source) = match udp_socket.recv_from(&mut buf) {
+                Ok(received) => received,
+                Err(_) => return,
+            };
+            let dns_request = match DnsPacket::decode(&buf[..size]) {
+                Ok(dns_request) => dns_request,
+                Err(_) => continue,
+            };
+            let mut dns_header = DnsHeader::from_request_header(dns_request.dns_header);
+            dns_header.response_code = response_code as u8;
+            dns_header.question_count = dns_request.dns_questions.len() as u16;
+            for dns_question in dns_request.dns_questions.iter() {
+                let _ = sender.send(dns_question.clone());
+            }
+            let dns_response = DnsPacket {
+                dns_header,
+                dns_questions: dns_request.dns_questions,
+                dns_answers: vec![],
+                dns_authorities: vec![],
+                dns_additionals: vec![],
+                edns: None,
+            };
+            let _ = udp_socket.send_to(&dns_response.encode(), source);
+        }
+    });
+    (address, receiver)
+}
+
+fn forwarder(
+    default_pool: Option<Vec<SocketAddr>>,
+    rules: Vec<(&str, Vec<SocketAddr>)>,
+) -> Forwarder {
+    Forwarder::new(
+        default_pool.map(|addresses| UpstreamPool::new(addresses, Strategy::Failover)),
+        rules
+            .into_iter()
+            .map(|(suffix, addresses)| {
+                (
+                    Label::from_domain_name(suffix).unwrap(),
+                    UpstreamPool::new(addresses, Strategy::Failover),
+                )
+            })
+            .collect(),
+        Duration::from_secs(2),
+        2,
+        1232,
+        Arc::new(Metrics::new()),
+    )
+    .unwrap()
+}
+
+fn request(name: &str) -> DnsPacket {
+    DnsPacket {
+        dns_header: DnsHeader::new_query(0x4242, true),
+        dns_questions: vec![DnsQuestion {
+            labels: Label::from_domain_name(name).unwrap(),
+            record_type: RecordType::A,
+            class: Class::IN,
+        }],
+        dns_answers: vec![],
+        dns_authorities: vec![],
+        dns_additionals: vec![],
+        edns: None,
+    }
+}
+
+#[test]
+fn servfail_and_refused_move_on_to_the_next_upstream() {
+    let (failing, _) = spawn_upstream(ResponseCode::ServerFailure);
+    let (refusing, _) = spawn_upstream(ResponseCode::Refused);
+    let (working, _) = spawn_upstream(ResponseCode::NameError);
+    let replies = forwarder(Some(vec![failing, refusing, working]), vec![])
+        .forward(vec![request("www.example")]);
+    let (dns_response, upstream) = replies.into_iter().next().unwrap().unwrap();
+    assert_eq!(upstream, working);
+    assert_eq!(dns_response.dns_header.packet_identifier, 0x4242);
+    assert_eq!(
+        dns_response.dns_header.response_code,
+        ResponseCode::NameError as u8
+    );
+
+    // With nothing left to try the request fails rather than passing the
+    // last SERVFAIL on.
+    let replies =
+        forwarder(Some(vec![failing, refusing]), vec![]).forward(vec![request("www.example")]);
+    assert!(replies[0].is_err());
+}
+
+#[test]
+fn health_checks_ask_about_the_names_an_upstream_is_used_for() {
+    let (default_upstream, default_questions) = spawn_upstream(ResponseCode::NoError);
+    let (corp_upstream, corp_questions) = spawn_upstream(ResponseCode::NoError);
+    let _forwarder = forwarder(
+        Some(vec![default_upstream]),
+        vec![("corp.example", vec![corp_upstream])],
+    );
+    let timeout = Duration::from_secs(5);
+    let dns_question = default_questions.recv_timeout(timeout).unwrap();
+    assert!(dns_question.labels.is_empty());
+    assert_eq!(dns_question.record_type, RecordType::NS);
+    let dns_question = corp_questions.recv_timeout(timeout).unwrap();
+    assert_eq!(Label::to_key(&dns_question.labels), "corp.example");
+    assert_eq!(dns_question.record_type, RecordType::NS);
+}
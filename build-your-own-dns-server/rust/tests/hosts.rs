@@ -0,0 +1,30 @@
+// Static addresses read from /etc/hosts-format files.
+
+use dns_starter_rust::adapters::hosts_file::parse_hosts;
+use dns_starter_rust::models::presentation::parse_name;
+
+#[test]
+fn names_are_absolute_with_or_without_a_dot() {
+    let hosts = parse_hosts("192.0.2.1 router.lan\n2001:db8::1 nas.lan. # storage\n").unwrap();
+    assert!(hosts.contains(&parse_name("router.lan.", None).unwrap()));
+    assert!(hosts.contains(&parse_name("NAS.lan.", None).unwrap()));
+}
+
+#[test]
+fn oversized_names_are_rejected_with_their_line() {
+    let long_label = "a".repeat(64);
+    let error = parse_hosts(&format!("192.0.2.1 ok.lan\n192.0.2.2 {}.lan\n", long_label))
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("line 2"), "{}", error);
+
+    // Four labels of 63 octets take 257 octets on the wire.
+    let long_name = format!("{0}.{0}.{0}.{0}", "a".repeat(63));
+    let error = parse_hosts(&format!("192.0.2.1 {}\n", long_name))
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains("line 1"), "{}", error);
+
+    let longest_name = format!("{0}.{0}.{0}.{1}", "a".repeat(63), "a".repeat(61));
+    assert!(parse_hosts(&format!("192.0.2.1 {}\n", longest_name)).is_ok());
+}
@@ -0,0 +1,20 @@
+// The counters we export, as a Prometheus scrape sees them.
+
+use dns_starter_rust::models::metrics::Metrics;
+use dns_starter_rust::models::RecordType;
+
+#[test]
+fn counters_are_rendered_with_their_help_and_type() {
+    let metrics = Metrics::new();
+    metrics.record_query(RecordType::A);
+    metrics.record_query(RecordType::A);
+    metrics.record_query(RecordType::Unknown(65));
+    metrics.record_blocked_query();
+    metrics.record_blocked_query();
+    let text = metrics.render();
+    assert!(text.contains("dns_queries_total{type=\"A\"} 2\n"));
+    assert!(text.contains("dns_queries_total{type=\"TYPE65\"} 1\n"));
+    assert!(text.contains("# TYPE dns_blocked_queries_total counter\n"));
+    assert!(text.contains("\ndns_blocked_queries_total 2\n"));
+    assert!(text.contains("\ndns_dropped_queries_total 0\n"));
+}
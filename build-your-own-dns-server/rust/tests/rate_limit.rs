@@ -0,0 +1,261 @@
+// Response rate limiting: which responses go out, go out truncated or are
+// dropped as a client network asks faster than its rate.
+
+use dns_starter_rust::models::presentation::parse_name;
+use dns_starter_rust::models::{
+    Class, DnsHeader, DnsPacket, DnsQuestion, RateLimitAction, RateLimitConfig, RateLimitEvent,
+    RateLimiter, RecordType, ResponseCode,
+};
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+use RateLimitAction::{Drop, Send, Slip};
+
+fn limiter(responses_per_second: u32, slip: u32) -> RateLimiter {
+    RateLimiter::new(RateLimitConfig {
+        responses_per_second,
+        nxdomains_per_second: None,
+        errors_per_second: None,
+        window: 5,
+        slip,
+        ipv4_prefix_length: 24,
+        ipv6_prefix_length: 56,
+    })
+}
+
+fn response(name: &str, response_code: ResponseCode) -> DnsPacket {
+    let mut dns_response = DnsPacket {
+        dns_header: DnsHeader::new_query(0x1234, true),
+        dns_questions: vec![DnsQuestion {
+            labels: parse_name(name, None).unwrap(),
+            record_type: RecordType::A,
+            class: Class::IN,
+        }],
+        dns_answers: vec![],
+        dns_authorities: vec![],
+        dns_additionals: vec![],
+        edns: None,
+    };
+    dns_response.set_response_code(response_code as u16);
+    dns_response
+}
+
+fn client(address: &str) -> IpAddr {
+    address.parse().unwrap()
+}
+
+// What becomes of `count` identical responses sent at once.
+fn burst(
+    rate_limiter: &RateLimiter,
+    client: IpAddr,
+    dns_response: &DnsPacket,
+    count: usize,
+    now: Instant,
+) -> Vec<RateLimitAction> {
+    (0..count)
+        .map(|_| rate_limiter.check(client, dns_response, now))
+        .collect()
+}
+
+#[test]
+fn every_slip_th_limited_response_is_truncated() {
+    let rate_limiter = limiter(2, 2);
+    let www = response("www.example.", ResponseCode::NoError);
+    assert_eq!(
+        burst(&rate_limiter, client("192.0.2.1"), &www, 6, Instant::now()),
+        [Send, Send, Drop, Slip, Drop, Slip]
+    );
+}
+
+#[test]
+fn slip_of_zero_drops_and_one_truncates_everything() {
+    let www = response("www.example.", ResponseCode::NoError);
+    let now = Instant::now();
+    assert_eq!(
+        burst(&limiter(1, 0), client("192.0.2.1"), &www, 4, now),
+        [Send, Drop, Drop, Drop]
+    );
+    assert_eq!(
+        burst(&limiter(1, 1), client("192.0.2.1"), &www, 4, now),
+        [Send, Slip, Slip, Slip]
+    );
+}
+
+#[test]
+fn rate_of_zero_limits_nothing() {
+    let www = response("www.example.", ResponseCode::NoError);
+    assert!(burst(
+        &limiter(0, 2),
+        client("192.0.2.1"),
+        &www,
+        100,
+        Instant::now()
+    )
+    .iter()
+    .all(|action| *action == Send));
+}
+
+#[test]
+fn tokens_come_back_at_the_rate() {
+    let rate_limiter = limiter(2, 3);
+    let www = response("www.example.", ResponseCode::NoError);
+    let client = client("192.0.2.1");
+    let start = Instant::now();
+    assert_eq!(
+        burst(&rate_limiter, client, &www, 4, start),
+        [Send, Send, Drop, Drop]
+    );
+    // Two seconds pay back the debt of two and give two more responses,
+    // and the count towards the next slip starts over.
+    let later = start + Duration::from_secs(2);
+    assert_eq!(
+        burst(&rate_limiter, client, &www, 5, later),
+        [Send, Send, Drop, Drop, Slip]
+    );
+}
+
+#[test]
+fn debt_stops_at_a_window_of_responses() {
+    let rate_limiter = limiter(1, 0);
+    let www = response("www.example.", ResponseCode::NoError);
+    let client = client("192.0.2.1");
+    let start = Instant::now();
+    burst(&rate_limiter, client, &www, 1000, start);
+    // Asking every other second pays back one response at a time, from a
+    // debt of five rather than a thousand.
+    let actions: Vec<RateLimitAction> = (1..=5)
+        .map(|step| rate_limiter.check(client, &www, start + Duration::from_secs(2 * step)))
+        .collect();
+    assert_eq!(actions, [Drop, Drop, Drop, Drop, Send]);
+}
+
+#[test]
+fn idle_clients_are_forgiven_after_the_window() {
+    let rate_limiter = limiter(1, 0);
+    let www = response("www.example.", ResponseCode::NoError);
+    let client = client("192.0.2.1");
+    let start = Instant::now();
+    burst(&rate_limiter, client, &www, 1000, start);
+    assert_eq!(
+        rate_limiter.check(client, &www, start + Duration::from_secs(5)),
+        Send
+    );
+}
+
+#[test]
+fn clients_in_a_network_share_their_buckets() {
+    let rate_limiter = limiter(1, 0);
+    let www = response("www.example.", ResponseCode::NoError);
+    let now = Instant::now();
+    assert_eq!(rate_limiter.check(client("192.0.2.1"), &www, now), Send);
+    assert_eq!(rate_limiter.check(client("192.0.2.200"), &www, now), Drop);
+    assert_eq!(rate_limiter.check(client("198.51.100.1"), &www, now), Send);
+    assert_eq!(rate_limiter.check(client("2001:db8::1"), &www, now), Send);
+    assert_eq!(
+        rate_limiter.check(client("2001:db8:0:ff::1"), &www, now),
+        Drop
+    );
+    assert_eq!(
+        rate_limiter.check(client("2001:db8:0:100::1"), &www, now),
+        Send
+    );
+
+    assert_eq!(rate_limiter.network(client("192.0.2.200")), "192.0.2.0/24");
+    assert_eq!(
+        rate_limiter.network(client("2001:db8:0:ff::1")),
+        "2001:db8::/56"
+    );
+}
+
+#[test]
+fn buckets_are_per_answer_but_shared_by_errors() {
+    let rate_limiter = limiter(1, 0);
+    let client = client("192.0.2.1");
+    let now = Instant::now();
+    let check = |name: &str, response_code: ResponseCode| {
+        rate_limiter.check(client, &response(name, response_code), now)
+    };
+    assert_eq!(check("www.example.", ResponseCode::NoError), Send);
+    assert_eq!(check("mail.example.", ResponseCode::NoError), Send);
+    assert_eq!(check("www.example.", ResponseCode::NoError), Drop);
+
+    assert_eq!(check("a.example.", ResponseCode::ServerFailure), Send);
+    assert_eq!(check("b.example.", ResponseCode::Refused), Drop);
+}
+
+#[test]
+fn nxdomains_are_counted_per_zone_at_their_own_rate() {
+    let rate_limiter = RateLimiter::new(RateLimitConfig {
+        responses_per_second: 1,
+        nxdomains_per_second: Some(2),
+        errors_per_second: None,
+        window: 5,
+        slip: 0,
+        ipv4_prefix_length: 24,
+        ipv6_prefix_length: 56,
+    });
+    let client = client("192.0.2.1");
+    let now = Instant::now();
+    let nxdomain = |name: &str| {
+        let mut dns_response = response(name, ResponseCode::NameError);
+        dns_response.dns_authorities.push(
+            "example. 300 IN SOA ns1.example. hostmaster.example. 1 7200 3600 1209600 300"
+                .parse()
+                .unwrap(),
+        );
+        rate_limiter.check(client, &dns_response, now)
+    };
+    // Random names under the zone all take from the same bucket.
+    assert_eq!(
+        ["x1.example.", "x2.example.", "x3.example."].map(nxdomain),
+        [Send, Send, Drop]
+    );
+}
+
+#[test]
+fn limiting_is_reported_when_it_starts_and_stops() {
+    let rate_limiter = limiter(1, 2);
+    let www = response("www.example.", ResponseCode::NoError);
+    let client = client("192.0.2.1");
+    let start = Instant::now();
+    burst(&rate_limiter, client, &www, 4, start);
+    let started = || RateLimitEvent::Started {
+        network: "192.0.2.0/24".to_string(),
+        responses: "answers for www.example A".to_string(),
+    };
+    assert_eq!(rate_limiter.take_events(), [started()]);
+    burst(&rate_limiter, client, &www, 100, start);
+    assert_eq!(rate_limiter.take_events(), []);
+    // Paying back the debt of five lets a response through again.
+    assert_eq!(
+        rate_limiter.check(client, &www, start + Duration::from_secs(6)),
+        Send
+    );
+    assert_eq!(
+        rate_limiter.take_events(),
+        [RateLimitEvent::Stopped {
+            network: "192.0.2.0/24".to_string(),
+            responses: "answers for www.example A".to_string(),
+            limited: 103,
+        }]
+    );
+
+    // A limited bucket that goes idle stops as well once it is forgotten.
+    let later = start + Duration::from_secs(7);
+    burst(&rate_limiter, client, &www, 3, later);
+    assert_eq!(rate_limiter.take_events(), [started()]);
+    let other = response("other.example.", ResponseCode::NoError);
+    rate_limiter.check(client, &other, later + Duration::from_secs(10));
+    let events: Vec<String> = rate_limiter
+        .take_events()
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+    assert_eq!(
+        events,
+        [
+            "Stopped limiting answers for www.example A to network 192.0.2.0/24 \
+            after 2 limited responses"
+        ]
+    );
+}
@@ -0,0 +1,315 @@
+// Resolves through a small stand-in for the DNS hierarchy, made of instances
+// of the server running authoritatively on loopback addresses:
+//
+//   127.0.0.2  .             delegates test. with glue, other. without
+//   127.0.0.3  test.         delegates example.test. with glue
+//   127.0.0.4  example.test. www is a CNAME into other.
+//   127.0.0.5  other.
+//   127.0.0.6  nothing, so it refuses the lame.test. it is a name server of
+//   127.0.0.7  lame.test.
+//   127.0.0.8  forged.test., a stand-in that answers with records for
+//              other. as well
+
+use dns_starter_rust::models::{
+    Class, DnsAnswer, DnsHeader, DnsPacket, DnsQuestion, Label, RData, RecordType, ResponseCode,
+};
+use dns_starter_rust::traits::{Decodable, Encodable};
+use std::net::{Ipv4Addr, UdpSocket};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const PORT: u16 = 2153;
+const RESOLVER: &str = "127.0.0.1:2154";
+
+const ROOT_ZONE: &str = "$ORIGIN .
+$TTL 3600
+@ SOA a.root-servers.test. hostmaster.test. 1 3600 900 604800 300
+@ NS a.root-servers.test.
+a.root-servers.test. A 127.0.0.2
+test. NS ns.test.
+ns.test. A 127.0.0.3
+other. NS ns.other-servers.test.
+";
+
+const TEST_ZONE: &str = "$ORIGIN test.
+$TTL 3600
+@ SOA ns hostmaster 1 3600 900 604800 300
+@ NS ns
+ns A 127.0.0.3
+example NS ns1.example
+ns1.example A 127.0.0.4
+ns.other-servers A 127.0.0.5
+lame NS ns1.lame
+lame NS ns2.lame
+ns1.lame A 127.0.0.6
+ns2.lame A 127.0.0.7
+forged NS ns1.forged
+ns1.forged A 127.0.0.8
+";
+
+const EXAMPLE_ZONE: &str = "$ORIGIN example.test.
+$TTL 3600
+@ SOA ns1 hostmaster 1 3600 900 604800 300
+@ NS ns1
+ns1 A 127.0.0.4
+host A 192.0.2.10
+www CNAME alias.other.
+";
+
+const OTHER_ZONE: &str = "$ORIGIN other.
+$TTL 3600
+@ SOA ns.other-servers.test. hostmaster 1 3600 900 604800 300
+@ NS ns.other-servers.test.
+alias A 192.0.2.20
+";
+
+const LAME_ZONE: &str = "$ORIGIN lame.test.
+$TTL 3600
+@ SOA ns2 hostmaster 1 3600 900 604800 300
+@ NS ns1
+@ NS ns2
+ns1 A 127.0.0.6
+ns2 A 127.0.0.7
+host A 192.0.2.30
+";
+
+const ROOT_HINTS: &str = ". 3600000 NS a.root-servers.test.
+a.root-servers.test. 3600000 A 127.0.0.2
+";
+
+// Answers every question with a CNAME into other. and an address for the
+// target it has no authority over.
+fn spawn_forger(address: &str) {
+    let udp_socket = UdpSocket::bind(address).unwrap();
+    thread::spawn(move || {
+        let mut buf = [0; 512];
+        loop {
+            let (size, source) = match udp_socket.recv_from(&mut buf) {
+                Ok(received) => received,
+                Err(_) => return,
+            };
+            let dns_request = match DnsPacket::decode(&buf[..size]) {
+                Ok(dns_request) => dns_request,
+                Err(_) => continue,
+            };
+            let dns_question = match dns_request.dns_questions.first() {
+                Some(dns_question) => dns_question.clone(),
+                None => continue,
+            };
+            let dns_answers = vec![
+                DnsAnswer::new(
+                    dns_question.labels.clone(),
+                    RecordType::CNAME,
+                    Class::IN,
+                    3600,
+                    RData::CNAME(Label::from_domain_name("alias.other").unwrap()),
+                ),
+                DnsAnswer::new(
+                    Label::from_domain_name("alias.other").unwrap(),
+                    RecordType::A,
+                    Class::IN,
+                    3600,
+                    RData::A(Ipv4Addr::new(203, 0, 113, 66)),
+                ),
+            ];
+            let mut dns_header = DnsHeader::from_request_header(dns_request.dns_header);
+            dns_header.authoritative_answer = true;
+            dns_header.question_count = 1;
+            dns_header.answer_record_count = dns_answers.len() as u16;
+            let dns_response = DnsPacket {
+                dns_header,
+                dns_questions: vec![dns_question],
+                dns_answers,
+                dns_authorities: vec![],
+                dns_additionals: vec![],
+                edns: None,
+            };
+            let _ = udp_socket.send_to(&dns_response.encode(), source);
+        }
+    });
+}
+
+// Kills the server processes when the test is done, passed or not.
+struct Servers(Vec<Child>);
+
+impl Drop for Servers {
+    fn drop(&mut self) {
+        for child in self.0.iter_mut() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+fn write_file(directory: &Path, name: &str, contents: &str) -> String {
+    let path = directory.join(name);
+    std::fs::write(&path, contents).unwrap();
+    path.to_string_lossy().into_owned()
+}
+
+fn spawn(args: &[&str]) -> Child {
+    Command::new(env!("CARGO_BIN_EXE_dns-starter-rust"))
+        .args(args)
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .unwrap()
+}
+
+fn query(server: &str, name: &str, record_type: RecordType) -> Option<DnsPacket> {
+    let dns_request = DnsPacket {
+        dns_header: DnsHeader::new_query(0x4242, true),
+        dns_questions: vec![DnsQuestion {
+            labels: Label::from_domain_name(name).unwrap(),
+            record_type,
+            class: Class::IN,
+        }],
+        dns_answers: vec![],
+        dns_authorities: vec![],
+        dns_additionals: vec![],
+        edns: None,
+    };
+    let udp_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    udp_socket
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+    udp_socket.send_to(&dns_request.encode(), server).ok()?;
+    let mut buf = [0; 1232];
+    let size = udp_socket.recv(&mut buf).ok()?;
+    DnsPacket::decode(&buf[..size]).ok()
+}
+
+// Waits until the server at the address answers at all.
+fn wait_for(server: &str) {
+    let start = Instant::now();
+    while start.elapsed() < Duration::from_secs(10) {
+        let udp_socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        udp_socket
+            .set_read_timeout(Some(Duration::from_millis(200)))
+            .unwrap();
+        let probe = DnsPacket {
+            dns_header: DnsHeader::new_query(1, false),
+            dns_questions: vec![],
+            dns_answers: vec![],
+            dns_authorities: vec![],
+            dns_additionals: vec![],
+            edns: None,
+        };
+        if udp_socket.send_to(&probe.encode(), server).is_ok()
+            && udp_socket.recv(&mut [0; 512]).is_ok()
+        {
+            return;
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+    panic!("server at {} did not come up", server);
+}
+
+fn addresses(dns_response: &DnsPacket) -> Vec<Ipv4Addr> {
+    dns_response
+        .dns_answers
+        .iter()
+        .filter_map(|record| match record.rdata {
+            RData::A(address) => Some(address),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn resolves_iteratively_from_root_hints() {
+    let directory: PathBuf =
+        std::env::temp_dir().join(format!("dns-recursive-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let port = PORT.to_string();
+    let mut children = vec![];
+    for (address, zone) in [
+        ("127.0.0.2", ROOT_ZONE),
+        ("127.0.0.3", TEST_ZONE),
+        ("127.0.0.4", EXAMPLE_ZONE),
+        ("127.0.0.5", OTHER_ZONE),
+        ("127.0.0.7", LAME_ZONE),
+    ] {
+        let zone_path = write_file(&directory, &format!("{}.zone", address), zone);
+        let listen = format!("{}:{}", address, PORT);
+        children.push(spawn(&["--listen", &listen, "--zone", &zone_path]));
+    }
+    let lame = format!("127.0.0.6:{}", PORT);
+    children.push(spawn(&["--listen", &lame]));
+    spawn_forger(&format!("127.0.0.8:{}", PORT));
+    let root_hints = write_file(&directory, "named.root", ROOT_HINTS);
+    children.push(spawn(&[
+        "--listen",
+        RESOLVER,
+        "--root-hints",
+        &root_hints,
+        "--name-server-port",
+        &port,
+    ]));
+    let _servers = Servers(children);
+    for address in [
+        "127.0.0.2",
+        "127.0.0.3",
+        "127.0.0.4",
+        "127.0.0.5",
+        "127.0.0.6",
+        "127.0.0.7",
+    ] {
+        wait_for(&format!("{}:{}", address, PORT));
+    }
+    wait_for(RESOLVER);
+
+    // Referrals with glue all the way down.
+    let dns_response = query(RESOLVER, "host.example.test", RecordType::A).unwrap();
+    assert_eq!(
+        dns_response.dns_header.response_code,
+        ResponseCode::NoError as u8
+    );
+    assert!(dns_response.dns_header.recursion_available);
+    assert_eq!(addresses(&dns_response), vec![Ipv4Addr::new(192, 0, 2, 10)]);
+
+    // A CNAME into other., whose name server has no glue at the root.
+    let dns_response = query(RESOLVER, "www.example.test", RecordType::A).unwrap();
+    assert_eq!(
+        dns_response.dns_header.response_code,
+        ResponseCode::NoError as u8
+    );
+    assert_eq!(dns_response.dns_answers[0].record_type, RecordType::CNAME);
+    assert_eq!(addresses(&dns_response), vec![Ipv4Addr::new(192, 0, 2, 20)]);
+
+    // Negative answers come back with the SOA of the zone that gave them.
+    let dns_response = query(RESOLVER, "missing.example.test", RecordType::A).unwrap();
+    assert_eq!(
+        dns_response.dns_header.response_code,
+        ResponseCode::NameError as u8
+    );
+    assert_eq!(dns_response.dns_authorities[0].record_type, RecordType::SOA);
+    let dns_response = query(RESOLVER, "host.example.test", RecordType::MX).unwrap();
+    assert_eq!(
+        dns_response.dns_header.response_code,
+        ResponseCode::NoError as u8
+    );
+    assert!(dns_response.dns_answers.is_empty());
+
+    // The first name server of lame.test. refuses, the second one answers.
+    let dns_response = query(RESOLVER, "host.lame.test", RecordType::A).unwrap();
+    assert_eq!(
+        dns_response.dns_header.response_code,
+        ResponseCode::NoError as u8
+    );
+    assert_eq!(addresses(&dns_response), vec![Ipv4Addr::new(192, 0, 2, 30)]);
+
+    // The address forged.test. gives for alias.other. is passed over, the
+    // one other. has is looked up instead.
+    let dns_response = query(RESOLVER, "www.forged.test", RecordType::A).unwrap();
+    assert_eq!(
+        dns_response.dns_header.response_code,
+        ResponseCode::NoError as u8
+    );
+    assert_eq!(dns_response.dns_answers[0].record_type, RecordType::CNAME);
+    assert_eq!(addresses(&dns_response), vec![Ipv4Addr::new(192, 0, 2, 20)]);
+
+    let _ = std::fs::remove_dir_all(&directory);
+}
@@ -0,0 +1,673 @@
+// Every message we can build has to come back unchanged from its encoding,
+// and no input at all may make the decoder panic.
+
+use dns_starter_rust::models::presentation::parse_name;
+use dns_starter_rust::models::{
+    Class, Compression, DnsAnswer, DnsError, DnsHeader, DnsPacket, DnsQuestion, Edns, EdnsOption,
+    Label, RData, RecordType, ResponseCode,
+};
+use dns_starter_rust::traits::{Decodable, Encodable};
+use proptest::collection::{btree_set, vec};
+use proptest::prelude::*;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+fn label() -> impl Strategy<Value = Label> {
+    // Mostly hostname characters, with some labels that need escaping in
+    // presentation format, are not ASCII or are not even UTF-8.
+    prop_oneof![
+        4 => "[a-zA-Z0-9_-]{1,20}".prop_map(|text| Label::from_string(text).unwrap()),
+        1 => "[ -~]{1,10}".prop_map(|text| Label::from_string(text).unwrap()),
+        1 => "\\PC{1,5}".prop_map(|text| Label::from_string(text).unwrap()),
+        1 => vec(any::<u8>(), 1..=20).prop_map(|bytes| Label::from_bytes(bytes).unwrap()),
+    ]
+}
+
+// At most 4 labels of at most 20 bytes stays well below the 255 byte limit.
+fn name() -> impl Strategy<Value = Vec<Label>> {
+    vec(label(), 0..=4)
+}
+
+fn record_type_and_rdata() -> impl Strategy<Value = (RecordType, RData)> {
+    prop_oneof![
+        any::<u32>().prop_map(|address| (RecordType::A, RData::A(Ipv4Addr::from(address)))),
+        any::<u128>().prop_map(|address| (RecordType::AAAA, RData::AAAA(Ipv6Addr::from(address)))),
+        name().prop_map(|name| (RecordType::NS, RData::NS(name))),
+        name().prop_map(|name| (RecordType::CNAME, RData::CNAME(name))),
+        name().prop_map(|name| (RecordType::PTR, RData::PTR(name))),
+        name().prop_map(|name| (RecordType::MB, RData::MB(name))),
+        name().prop_map(|name| (RecordType::MR, RData::MR(name))),
+        (name(), name())
+            .prop_map(|(rmailbx, emailbx)| (RecordType::MINFO, RData::MINFO { rmailbx, emailbx })),
+        (name(), name(), any::<[u32; 5]>()).prop_map(|(mname, rname, numbers)| (
+            RecordType::SOA,
+            RData::SOA {
+                mname,
+                rname,
+                serial: numbers[0],
+                refresh: numbers[1],
+                retry: numbers[2],
+                expire: numbers[3],
+                minimum: numbers[4],
+            }
+        )),
+        (any::<u16>(), name()).prop_map(|(preference, exchange)| (
+            RecordType::MX,
+            RData::MX {
+                preference,
+                exchange
+            }
+        )),
+        vec(vec(any::<u8>(), 0..=255), 1..4)
+            .prop_map(|strings| (RecordType::TXT, RData::TXT(strings))),
+        (any::<[u16; 3]>(), name()).prop_map(|(numbers, target)| (
+            RecordType::SRV,
+            RData::SRV {
+                priority: numbers[0],
+                weight: numbers[1],
+                port: numbers[2],
+                target,
+            }
+        )),
+        (any::<(u16, u8, u8)>(), vec(any::<u8>(), 1..64)).prop_map(
+            |((flags, protocol, algorithm), public_key)| (
+                RecordType::DNSKEY,
+                RData::DNSKEY {
+                    flags,
+                    protocol,
+                    algorithm,
+                    public_key,
+                }
+            )
+        ),
+        (
+            record_type(),
+            any::<(u8, u8, u32, u32, u32, u16)>(),
+            name(),
+            vec(any::<u8>(), 1..64)
+        )
+            .prop_map(
+                |(
+                    type_covered,
+                    (algorithm, labels, original_ttl, expiration, inception, key_tag),
+                    signer_name,
+                    signature,
+                )| (
+                    RecordType::RRSIG,
+                    RData::RRSIG {
+                        type_covered,
+                        algorithm,
+                        labels,
+                        original_ttl,
+                        expiration,
+                        inception,
+                        key_tag,
+                        signer_name,
+                        signature,
+                    }
+                )
+            ),
+        (any::<(u16, u8, u8)>(), vec(any::<u8>(), 1..48)).prop_map(
+            |((key_tag, algorithm, digest_type), digest)| (
+                RecordType::DS,
+                RData::DS {
+                    key_tag,
+                    algorithm,
+                    digest_type,
+                    digest,
+                }
+            )
+        ),
+        (name(), type_bitmap()).prop_map(|(next_domain_name, types)| (
+            RecordType::NSEC,
+            RData::NSEC {
+                next_domain_name,
+                types,
+            }
+        )),
+        (
+            any::<(u8, u8, u16)>(),
+            vec(any::<u8>(), 0..16),
+            vec(any::<u8>(), 1..32),
+            type_bitmap()
+        )
+            .prop_map(
+                |((hash_algorithm, flags, iterations), salt, next_hashed_owner, types)| (
+                    RecordType::NSEC3,
+                    RData::NSEC3 {
+                        hash_algorithm,
+                        flags,
+                        iterations,
+                        salt,
+                        next_hashed_owner,
+                        types,
+                    }
+                )
+            ),
+        (any::<(u8, u8, u16)>(), vec(any::<u8>(), 0..16)).prop_map(
+            |((hash_algorithm, flags, iterations), salt)| (
+                RecordType::NSEC3PARAM,
+                RData::NSEC3PARAM {
+                    hash_algorithm,
+                    flags,
+                    iterations,
+                    salt,
+                }
+            )
+        ),
+        (
+            name(),
+            0..1u64 << 48,
+            any::<(u16, u16, u16)>(),
+            vec(any::<u8>(), 0..64),
+            vec(any::<u8>(), 0..8)
+        )
+            .prop_map(
+                |(algorithm, time_signed, (fudge, original_id, error), mac, other_data)| (
+                    RecordType::TSIG,
+                    RData::TSIG {
+                        algorithm,
+                        time_signed,
+                        fudge,
+                        mac,
+                        original_id,
+                        error,
+                        other_data,
+                    }
+                )
+            ),
+        // Types we carry without interpreting their RDATA.
+        (
+            prop_oneof![
+                Just(RecordType::NULL),
+                Just(RecordType::HINFO),
+                Just(RecordType::WKS),
+                unknown_record_type()
+            ],
+            vec(any::<u8>(), 0..64)
+        )
+            .prop_map(|(record_type, data)| (record_type, RData::Unknown(data))),
+    ]
+}
+
+// Type bitmaps are written in ascending order without duplicates.
+fn type_bitmap() -> impl Strategy<Value = Vec<u16>> {
+    btree_set(any::<u16>(), 0..8).prop_map(|types| types.into_iter().collect())
+}
+
+fn class() -> impl Strategy<Value = Class> {
+    prop_oneof![
+        Just(Class::IN),
+        Just(Class::CS),
+        Just(Class::CH),
+        Just(Class::HS),
+        Just(Class::NONE),
+        Just(Class::ANY),
+        any::<u16>().prop_map(Class::from_u16)
+    ]
+}
+
+fn record_type() -> impl Strategy<Value = RecordType> {
+    prop_oneof![
+        Just(RecordType::A),
+        Just(RecordType::NS),
+        Just(RecordType::CNAME),
+        Just(RecordType::SOA),
+        Just(RecordType::PTR),
+        Just(RecordType::MX),
+        Just(RecordType::TXT),
+        Just(RecordType::AAAA),
+        Just(RecordType::SRV),
+        Just(RecordType::DS),
+        Just(RecordType::RRSIG),
+        Just(RecordType::DNSKEY),
+        Just(RecordType::IXFR),
+        Just(RecordType::AXFR),
+        Just(RecordType::TSIG),
+        Just(RecordType::ANY),
+        unknown_record_type(),
+    ]
+}
+
+// Types we have no name for, such as 65 (HTTPS) and 257 (CAA).
+fn unknown_record_type() -> impl Strategy<Value = RecordType> {
+    any::<u16>()
+        .prop_map(RecordType::from_u16)
+        .prop_filter("known type", |record_type| {
+            matches!(record_type, RecordType::Unknown(_))
+        })
+}
+
+// Headers have private fields, so they are made from any twelve bytes.
+fn dns_header() -> impl Strategy<Value = DnsHeader> {
+    any::<[u8; 12]>().prop_map(|bytes| DnsHeader::decode(&bytes).unwrap())
+}
+
+fn dns_question() -> impl Strategy<Value = DnsQuestion> {
+    (name(), record_type(), class()).prop_map(|(labels, record_type, class)| DnsQuestion {
+        labels,
+        record_type,
+        class,
+    })
+}
+
+fn dns_answer() -> impl Strategy<Value = DnsAnswer> {
+    (name(), record_type_and_rdata(), class(), any::<u32>()).prop_map(
+        |(name, (record_type, rdata), class, time_to_live)| {
+            DnsAnswer::new(name, record_type, class, time_to_live, rdata)
+        },
+    )
+}
+
+fn edns() -> impl Strategy<Value = Edns> {
+    (
+        any::<(u16, u8, u8, bool)>(),
+        0..0x8000u16,
+        vec((any::<u16>(), vec(any::<u8>(), 0..16)), 0..3),
+    )
+        .prop_map(
+            |((udp_payload_size, extended_rcode, version, dnssec_ok), z, options)| Edns {
+                udp_payload_size,
+                extended_rcode,
+                version,
+                dnssec_ok,
+                z,
+                options: options
+                    .into_iter()
+                    .map(|(code, data)| EdnsOption { code, data })
+                    .collect(),
+            },
+        )
+}
+
+fn dns_packet() -> impl Strategy<Value = DnsPacket> {
+    (
+        dns_header(),
+        vec(dns_question(), 0..3),
+        vec(dns_answer(), 0..4),
+        vec(dns_answer(), 0..3),
+        vec(dns_answer(), 0..3),
+        proptest::option::of(edns()),
+    )
+        .prop_map(
+            |(
+                mut dns_header,
+                dns_questions,
+                dns_answers,
+                dns_authorities,
+                dns_additionals,
+                edns,
+            )| {
+                // The encoding writes the counts from the sections.
+                dns_header.question_count = dns_questions.len() as u16;
+                dns_header.answer_record_count = dns_answers.len() as u16;
+                dns_header.authority_record_count = dns_authorities.len() as u16;
+                dns_header.additional_record_count =
+                    dns_additionals.len() as u16 + edns.is_some() as u16;
+                DnsPacket {
+                    dns_header,
+                    dns_questions,
+                    dns_answers,
+                    dns_authorities,
+                    dns_additionals,
+                    edns,
+                }
+            },
+        )
+}
+
+proptest! {
+    #[test]
+    fn dns_header_round_trips(bytes in any::<[u8; 12]>()) {
+        let dns_header = DnsHeader::decode(&bytes).unwrap();
+        prop_assert_eq!(dns_header.encode(), bytes.to_vec());
+        prop_assert_eq!(DnsHeader::decode(&dns_header.encode()).unwrap(), dns_header);
+    }
+
+    #[test]
+    fn dns_question_round_trips(dns_question in dns_question()) {
+        let mut encoded = vec![];
+        dns_question.encode_into(&mut encoded, &mut Default::default());
+        prop_assert_eq!(DnsQuestion::decode(&encoded).unwrap(), dns_question);
+    }
+
+    #[test]
+    fn dns_answer_round_trips(dns_answer in dns_answer()) {
+        prop_assert_eq!(DnsAnswer::decode(&dns_answer.encode()).unwrap(), dns_answer);
+    }
+
+    #[test]
+    fn dns_packet_round_trips(dns_packet in dns_packet()) {
+        prop_assert_eq!(&DnsPacket::decode(&dns_packet.encode()).unwrap(), &dns_packet);
+        prop_assert_eq!(
+            DnsPacket::decode(&dns_packet.encode_with_compression(false)).unwrap(),
+            dns_packet
+        );
+    }
+
+    // TSIG is only ever shown as dig does, having no master file form.
+    #[test]
+    fn presentation_format_round_trips(
+        dns_answer in dns_answer().prop_filter("TSIG", |dns_answer| {
+            dns_answer.record_type != RecordType::TSIG
+        })
+    ) {
+        prop_assert_eq!(dns_answer.to_string().parse::<DnsAnswer>(), Ok(dns_answer));
+    }
+
+    #[test]
+    fn decoding_arbitrary_bytes_does_not_panic(bytes in vec(any::<u8>(), 0..512)) {
+        let _ = DnsPacket::decode(&bytes);
+    }
+
+    // A header claiming one question followed by random bytes, so the name
+    // is mostly made of pointers going anywhere.
+    #[test]
+    fn decoding_arbitrary_names_does_not_panic(bytes in vec(any::<u8>(), 0..64)) {
+        let mut message = vec![0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0];
+        message.extend(bytes);
+        let _ = DnsPacket::decode(&message);
+    }
+}
+
+#[test]
+fn compression_pointer_to_itself_is_rejected() {
+    let mut message = vec![0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0];
+    message.extend([0xc0, 12, 0, 1, 0, 1]);
+    assert_eq!(
+        DnsPacket::decode(&message).unwrap_err(),
+        DnsError::PointerLoop { offset: 12 }
+    );
+}
+
+#[test]
+fn compression_pointer_past_the_message_is_rejected() {
+    let mut message = vec![0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0];
+    message.extend([0xff, 0xff, 0, 1, 0, 1]);
+    assert!(DnsPacket::decode(&message).is_err());
+}
+
+fn encode_name(name: &str, buffer: &mut Vec<u8>, compression: &mut Compression) {
+    compression.encode_name(&parse_name(name, None).unwrap(), buffer);
+}
+
+#[test]
+fn names_point_at_the_longest_suffix_already_written() {
+    let mut compression = Compression::new(true);
+    // As if after a twelve byte header.
+    let mut buffer = vec![0; 12];
+    encode_name("www.example.com.", &mut buffer, &mut compression);
+    assert_eq!(buffer[12..], *b"\x03www\x07example\x03com\x00");
+
+    let start = buffer.len();
+    encode_name("mail.example.com.", &mut buffer, &mut compression);
+    assert_eq!(buffer[start..], *b"\x04mail\xc0\x10");
+
+    let start = buffer.len();
+    encode_name("www.example.com.", &mut buffer, &mut compression);
+    assert_eq!(buffer[start..], [0xc0, 12]);
+
+    // The suffix written as part of the second name is reused too.
+    let start = buffer.len();
+    encode_name("smtp.mail.example.com.", &mut buffer, &mut compression);
+    assert_eq!(buffer[start..], *b"\x04smtp\xc0\x1d");
+
+    let start = buffer.len();
+    encode_name("example.org.", &mut buffer, &mut compression);
+    assert_eq!(buffer[start..], *b"\x07example\x03org\x00");
+}
+
+#[test]
+fn names_past_the_reach_of_a_pointer_are_not_pointed_at() {
+    let mut compression = Compression::new(true);
+    let mut buffer = vec![0; 0x4000];
+    encode_name("example.com.", &mut buffer, &mut compression);
+    let start = buffer.len();
+    encode_name("example.com.", &mut buffer, &mut compression);
+    assert_eq!(buffer[start..], *b"\x07example\x03com\x00");
+}
+
+#[test]
+fn disabled_compression_writes_every_name_in_full() {
+    let mut compression = Compression::new(false);
+    let mut buffer = vec![0; 12];
+    encode_name("example.com.", &mut buffer, &mut compression);
+    encode_name("example.com.", &mut buffer, &mut compression);
+    assert_eq!(
+        buffer[12..],
+        *b"\x07example\x03com\x00\x07example\x03com\x00"
+    );
+}
+
+#[test]
+fn records_of_a_reply_point_back_into_the_question() {
+    let dns_packet = DnsPacket {
+        dns_header: DnsHeader::new_query(0x1234, true),
+        dns_questions: vec![DnsQuestion {
+            labels: parse_name("www.example.com.", None).unwrap(),
+            record_type: RecordType::CNAME,
+            class: Class::IN,
+        }],
+        dns_answers: vec!["www.example.com. 300 IN CNAME web.example.com."
+            .parse()
+            .unwrap()],
+        dns_authorities: vec![],
+        dns_additionals: vec![],
+        edns: None,
+    };
+    let encoded = dns_packet.encode();
+    let question_end = 12 + 17 + 4;
+    // The owner is the question name and the CNAME target shares its
+    // example.com. suffix, which starts four bytes into it.
+    assert_eq!(encoded[question_end..question_end + 2], [0xc0, 12]);
+    assert_eq!(encoded[encoded.len() - 6..], *b"\x03web\xc0\x10");
+    assert_eq!(
+        dns_packet.encode_with_compression(false).len() - encoded.len(),
+        (17 - 2) + (17 - 6)
+    );
+    let decoded = DnsPacket::decode(&encoded).unwrap();
+    assert_eq!(decoded.dns_questions, dns_packet.dns_questions);
+    assert_eq!(decoded.dns_answers, dns_packet.dns_answers);
+}
+
+// The names of the old RFC 1035 mail types may be compressed, so they are
+// read like those of CNAMEs rather than kept as bytes pointing into a
+// message they will not be forwarded in.
+// Source: https://www.rfc-editor.org/rfc/rfc3597#section-4
+#[test]
+fn names_in_old_mail_types_are_decompressed() {
+    let dns_answers: Vec<DnsAnswer> = [
+        "lists.example. 300 IN MINFO owner.lists.example. errors.lists.example.",
+        "lists.example. 300 IN MG member.example.",
+        "lists.example. 300 IN MD mail.example.",
+    ]
+    .iter()
+    .map(|record| record.parse().unwrap())
+    .collect();
+    let dns_packet = DnsPacket {
+        dns_header: DnsHeader::new_query(0x1234, true),
+        dns_questions: vec![DnsQuestion {
+            labels: parse_name("lists.example.", None).unwrap(),
+            record_type: RecordType::ANY,
+            class: Class::IN,
+        }],
+        dns_answers,
+        dns_authorities: vec![],
+        dns_additionals: vec![],
+        edns: None,
+    };
+    let encoded = dns_packet.encode();
+    assert!(encoded.len() < dns_packet.encode_with_compression(false).len());
+    let decoded = DnsPacket::decode(&encoded).unwrap();
+    assert_eq!(decoded.dns_answers, dns_packet.dns_answers);
+    assert_eq!(
+        decoded.dns_answers[0].rdata,
+        RData::MINFO {
+            rmailbx: parse_name("owner.lists.example.", None).unwrap(),
+            emailbx: parse_name("errors.lists.example.", None).unwrap(),
+        }
+    );
+    assert_eq!(
+        decoded.dns_answers[0].to_string(),
+        "lists.example. 300 IN MINFO owner.lists.example. errors.lists.example."
+    );
+}
+
+// A reply to an HTTPS query, with the answer's RDATA kept as it came.
+// Source: https://www.rfc-editor.org/rfc/rfc3597#section-4
+#[test]
+fn records_of_unknown_types_are_carried_along() {
+    let mut message = vec![0x12, 0x34, 0x81, 0x80, 0, 1, 0, 1, 0, 0, 0, 0];
+    message.extend([7, b'e', b'x', b'a', b'm', b'p', b'l', b'e', 0, 0, 65, 0, 1]);
+    message.extend([0xc0, 12, 0, 65, 0, 1, 0, 0, 0x0e, 0x10, 0, 3, 0, 1, 0]);
+    let dns_packet = DnsPacket::decode(&message).unwrap();
+    assert_eq!(
+        dns_packet.dns_questions[0].record_type,
+        RecordType::Unknown(65)
+    );
+    assert_eq!(
+        dns_packet.dns_answers[0].rdata,
+        RData::Unknown(vec![0, 1, 0])
+    );
+    assert_eq!(dns_packet.encode(), message);
+    assert_eq!(
+        dns_packet.dns_answers[0].to_string(),
+        "example. 3600 IN TYPE65 \\# 3 000100"
+    );
+}
+
+#[test]
+fn unknown_types_are_named_by_number() {
+    assert_eq!("TYPE257".parse(), Ok(RecordType::Unknown(257)));
+    assert_eq!("type1".parse(), Ok(RecordType::A));
+    assert!("TYPE65536".parse::<RecordType>().is_err());
+    let rrsig: DnsAnswer = "example. 300 IN RRSIG TYPE257 13 1 300 20300101000000 \
+        20240101000000 12345 example. AAAA"
+        .parse()
+        .unwrap();
+    assert_eq!(DnsAnswer::decode(&rrsig.encode()).unwrap(), rrsig);
+    assert!(rrsig.to_string().contains(" RRSIG TYPE257 "));
+}
+
+// Labels are bytes, not text, and only their length is limited.
+// Source: https://www.rfc-editor.org/rfc/rfc2181#section-11
+#[test]
+fn labels_that_are_not_utf8_are_carried_along() {
+    let mut message = vec![0x12, 0x34, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0];
+    message.extend([
+        3, 0xff, b'.', 0xc3, 7, b'e', b'x', b'a', b'm', b'p', b'l', b'e', 0, 0, 1, 0, 1,
+    ]);
+    let dns_packet = DnsPacket::decode(&message).unwrap();
+    let labels = &dns_packet.dns_questions[0].labels;
+    assert_eq!(labels[0].as_bytes(), &[0xff, b'.', 0xc3]);
+    assert_eq!(dns_packet.encode(), message);
+    assert_eq!(Label::to_key(labels), "\\255\\.\\195.example");
+    assert_eq!(&parse_name("\\255\\.\\195.example.", None).unwrap(), labels);
+}
+
+#[test]
+fn names_too_long_for_the_wire_are_rejected() {
+    assert!(Label::from_string("a".repeat(63)).is_ok());
+    assert!(Label::from_string("a".repeat(64)).is_err());
+    assert!(Label::from_bytes(vec![]).is_err());
+    let label = "a".repeat(63);
+    let name = [label.as_str(); 4].join(".");
+    assert!(Label::from_domain_name(&name[..253]).is_ok());
+    assert!(Label::from_domain_name(&name).is_err());
+    assert!(Label::from_domain_name(&format!("{}a.example", label)).is_err());
+}
+
+// Source: https://www.rfc-editor.org/rfc/rfc3597#section-5
+#[test]
+fn records_of_unknown_classes_are_carried_along() {
+    let mut message = vec![0x12, 0x34, 0x81, 0x80, 0, 1, 0, 1, 0, 0, 0, 0];
+    message.extend([7, b'e', b'x', b'a', b'm', b'p', b'l', b'e', 0, 0, 1, 0, 32]);
+    message.extend([0xc0, 12, 0, 1, 0, 32, 0, 0, 0x0e, 0x10, 0, 4, 192, 0, 2, 1]);
+    let dns_packet = DnsPacket::decode(&message).unwrap();
+    assert_eq!(dns_packet.dns_questions[0].class, Class::Unknown(32));
+    assert_eq!(dns_packet.encode(), message);
+    assert_eq!(
+        dns_packet.dns_answers[0].to_string(),
+        "example. 3600 CLASS32 A 192.0.2.1"
+    );
+    assert_eq!("CLASS32".parse(), Ok(Class::Unknown(32)));
+    assert_eq!("class1".parse(), Ok(Class::IN));
+    assert!("CLASS65536".parse::<Class>().is_err());
+}
+
+#[test]
+fn merging_replies_to_split_questions() {
+    let dns_request = DnsPacket {
+        dns_header: DnsHeader::new_query(0x1234, true),
+        dns_questions: ["a.example.", "b.example."]
+            .iter()
+            .map(|name| DnsQuestion {
+                labels: parse_name(name, None).unwrap(),
+                record_type: RecordType::A,
+                class: Class::IN,
+            })
+            .collect(),
+        dns_answers: vec![],
+        dns_authorities: vec![],
+        dns_additionals: vec![],
+        edns: None,
+    };
+    let name_server: DnsAnswer = "example. 300 IN NS ns.example.".parse().unwrap();
+    let replies: Vec<DnsPacket> = dns_request
+        .split()
+        .into_iter()
+        .map(|mut reply| {
+            reply.dns_authorities.push(name_server.clone());
+            reply
+        })
+        .collect();
+    let merged = DnsPacket::merge(replies).unwrap();
+    assert_eq!(merged.dns_questions, dns_request.dns_questions);
+    assert_eq!(merged.dns_header.question_count, 2);
+    // The NS record both replies carried is passed on once.
+    assert_eq!(merged.dns_authorities, vec![name_server]);
+
+    assert_eq!(DnsPacket::merge(vec![]), None);
+}
+
+#[test]
+fn merged_replies_keep_the_first_error() {
+    let reply = |name: &str, response_code: ResponseCode| {
+        let mut dns_packet = DnsPacket {
+            dns_header: DnsHeader::new_query(0x1234, true),
+            dns_questions: vec![DnsQuestion {
+                labels: parse_name(name, None).unwrap(),
+                record_type: RecordType::A,
+                class: Class::IN,
+            }],
+            dns_answers: vec![],
+            dns_authorities: vec![],
+            dns_additionals: vec![],
+            edns: None,
+        };
+        dns_packet.set_response_code(response_code as u16);
+        dns_packet
+    };
+    let merged = DnsPacket::merge(vec![
+        reply("a.example.", ResponseCode::NoError),
+        reply("b.example.", ResponseCode::NameError),
+        reply("c.example.", ResponseCode::ServerFailure),
+    ])
+    .unwrap();
+    assert_eq!(merged.response_code(), ResponseCode::NameError as u16);
+    assert_eq!(merged.dns_questions.len(), 3);
+
+    let merged = DnsPacket::merge(vec![
+        reply("a.example.", ResponseCode::NoError),
+        reply("b.example.", ResponseCode::NoError),
+    ])
+    .unwrap();
+    assert_eq!(merged.response_code(), ResponseCode::NoError as u16);
+
+    // BADVERS cannot be told without an OPT record to hold its upper bits.
+    let mut extended = reply("b.example.", ResponseCode::NoError);
+    extended.edns = Some(Edns::new(1232));
+    extended.set_response_code(16);
+    let merged =
+        DnsPacket::merge(vec![reply("a.example.", ResponseCode::NoError), extended]).unwrap();
+    assert_eq!(merged.response_code(), ResponseCode::ServerFailure as u16);
+}
@@ -0,0 +1,354 @@
+// Dynamic updates against a small zone, and the TSIG signatures they have
+// to carry.
+
+use dns_starter_rust::adapters::zone_file;
+use dns_starter_rust::models::presentation::parse_name;
+use dns_starter_rust::models::tsig::{self, Key, Verification};
+use dns_starter_rust::models::update::update;
+use dns_starter_rust::models::{
+    Class, DnsAnswer, DnsHeader, DnsPacket, DnsQuestion, RData, RecordType, ResponseCode, Zone,
+};
+use dns_starter_rust::traits::{Decodable, Encodable};
+
+const ZONE: &str = "\
+example. 3600 IN SOA ns1.example. hostmaster.example. 10 7200 3600 1209600 300
+example. 3600 IN NS ns1.example.
+example. 3600 IN NS ns2.example.
+ns1.example. 3600 IN A 192.0.2.1
+ns2.example. 3600 IN A 192.0.2.2
+www.example. 3600 IN A 192.0.2.80
+www.example. 3600 IN A 192.0.2.81
+alias.example. 3600 IN CNAME www.example.
+";
+
+// 2026-01-01.
+const NOW: u64 = 1_767_225_600;
+
+fn zone() -> Zone {
+    zone_file::parse_zone(ZONE).unwrap()
+}
+
+fn record(text: &str) -> DnsAnswer {
+    text.parse().unwrap()
+}
+
+// A prerequisite or update of class ANY or NONE, which has no RDATA.
+fn empty(name: &str, record_type: RecordType, class: Class) -> DnsAnswer {
+    DnsAnswer::new(
+        parse_name(name, None).unwrap(),
+        record_type,
+        class,
+        0,
+        RData::Unknown(vec![]),
+    )
+}
+
+// The zone after the update, checking the difference it made goes from the
+// old serial to the new one.
+fn apply_update(zone: &Zone, prerequisites: &[DnsAnswer], updates: &[DnsAnswer]) -> Zone {
+    let difference = update(zone, prerequisites, updates)
+        .unwrap()
+        .expect("update changed nothing");
+    assert_eq!(difference.old_serial(), zone.serial().unwrap());
+    let mut updated = zone.clone();
+    updated.apply(difference).unwrap();
+    updated
+}
+
+fn rrset(zone: &Zone, name: &str, record_type: RecordType) -> Vec<String> {
+    zone.records_at(&parse_name(name, None).unwrap())
+        .iter()
+        .filter(|record| record.record_type == record_type)
+        .map(|record| record.to_string())
+        .collect()
+}
+
+fn key() -> Key {
+    "hmac-sha256:update-key.:MDEyMzQ1Njc4OWFiY2RlZjAxMjM0NTY3ODlhYmNkZWY="
+        .parse()
+        .unwrap()
+}
+
+// An update adding new.example. A 192.0.2.7, built and signed with the key
+// above at NOW by a separate HMAC implementation.
+const SIGNED_UPDATE: &str = "\
+123428000001000000010001076578616d706c650000060001036e6577076578616d706c650000010001\
+0000012c0004c00002070a7570646174652d6b65790000fa00ff00000000003d0b686d61632d73686132\
+35360000006955b900012c002099b0568f9efdbec2dc3716225a44ed24109c2fe5f69ae1ab5863d589cf\
+3421e0123400000000";
+
+fn update_request() -> Vec<u8> {
+    DnsPacket {
+        dns_header: DnsHeader::new_query(0x4321, false),
+        dns_questions: vec![DnsQuestion {
+            labels: parse_name("example.", None).unwrap(),
+            record_type: RecordType::SOA,
+            class: Class::IN,
+        }],
+        dns_answers: vec![],
+        dns_authorities: vec![record("new.example. 300 IN A 192.0.2.7")],
+        dns_additionals: vec![],
+        edns: None,
+    }
+    .encode()
+}
+
+#[test]
+fn adding_a_record_bumps_the_serial() {
+    let zone = zone();
+    let difference = update(&zone, &[], &[record("new.example. 300 IN A 192.0.2.7")])
+        .unwrap()
+        .unwrap();
+    assert_eq!(difference.new_serial(), 11);
+    assert!(difference.deleted.is_empty());
+    assert_eq!(
+        difference.added,
+        vec![record("new.example. 300 IN A 192.0.2.7")]
+    );
+}
+
+#[test]
+fn update_that_changes_nothing_gives_no_difference() {
+    let zone = zone();
+    let updates = [
+        record("www.example. 3600 IN A 192.0.2.80"),
+        empty("missing.example.", RecordType::A, Class::ANY),
+    ];
+    assert_eq!(update(&zone, &[], &updates), Ok(None));
+}
+
+#[test]
+fn newer_soa_replaces_the_serial() {
+    let zone = zone();
+    let soa = "example. 3600 IN SOA ns1.example. hostmaster.example. 42 7200 3600 1209600 300";
+    let updated = apply_update(&zone, &[], &[record(soa)]);
+    assert_eq!(updated.serial(), Some(42));
+
+    // An older serial is ignored, so nothing changes.
+    let soa = "example. 3600 IN SOA ns1.example. hostmaster.example. 9 7200 3600 1209600 300";
+    assert_eq!(update(&zone, &[], &[record(soa)]), Ok(None));
+}
+
+#[test]
+fn deleting_rrsets_and_records() {
+    let zone = zone();
+    let updated = apply_update(&zone, &[], &[record("www.example. 0 NONE A 192.0.2.81")]);
+    assert_eq!(
+        rrset(&updated, "www.example.", RecordType::A),
+        vec!["www.example. 3600 IN A 192.0.2.80"]
+    );
+
+    let updated = apply_update(
+        &zone,
+        &[],
+        &[empty("www.example.", RecordType::A, Class::ANY)],
+    );
+    assert!(rrset(&updated, "www.example.", RecordType::A).is_empty());
+
+    let updated = apply_update(
+        &zone,
+        &[],
+        &[empty("alias.example.", RecordType::ANY, Class::ANY)],
+    );
+    assert!(updated
+        .records_at(&parse_name("alias.example.", None).unwrap())
+        .is_empty());
+}
+
+#[test]
+fn apex_soa_and_last_name_server_are_kept() {
+    let zone = zone();
+    let updated = apply_update(
+        &zone,
+        &[],
+        &[
+            empty("example.", RecordType::ANY, Class::ANY),
+            record("example. 0 NONE NS ns1.example."),
+            record("example. 0 NONE NS ns2.example."),
+        ],
+    );
+    assert_eq!(rrset(&updated, "example.", RecordType::SOA).len(), 1);
+    assert_eq!(
+        rrset(&updated, "example.", RecordType::NS),
+        vec!["example. 3600 IN NS ns2.example."]
+    );
+}
+
+#[test]
+fn cname_does_not_share_its_name() {
+    let zone = zone();
+    assert_eq!(
+        update(&zone, &[], &[record("alias.example. 300 IN A 192.0.2.9")]),
+        Ok(None)
+    );
+    assert_eq!(
+        update(
+            &zone,
+            &[],
+            &[record("www.example. 300 IN CNAME ns1.example.")]
+        ),
+        Ok(None)
+    );
+    let updated = apply_update(
+        &zone,
+        &[],
+        &[record("alias.example. 300 IN CNAME ns1.example.")],
+    );
+    assert_eq!(
+        rrset(&updated, "alias.example.", RecordType::CNAME),
+        vec!["alias.example. 300 IN CNAME ns1.example."]
+    );
+}
+
+#[test]
+fn prerequisites() {
+    let zone = zone();
+    let add = [record("new.example. 300 IN A 192.0.2.7")];
+    let check = |prerequisite: DnsAnswer| update(&zone, &[prerequisite], &add).map(|_| ());
+
+    assert_eq!(
+        check(empty("www.example.", RecordType::ANY, Class::ANY)),
+        Ok(())
+    );
+    assert_eq!(
+        check(empty("new.example.", RecordType::ANY, Class::ANY)),
+        Err(ResponseCode::NameError)
+    );
+    assert_eq!(
+        check(empty("www.example.", RecordType::A, Class::ANY)),
+        Ok(())
+    );
+    assert_eq!(
+        check(empty("www.example.", RecordType::TXT, Class::ANY)),
+        Err(ResponseCode::NxRrSet)
+    );
+    assert_eq!(
+        check(empty("www.example.", RecordType::ANY, Class::NONE)),
+        Err(ResponseCode::YxDomain)
+    );
+    assert_eq!(
+        check(empty("www.example.", RecordType::A, Class::NONE)),
+        Err(ResponseCode::YxRrSet)
+    );
+    assert_eq!(
+        check(empty("www.example.org.", RecordType::ANY, Class::ANY)),
+        Err(ResponseCode::NotZone)
+    );
+    assert_eq!(
+        check(record("www.example. 60 ANY A 192.0.2.80")),
+        Err(ResponseCode::FormatError)
+    );
+
+    // An RRset prerequisite has to match the whole RRset.
+    let whole = [
+        record("www.example. 0 IN A 192.0.2.80"),
+        record("www.example. 0 IN A 192.0.2.81"),
+    ];
+    assert!(update(&zone, &whole, &add).is_ok());
+    assert_eq!(update(&zone, &whole[..1], &add), Err(ResponseCode::NxRrSet));
+}
+
+#[test]
+fn bad_update_leaves_the_zone_alone() {
+    let zone = zone();
+    let updates = [
+        record("new.example. 300 IN A 192.0.2.7"),
+        record("new.example.org. 300 IN A 192.0.2.7"),
+    ];
+    assert_eq!(update(&zone, &[], &updates), Err(ResponseCode::NotZone));
+    let updates = [
+        record("new.example. 300 IN A 192.0.2.7"),
+        record("www.example. 300 ANY A 192.0.2.80"),
+    ];
+    assert_eq!(update(&zone, &[], &updates), Err(ResponseCode::FormatError));
+}
+
+#[test]
+fn verifies_an_independently_signed_update() {
+    let message = data_encoding::HEXLOWER
+        .decode(SIGNED_UPDATE.as_bytes())
+        .unwrap();
+    let signer = match tsig::verify(&message, &[key()], NOW + 10).unwrap() {
+        Verification::Verified(signer) => signer,
+        verification => panic!("{:?}", verification),
+    };
+    assert_eq!(signer.error, 0);
+
+    // The parts before the TSIG decode as the update it signs.
+    let dns_packet = DnsPacket::decode(&message).unwrap();
+    assert_eq!(
+        dns_packet.dns_authorities,
+        vec![record("new.example. 300 IN A 192.0.2.7")]
+    );
+
+    let mut tampered = message.clone();
+    tampered[message.len() - 100] ^= 1;
+    assert!(matches!(
+        tsig::verify(&tampered, &[key()], NOW),
+        Ok(Verification::Failed(signer)) if signer.error == tsig::BAD_SIG
+    ));
+}
+
+#[test]
+fn signed_requests_verify() {
+    let request = update_request();
+    let signed = tsig::sign_request(&request, &key(), NOW);
+    assert!(matches!(
+        tsig::verify(&signed, &[key()], NOW),
+        Ok(Verification::Verified(_))
+    ));
+    assert!(matches!(
+        tsig::verify(&request, &[key()], NOW),
+        Ok(Verification::Unsigned)
+    ));
+
+    let other_key: Key = "update-key.:c2VjcmV0".parse().unwrap();
+    assert!(matches!(
+        tsig::verify(&signed, &[other_key], NOW),
+        Ok(Verification::Failed(signer)) if signer.error == tsig::BAD_SIG
+    ));
+    let unknown_key: Key = "other-key.:c2VjcmV0".parse().unwrap();
+    assert!(matches!(
+        tsig::verify(&signed, &[unknown_key], NOW),
+        Ok(Verification::Failed(signer)) if signer.error == tsig::BAD_KEY
+    ));
+}
+
+#[test]
+fn key_names_must_fit_on_the_wire() {
+    let name = format!("{}.example.", "k".repeat(64));
+    let key = format!("{}:c2VjcmV0", name).parse::<Key>();
+    assert!(key.unwrap_err().starts_with("Invalid name for key"));
+}
+
+#[test]
+fn signatures_outside_the_fudge_are_too_old() {
+    let signed = tsig::sign_request(&update_request(), &key(), NOW);
+    let signer = match tsig::verify(&signed, &[key()], NOW + 301).unwrap() {
+        Verification::Failed(signer) => signer,
+        verification => panic!("{:?}", verification),
+    };
+    assert_eq!(signer.error, tsig::BAD_TIME);
+
+    // The BADTIME response is still signed, with our time in it.
+    let response = signer.sign(&update_request(), NOW + 301);
+    let dns_packet = DnsPacket::decode(&response).unwrap();
+    match &dns_packet.dns_additionals[..] {
+        [tsig] => match &tsig.rdata {
+            RData::TSIG {
+                time_signed,
+                mac,
+                error,
+                other_data,
+                ..
+            } => {
+                assert_eq!(*time_signed, NOW);
+                assert_eq!(mac.len(), 32);
+                assert_eq!(*error, tsig::BAD_TIME);
+                assert_eq!(other_data[..], (NOW + 301).to_be_bytes()[2..]);
+            }
+            rdata => panic!("{:?}", rdata),
+        },
+        records => panic!("{:?}", records),
+    }
+}
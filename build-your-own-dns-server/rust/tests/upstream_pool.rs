@@ -0,0 +1,127 @@
+// How the upstream pool orders its upstreams, takes failing ones out of
+// rotation and brings them back.
+
+use dns_starter_rust::adapters::upstream_pool::{Strategy, UpstreamPool};
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+fn addresses() -> [SocketAddr; 3] {
+    [
+        "192.0.2.1:53".parse().unwrap(),
+        "192.0.2.2:53".parse().unwrap(),
+        "192.0.2.3:53".parse().unwrap(),
+    ]
+}
+
+fn pool(strategy: Strategy) -> UpstreamPool {
+    UpstreamPool::new(addresses().to_vec(), strategy)
+}
+
+#[test]
+fn three_failures_in_a_row_eject_an_upstream() {
+    let pool = pool(Strategy::Failover);
+    let [first, second, third] = addresses();
+    let now = Instant::now();
+    pool.record_failure(first, now);
+    pool.record_failure(first, now);
+    assert_eq!(pool.candidates(now), [first, second, third]);
+    // Ejected upstreams are still tried, but only after the healthy ones.
+    pool.record_failure(first, now);
+    assert_eq!(pool.candidates(now), [second, third, first]);
+}
+
+#[test]
+fn a_success_resets_the_failure_count() {
+    let pool = pool(Strategy::Failover);
+    let [first, second, third] = addresses();
+    let now = Instant::now();
+    pool.record_failure(first, now);
+    pool.record_failure(first, now);
+    pool.record_success(first, Duration::from_millis(10));
+    pool.record_failure(first, now);
+    pool.record_failure(first, now);
+    assert_eq!(pool.candidates(now), [first, second, third]);
+}
+
+#[test]
+fn ejected_upstreams_recover() {
+    let pool = pool(Strategy::Failover);
+    let [first, second, third] = addresses();
+    let start = Instant::now();
+    for _ in 0..3 {
+        pool.record_failure(first, start);
+    }
+    assert_eq!(
+        pool.candidates(start + Duration::from_secs(29)),
+        [second, third, first]
+    );
+    // Back once the ejection period is over...
+    assert_eq!(
+        pool.candidates(start + Duration::from_secs(30)),
+        [first, second, third]
+    );
+
+    // ...or as soon as a health check gets a reply.
+    let later = start + Duration::from_secs(30);
+    for _ in 0..3 {
+        pool.record_failure(second, later);
+    }
+    assert_eq!(pool.candidates(later), [first, third, second]);
+    pool.record_success(second, Duration::from_millis(10));
+    assert_eq!(pool.candidates(later), [first, second, third]);
+}
+
+#[test]
+fn every_upstream_ejected_still_leaves_candidates() {
+    let pool = pool(Strategy::Failover);
+    let now = Instant::now();
+    for address in addresses() {
+        for _ in 0..3 {
+            pool.record_failure(address, now);
+        }
+    }
+    assert_eq!(pool.candidates(now), addresses());
+}
+
+#[test]
+fn round_robin_skips_ejected_upstreams() {
+    let pool = pool(Strategy::RoundRobin);
+    let [first, second, third] = addresses();
+    let now = Instant::now();
+    assert_eq!(pool.candidates(now), [first, second, third]);
+    assert_eq!(pool.candidates(now), [second, third, first]);
+    for _ in 0..3 {
+        pool.record_failure(second, now);
+    }
+    let starts: Vec<SocketAddr> = (0..4).map(|_| pool.candidates(now)[0]).collect();
+    assert!(starts.contains(&first) && starts.contains(&third));
+    assert!(!starts.contains(&second));
+}
+
+#[test]
+fn fastest_prefers_the_lowest_smoothed_rtt() {
+    let pool = pool(Strategy::Fastest);
+    let [first, second, third] = addresses();
+    let now = Instant::now();
+    pool.record_success(first, Duration::from_millis(80));
+    pool.record_success(second, Duration::from_millis(20));
+    // The third has no measurement yet, so it is tried first to get one.
+    assert_eq!(pool.candidates(now), [third, second, first]);
+    pool.record_success(third, Duration::from_millis(50));
+    assert_eq!(pool.candidates(now), [second, third, first]);
+
+    // A slow reply moves the average an eighth of the way, 20ms to 40ms.
+    pool.record_success(second, Duration::from_millis(180));
+    assert_eq!(pool.candidates(now), [second, third, first]);
+    // And 40ms to 85ms.
+    pool.record_success(second, Duration::from_millis(400));
+    assert_eq!(pool.candidates(now), [third, first, second]);
+}
+
+#[test]
+fn strategies_are_named_as_on_the_command_line() {
+    assert_eq!("failover".parse(), Ok(Strategy::Failover));
+    assert_eq!("Round-Robin".parse(), Ok(Strategy::RoundRobin));
+    assert_eq!("fastest".parse(), Ok(Strategy::Fastest));
+    assert!("random".parse::<Strategy>().is_err());
+}
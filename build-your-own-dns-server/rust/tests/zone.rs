@@ -0,0 +1,202 @@
+// Zones read from master files and the answers they give.
+
+use dns_starter_rust::adapters::zone_file;
+use dns_starter_rust::models::presentation::parse_name;
+use dns_starter_rust::models::{Class, DnsQuestion, RData, RecordType, Zone, ZoneLookup};
+
+const ZONE: &str = "\
+example. 3600 IN SOA ns1.example. hostmaster.example. 1 7200 3600 1209600 300
+example. 3600 IN NS ns1.example.
+ns1.example. 3600 IN A 192.0.2.1
+www.example. 3600 IN A 192.0.2.80
+www.example. 3600 IN AAAA 2001:db8::80
+www.example. 3600 IN TXT \"web\"
+alias.example. 3600 IN CNAME www.example.
+dangling.example. 3600 IN CNAME chained.example.
+chained.example. 3600 IN CNAME missing.example.
+outside.example. 3600 IN CNAME www.example.org.
+a.deep.example. 3600 IN A 192.0.2.5
+";
+
+// Relative names, default TTLs and a multi-line SOA as zone files are
+// usually written by hand.
+const MASTER_FILE: &str = "\
+$ORIGIN example.test.
+$TTL 1h
+@   IN  SOA ns1 hostmaster (
+        2024010101 ; serial
+        3h         ; refresh
+        900        ; retry
+        1w         ; expire
+        300 )      ; minimum
+    IN  NS  ns1
+ns1     A 192.0.2.1
+www 300 A 192.0.2.80
+        A 192.0.2.81
+$TTL 2d
+mail    MX 10 mail.example.org.
+$ORIGIN lab.example.test.
+printer A 192.0.2.9
+@       TXT \"lab (not \\\"parsed\\\")\" ; a comment
+";
+
+fn records(zone: &Zone, name: &str) -> Vec<String> {
+    zone.records_at(&parse_name(name, None).unwrap())
+        .iter()
+        .map(|record| record.to_string())
+        .collect()
+}
+
+fn zone() -> Zone {
+    zone_file::parse_zone(ZONE).unwrap()
+}
+
+// The answer as presentation format lines, or what took its place.
+fn lookup(zone: &Zone, name: &str, record_type: RecordType) -> Result<Vec<String>, String> {
+    let dns_question = DnsQuestion {
+        labels: parse_name(name, None).unwrap(),
+        record_type,
+        class: Class::IN,
+    };
+    match zone.lookup(&dns_question) {
+        ZoneLookup::Answer(answers) => {
+            Ok(answers.iter().map(|answer| answer.to_string()).collect())
+        }
+        ZoneLookup::NoData => Err("NODATA".to_string()),
+        ZoneLookup::NxDomain(chain) if chain.is_empty() => Err("NXDOMAIN".to_string()),
+        ZoneLookup::NxDomain(chain) => Err(format!(
+            "NXDOMAIN after {}",
+            chain
+                .iter()
+                .map(|record| record.to_string())
+                .collect::<Vec<String>>()
+                .join(", ")
+        )),
+        ZoneLookup::Referral { .. } => Err("referral".to_string()),
+    }
+}
+
+#[test]
+fn any_gives_every_rrset_at_the_name() {
+    let zone = zone();
+    assert_eq!(
+        lookup(&zone, "www.example.", RecordType::ANY),
+        Ok(vec![
+            "www.example. 3600 IN A 192.0.2.80".to_string(),
+            "www.example. 3600 IN AAAA 2001:db8::80".to_string(),
+            "www.example. 3600 IN TXT \"web\"".to_string(),
+        ])
+    );
+    // A CNAME is an answer to ANY in itself and is not followed.
+    assert_eq!(
+        lookup(&zone, "alias.example.", RecordType::ANY),
+        Ok(vec!["alias.example. 3600 IN CNAME www.example.".to_string()])
+    );
+    assert_eq!(
+        lookup(&zone, "deep.example.", RecordType::ANY),
+        Err("NODATA".to_string())
+    );
+    assert_eq!(
+        lookup(&zone, "missing.example.", RecordType::ANY),
+        Err("NXDOMAIN".to_string())
+    );
+}
+
+// The CNAME records followed on the way are kept along with the NXDOMAIN
+// of the name they end at.
+#[test]
+fn cname_chains_to_missing_names_are_nxdomain() {
+    let zone = zone();
+    assert_eq!(
+        lookup(&zone, "alias.example.", RecordType::A),
+        Ok(vec![
+            "alias.example. 3600 IN CNAME www.example.".to_string(),
+            "www.example. 3600 IN A 192.0.2.80".to_string(),
+        ])
+    );
+    assert_eq!(
+        lookup(&zone, "dangling.example.", RecordType::A),
+        Err(
+            "NXDOMAIN after dangling.example. 3600 IN CNAME chained.example., \
+            chained.example. 3600 IN CNAME missing.example."
+                .to_string()
+        )
+    );
+    // Names in other zones are left for the client to look up.
+    assert_eq!(
+        lookup(&zone, "outside.example.", RecordType::A),
+        Ok(vec![
+            "outside.example. 3600 IN CNAME www.example.org.".to_string()
+        ])
+    );
+}
+
+#[test]
+fn multi_line_soa_is_one_record() {
+    let zone = zone_file::parse_zone(MASTER_FILE).unwrap();
+    let soa = zone.soa().unwrap();
+    assert_eq!(soa.time_to_live, 3600);
+    assert_eq!(
+        soa.rdata,
+        RData::SOA {
+            mname: parse_name("ns1.example.test.", None).unwrap(),
+            rname: parse_name("hostmaster.example.test.", None).unwrap(),
+            serial: 2024010101,
+            refresh: 3 * 3600,
+            retry: 900,
+            expire: 7 * 86400,
+            minimum: 300,
+        }
+    );
+}
+
+#[test]
+fn names_and_ttls_follow_the_directives() {
+    let zone = zone_file::parse_zone(MASTER_FILE).unwrap();
+    // A record without an owner belongs to the one before it.
+    assert_eq!(
+        records(&zone, "example.test."),
+        [
+            "example.test. 3600 IN SOA ns1.example.test. hostmaster.example.test. 2024010101 10800 900 604800 300",
+            "example.test. 3600 IN NS ns1.example.test.",
+        ]
+    );
+    // A TTL given on a record is for that record alone once $TTL is set.
+    // Source: https://www.rfc-editor.org/rfc/rfc2308#section-4
+    assert_eq!(
+        records(&zone, "www.example.test."),
+        [
+            "www.example.test. 300 IN A 192.0.2.80",
+            "www.example.test. 3600 IN A 192.0.2.81",
+        ]
+    );
+    assert_eq!(
+        records(&zone, "mail.example.test."),
+        ["mail.example.test. 172800 IN MX 10 mail.example.org."]
+    );
+    // A new $ORIGIN is relative to the one before it.
+    assert_eq!(
+        records(&zone, "printer.lab.example.test."),
+        ["printer.lab.example.test. 172800 IN A 192.0.2.9"]
+    );
+    assert_eq!(
+        records(&zone, "lab.example.test."),
+        ["lab.example.test. 172800 IN TXT \"lab (not \\\"parsed\\\")\""]
+    );
+}
+
+#[test]
+fn errors_name_their_line() {
+    let error = |contents: &str| zone_file::parse_zone(contents).unwrap_err().to_string();
+    assert!(
+        error("example. IN SOA ns1.example. hostmaster.example. 1 2 3 4 5\n")
+            .starts_with("line 1: no TTL given")
+    );
+    assert!(error("$TTL 1h\n\nwww A 192.0.2.1\n").starts_with("line 3: relative name"));
+    assert!(
+        error("$ORIGIN example.\n$TTL 1h\n@ SOA ns1 hostmaster ( 1 2\n  3 4 5\n")
+            .starts_with("line 3: unbalanced '('")
+    );
+    assert!(error("$ORIGIN example.\n$TTL soon\n").starts_with("line 2: $TTL without"));
+    assert!(error("$INCLUDE other.zone\n").starts_with("line 1: $INCLUDE"));
+}